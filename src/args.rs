@@ -5,18 +5,147 @@
 // There is no theoretical upper limit on the number of cats in the world, and even the practical one exceeds the buffer capacity of the Windows terminal.
 // To that end in a real project I would have added pagination, a result cap, compressed formatting when it's approached, and a flag to exceed it on purpose.
 
-use anyhow::{Error, Result};
+use crate::cmds::CAT_RECORD_COLUMNS;
+use crate::query::Query;
+use anyhow::{anyhow, bail, Error, Result};
+use regex::Regex;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::env;
+use std::fmt::{self, Display, Formatter};
 use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::clap::AppSettings;
 
 /// A simple command-line interface to the cats registry.
 #[derive(Debug, StructOpt)]
 pub struct Args {
     #[structopt(subcommand)]
     pub cmd: Cmd,
-    /// Whether the output should be in JSON format.
-    #[structopt(long, short)]
+    /// How to show the output: table, plain, plain-labeled, json, csv, tsv, toml, markdown, or html.
+    ///
+    /// Defaults to the config file's format, or else a table in a terminal and plain text
+    /// everywhere else.
+    #[structopt(long, value_name = "format")]
+    pub format: Option<OutputFormat>,
+    /// Shorthand for `--format json`.
+    #[structopt(long, short, conflicts_with = "format")]
     pub json: bool,
+    /// Writes tab-separated records ending in NULs instead of newlines, for `xargs -0`.
+    ///
+    /// Unlike with `--format tsv`, line breaks in names and notes are left as they are.
+    #[structopt(long, short = "0", conflicts_with = "json")]
+    pub print0: bool,
+    /// When to color tables: auto, always, or never.
+    ///
+    /// `auto` colors them in a terminal, unless the `NO_COLOR` environment variable is set.
+    #[structopt(long, value_name = "when", default_value = "auto")]
+    pub color: ColorChoice,
+    /// How to draw tables: ascii, unicode, borderless, or compact.
+    ///
+    /// Defaults to the config file's table style, or else ascii.
+    #[structopt(long, value_name = "style")]
+    pub table_style: Option<TableStyle>,
+    /// The language to print in: en, es, or de.
+    ///
+    /// Defaults to the language of the locale in `LC_ALL`, `LC_MESSAGES`, or `LANG`, or else English.
+    /// Errors and help are only in English so far.
+    #[structopt(long, value_name = "lang")]
+    pub lang: Option<Lang>,
+    /// Lets tables be wider than the terminal, instead of cutting long text short to fit.
+    #[structopt(long)]
+    pub no_truncate: bool,
+    /// Prints tables straight to the terminal, instead of through `$PAGER` when they don't fit.
+    #[structopt(long)]
+    pub no_pager: bool,
+    /// Prints how long opening the registry, running the command and its SQL, and printing the
+    /// result took to stderr, along with how many rows the SQL returned.
+    ///
+    /// Tables aren't paged with this, so that how long they took to print doesn't include reading
+    /// them.
+    #[structopt(long)]
+    pub timing: bool,
+    /// Writes the result to a file instead of stdout. The file is only replaced once all of it has
+    /// been written.
+    #[structopt(long, short, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+    /// A template to render each result with instead, e.g. `{{name}} ({{age}}) - {{breed}}`.
+    ///
+    /// It's a Handlebars template over the result's JSON: `{{field}}` is replaced with that field,
+    /// `{{a.b}}` looks inside a field, and there are `{{#if}}`, `{{#each}}`, and the rest. Nothing
+    /// is HTML-escaped unless it's written `{{escape field}}`. Unknown fields render as nothing,
+    /// and `\n` and `\t` stand for a line break and a tab.
+    #[structopt(long, conflicts_with_all = &["format", "json", "print0"])]
+    pub template: Option<String>,
+    /// A file holding a template to render each result with, like `--template`.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["template", "format", "json", "print0"]
+    )]
+    pub template_file: Option<PathBuf>,
+    /// Which columns to print cats with, and in what order, e.g. `id,name,age`.
+    ///
+    /// The columns are named like they are in CSV, and custom fields like `fields.floof-level`.
+    /// Results other than cats are printed as usual.
+    #[structopt(
+        long,
+        value_name = "columns",
+        require_delimiter = true,
+        conflicts_with_all = &["json", "template", "template-file"]
+    )]
+    pub fields: Option<Vec<Column>>,
+    /// The path to the registry database.
+    ///
+    /// Defaults to `cat_registry.db` in the platform's data directory, e.g. `$XDG_DATA_HOME/cats`.
+    /// Use `:memory:` for a temporary registry that is thrown away afterwards.
+    /// A `libsql://` or `https://` URL uses a registry hosted on a libsql server such as Turso,
+    /// which requires cats to be built with the remote feature. Remote registries only support
+    /// `add`, `get`, `find`, `search`, `update --id`, and `delete` with a single `--id`.
+    #[structopt(long, env = "CATS_DB", parse(from_os_str))]
+    pub db: Option<PathBuf>,
+    /// How the registry is stored: sqlite, or json for a single human-readable file.
+    ///
+    /// With json, the default registry is `cat_registry.json` instead.
+    #[structopt(long, env = "CATS_BACKEND", default_value = "sqlite")]
+    pub backend: Backend,
+    /// A file produced by `export` to import before running the command.
+    ///
+    /// Mostly useful with `--db :memory:`, to run commands against a known set of cats.
+    #[structopt(long, parse(from_os_str))]
+    pub seed: Option<PathBuf>,
+    /// The key to decrypt the registry with, if it is encrypted.
+    ///
+    /// New registries created with a key are encrypted with it. Prefer setting this through the environment, since other users can see command lines.
+    /// Requires cats to be built with the sqlcipher feature.
+    #[structopt(long, env = "CATS_KEY", hide_env_values = true)]
+    pub key: Option<String>,
+    /// The auth token for a remote registry.
+    #[structopt(long, env = "CATS_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// Whether to open the registry read-only, refusing any command that would change it.
+    #[structopt(long, conflicts_with = "seed")]
+    pub read_only: bool,
+    /// Runs `add`, `update`, `delete`, `import`, or a `batch` script and prints what it changed,
+    /// then undoes it all.
+    ///
+    /// The same SQL runs as usual, so anything that would fail still does. Every command in a
+    /// script is undone, not just those four. Bulk deletes don't ask for confirmation with this.
+    /// Only works with local registries.
+    #[structopt(long, conflicts_with = "read-only")]
+    pub dry_run: bool,
+    /// The name of a profile from the config file whose registry should be used instead.
+    ///
+    /// Takes precedence over `--db`.
+    #[structopt(long, short, env = "CATS_PROFILE")]
+    pub profile: Option<String>,
+    /// The path to the config file.
+    ///
+    /// Defaults to `config.toml` in the platform's config directory, e.g. `~/.config/cats`.
+    #[structopt(long, env = "CATS_CONFIG", parse(from_os_str))]
+    pub config: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -36,100 +165,2835 @@ pub enum Cmd {
         #[structopt(flatten)]
         cmd: CmdFind,
     },
+    /// Searches the names, breeds, and notes of every cat for words, best matches first.
+    ///
+    /// `tab*` matches words starting with tab, quotes match a whole phrase, and AND, OR, and NOT
+    /// combine searches.
+    ///
+    /// Searches made with `find` options can also be saved under a name, and run again later.
+    #[structopt(setting = AppSettings::ArgRequiredElseHelp)]
+    Search {
+        /// What to search for.
+        #[structopt(long, short)]
+        query: Option<String>,
+        #[structopt(subcommand)]
+        cmd: Option<CmdSearch>,
+    },
     /// Gets a cat or set of cats by ID.
     Get {
         /// The ID of the cat. May be specified multiple times.
-        #[structopt(long, short, use_delimiter = true)]
+        #[structopt(long, short, use_delimiter = true, required_unless = "microchip")]
         id: Vec<u64>,
+        /// The microchip number of the cat, instead of its ID.
+        #[structopt(long, conflicts_with = "id")]
+        microchip: Option<Microchip>,
+        /// Whether to exit with code 2 if none of the cats exist.
+        #[structopt(long)]
+        fail_if_empty: bool,
+        /// Whether to draw each cat's first photo below it, in terminals that can show images.
+        ///
+        /// That's Kitty, Ghostty, WezTerm, iTerm2, and VS Code. Sixel isn't supported, so xterm and
+        /// the like get the photo's name instead, as do other terminals. HTML puts the photo in
+        /// the table.
+        #[structopt(long)]
+        show_photo: bool,
     },
     /// Update a cat's information.
     Update {
         #[structopt(flatten)]
         cmd: CmdUpdate,
     },
-    /// Removes a cat from the registry.
+    /// Moves a cat to the trash.
+    ///
+    /// Trashed cats are hidden from every command except `find --deleted`, and can be brought
+    /// back with `restore`. The cats are listed first and you're asked to confirm it. Every cat
+    /// matching the `--where-` options can be deleted at once.
     Delete {
-        /// The ID of the cat to remove.
+        /// The ID of the cat to remove. May be specified multiple times, and if any of them don't
+        /// exist then none are removed.
+        #[structopt(long, short, use_delimiter = true, required_unless_one = CatFilter::OPTIONS)]
+        id: Vec<u64>,
+        #[structopt(flatten)]
+        filter: CatFilter,
+        /// Whether to permanently remove the cat instead, whether or not it is in the trash.
+        #[structopt(long)]
+        purge: bool,
+        /// Whether to delete without asking first, when there's a terminal to ask on.
+        ///
+        /// Deleting with the `--where-` options needs this when there isn't.
+        #[structopt(long, short)]
+        yes: bool,
+    },
+    /// Takes a cat back out of the trash, or restores the registry from a backup.
+    ///
+    /// Backups are checked for corruption and compatibility before anything is changed.
+    Restore {
+        /// The ID of the cat to restore.
+        #[structopt(long, short, required_unless = "file")]
+        id: Option<u64>,
+        /// A file produced by `backup` to restore the registry from.
+        #[structopt(long, short, parse(from_os_str), conflicts_with = "id")]
+        file: Option<PathBuf>,
+        /// Whether to replace the registry even if it already contains cats.
+        #[structopt(long, requires = "file")]
+        force: bool,
+        /// Whether to add the backup's cats to the registry instead of replacing it.
+        ///
+        /// Cats whose IDs are already taken are skipped.
+        #[structopt(long, requires = "file", conflicts_with = "force")]
+        merge: bool,
+    },
+    /// Shows the log of changes made to a cat.
+    History {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+    },
+    /// Reverts the most recent change that hasn't already been undone.
+    ///
+    /// Undoing is itself recorded in the history, so running this repeatedly steps further back.
+    Undo {
+        /// The number of a specific history entry to revert instead.
+        #[structopt(long, short)]
+        id: Option<u64>,
+    },
+    /// Records what a cat weighed on a given day.
+    ///
+    /// The cat's weight becomes the most recent one recorded, which this might not be.
+    Weigh {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// What the cat weighed, e.g. 4.3kg or 9.5lb.
+        #[structopt(long, short)]
+        weight: Weight,
+        /// The day the cat was weighed, e.g. 2024-01-31. Defaults to today.
+        #[structopt(long)]
+        date: Option<Date>,
+    },
+    /// Shows every weight recorded for a cat, oldest first, and how much it changed each time.
+    Weights {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+    },
+    /// Shows a cat's family tree: its parents and their parents, and its kittens and theirs.
+    Pedigree {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// How many generations to go back and forward.
+        #[structopt(long, short, default_value = "3")]
+        depth: u32,
+    },
+    /// Keeps a log of how each cat came to the shelter and how it left, for shelter statistics.
+    Event {
+        #[structopt(subcommand)]
+        cmd: CmdEvent,
+    },
+    /// Keeps track of vaccinations, vet visits, and other medical care.
+    Medical {
+        #[structopt(subcommand)]
+        cmd: CmdMedical,
+    },
+    /// Keeps track of what each cat is fed, and how much.
+    Feeding {
+        #[structopt(subcommand)]
+        cmd: CmdFeeding,
+    },
+    /// Lists the licenses and rabies vaccinations that have run out or soon will, soonest first.
+    Expiring {
+        /// How far ahead to look, e.g. 30d or 3mo.
+        #[structopt(long, short, value_name = "period", default_value = "30d")]
+        within: Period,
+    },
+    /// Keeps track of the medications cats are on.
+    Meds {
+        #[structopt(subcommand)]
+        cmd: CmdMeds,
+    },
+    /// Manages the people cats belong to.
+    Owner {
+        #[structopt(subcommand)]
+        cmd: CmdOwner,
+    },
+    /// Marks a cat as adopted by an owner, who becomes the cat's owner.
+    Adopt {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The ID of the owner adopting it, as shown by `owner find`.
+        #[structopt(long, short)]
+        owner: u64,
+        /// The day it was adopted, e.g. 2024-01-31. Defaults to today.
+        #[structopt(long)]
+        date: Option<Date>,
+    },
+    /// Marks an adopted cat as returned to the shelter, leaving it without an owner.
+    Return {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+    },
+    /// Records that a cat has died. It stays in the registry, but `find` leaves it out by default.
+    MarkDeceased {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The day it died, e.g. 2024-01-31. Defaults to today.
+        #[structopt(long)]
+        date: Option<Date>,
+    },
+    /// Manages the rooms, kennels, and foster homes cats can be in.
+    Location {
+        #[structopt(subcommand)]
+        cmd: CmdLocation,
+    },
+    /// Moves a cat to a room, kennel, or foster home.
+    Move {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The name of the location, as shown by `location list`.
+        #[structopt(long, short, value_name = "location")]
+        to: String,
+    },
+    /// Gives a cat to an owner, or takes it away from its owner.
+    Assign {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The ID of the owner, as shown by `owner find`.
+        #[structopt(long, short, required_unless = "unassign")]
+        owner: Option<u64>,
+        /// Whether to leave the cat without an owner instead.
+        #[structopt(long, conflicts_with = "owner")]
+        unassign: bool,
+    },
+    /// Records which kittens were born together, and to which parents.
+    Litter {
+        #[structopt(subcommand)]
+        cmd: CmdLitter,
+    },
+    /// Lists a cat's parents, siblings, littermates, kittens, and the cats it's bonded with.
+    Related {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+    },
+    /// Marks two cats as bonded, so that they are adopted together.
+    Bond {
+        /// The ID of one of the cats.
+        #[structopt(long, short)]
+        id: u64,
+        /// The ID of the other cat.
         #[structopt(long, short)]
+        with: u64,
+        /// Whether to mark them as no longer bonded instead.
+        #[structopt(long)]
+        remove: bool,
+    },
+    /// Manages photos of the cats, which are kept in the registry itself.
+    Photo {
+        #[structopt(subcommand)]
+        cmd: CmdPhoto,
+    },
+    /// Manages documents about the cats, like vet records and adoption contracts.
+    Attachment {
+        #[structopt(subcommand)]
+        cmd: CmdAttachment,
+    },
+    /// Attaches a file to a cat. Short for `attachment add`.
+    Attach {
+        /// The ID of the cat.
         id: u64,
+        /// The file to attach.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// What the file is, e.g. `adoption contract`.
+        #[structopt(long, short)]
+        description: Option<String>,
+        /// The file's MIME type, e.g. application/pdf. Defaults to a guess from its contents and
+        /// extension.
+        #[structopt(long)]
+        mime_type: Option<String>,
+        /// Whether to only keep the path of the file instead of a copy of it.
+        #[structopt(long)]
+        link: bool,
+    },
+    /// Lists the attachments of a cat, or of every cat. Short for `attachment list`.
+    Attachments {
+        /// The ID of the cat. Defaults to every cat.
+        id: Option<u64>,
+    },
+    /// Manages custom fields, which hold whatever else needs to be kept about each cat.
+    ///
+    /// Once defined, a field can be set with `add --set` and `update --set`, and searched with
+    /// `find --where`.
+    Field {
+        #[structopt(subcommand)]
+        cmd: CmdField,
+    },
+    /// Summarizes the cats in the registry: how many there are, how old they are, and their breeds.
+    Stats {
+        /// Summarizes each group of cats separately instead: breed, age, or name-initial.
+        #[structopt(long)]
+        by: Option<GroupBy>,
+        /// Draws bar charts of how many cats are each age and each breed instead.
+        #[structopt(long, conflicts_with = "by")]
+        chart: bool,
+    },
+    /// Lists every breed in the registry, along with how many cats have it.
+    ///
+    /// Breeds that look like different spellings of the same one, like `Mainecoon` and
+    /// `Maine Coon`, are pointed out, so they can be cleaned up with `update`.
+    Breeds,
+    /// Writes every cat in the registry, including IDs, to a file or stdout.
+    Export {
+        #[structopt(flatten)]
+        cmd: CmdExport,
+    },
+    /// Writes a consistent snapshot of the registry database to a new file.
+    ///
+    /// This is safe to run while the registry is in use.
+    Backup {
+        /// The file to write the backup to.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+        /// Whether to replace the file if it already exists.
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Changes the key an encrypted registry uses.
+    ///
+    /// Requires cats to be built with the sqlcipher feature.
+    Rekey {
+        /// The new key.
+        #[structopt(long, env = "CATS_NEW_KEY", hide_env_values = true)]
+        new_key: String,
+    },
+    /// Adds every cat from another registry, such as one kept at a different location.
+    ///
+    /// Cats whose IDs are already taken get new ones. Cats with the same name, age, and breed as one
+    /// already in the registry are likely duplicates, and are handled according to `--strategy`.
+    Merge {
+        /// The other registry's database file.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+        /// What to do with likely duplicates: ask, skip, or keep-both.
+        ///
+        /// Defaults to asking about each one, or skipping them if stdin isn't a terminal.
+        #[structopt(long)]
+        strategy: Option<MergeStrategy>,
+    },
+    /// Compares the cats in the registry with the ones in another, such as a backup or a synced copy.
+    ///
+    /// Cats are matched up by ID. Changes are shown from this registry to the other one.
+    Diff {
+        /// The other registry's database file.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Checks the registry for corruption, an outdated schema, and impossible values.
+    ///
+    /// Problems are listed with a suggested fix.
+    Doctor {
+        /// Whether to apply the fixes that can't lose any information.
+        #[structopt(long)]
+        fix: bool,
+    },
+    /// Compacts the registry and refreshes its indexes.
+    ///
+    /// With no options, this vacuums, analyzes, and reindexes.
+    Maintenance {
+        #[structopt(flatten)]
+        cmd: CmdMaintenance,
+    },
+    /// Manages named registries, which can be selected with `--profile`.
+    Profile {
+        #[structopt(subcommand)]
+        cmd: CmdProfile,
+    },
+    /// Loads cats from a file produced by `export`.
+    ///
+    /// The whole file is imported in one transaction, so any error leaves the registry untouched.
+    Import {
+        #[structopt(flatten)]
+        cmd: CmdImport,
+    },
+    /// Runs the commands in a script in one transaction, so either all of them take effect or none
+    /// do.
+    ///
+    /// Each line is a command and its options, e.g. `add --name Tom --age 3`, quoted like in a
+    /// shell. Blank lines and lines starting with `#` are skipped. The results are printed once
+    /// the whole script has succeeded, and nothing asks for confirmation unless stdin is a
+    /// terminal. Commands that can't run inside a transaction, like `maintenance`, `backup`, and
+    /// `rekey`, can't be in a script.
+    Batch {
+        /// The script, or - for stdin.
+        #[structopt(long, short, parse(from_os_str), default_value = "-")]
+        file: PathBuf,
     },
 }
 
 #[derive(Debug, StructOpt)]
-pub struct CmdUpdate {
-    /// The ID of the cat to update.
-    #[structopt(long, short)]
-    pub id: u64,
-    /// The cat's new name.
-    #[structopt(long, short)]
-    pub name: Option<String>,
-    /// The cat's new age.
-    #[structopt(long, short)]
-    pub age: Option<u32>,
-    /// The cat's new breed.
+pub struct CmdMaintenance {
+    /// Whether to rebuild the database file, reclaiming the space left behind by deleted cats.
+    #[structopt(long)]
+    pub vacuum: bool,
+    /// Whether to gather the statistics SQLite uses to plan searches.
+    #[structopt(long)]
+    pub analyze: bool,
+    /// Whether to rebuild every index.
+    #[structopt(long)]
+    pub reindex: bool,
+    /// Whether to only do what the registry seems to need.
     ///
-    /// Make sure it's spelled correctly, because cat breeds change too often for the registry to
-    /// know if it is a real breed.
-    #[structopt(long, short)]
-    pub breed: Option<String>,
+    /// This vacuums once a quarter of the file is free space, and analyzes when the statistics are out of date.
+    #[structopt(long, conflicts_with_all = &["vacuum", "analyze", "reindex"])]
+    pub auto: bool,
 }
 
 #[derive(Debug, StructOpt)]
-pub struct CmdFind {
-    /// The name of the cat.
-    #[structopt(long, short, use_delimiter = true)]
-    pub name: Option<Vec<String>>,
-    /// The age of the cat, in years.
-    ///
-    /// You can specify a range, e.g. 5-12
-    #[structopt(long, short, use_delimiter = true)]
-    pub age: Option<Vec<Age>>,
-    /// The breed of the cat.
-    #[structopt(long, short, use_delimiter = true)]
-    pub breed: Option<Vec<String>>,
-    /// Whether to search for cats that don't have a set breed.
-    #[structopt(long, conflicts_with = "breed")]
-    pub no_breed: bool,
-    /// Whether to match the name and breed via fuzzy match.
-    ///
-    /// By default, they will be searched case insensitively but otherwise exact.
-    #[structopt(long, short)]
-    pub fuzzy: bool,
+pub enum CmdProfile {
+    /// Lists every profile.
+    List,
+    /// Adds a profile, or changes which registry an existing one uses.
+    Add {
+        /// The name of the profile.
+        #[structopt(long, short)]
+        name: String,
+        /// The path to the profile's registry database.
+        ///
+        /// Defaults to `<name>.db` in the platform's data directory.
+        #[structopt(long, short, parse(from_os_str))]
+        path: Option<PathBuf>,
+    },
+    /// Removes a profile. Its registry database is left where it is.
+    Remove {
+        /// The name of the profile.
+        #[structopt(long, short)]
+        name: String,
+    },
 }
 
 #[derive(Debug, StructOpt)]
-pub struct CmdAdd {
-    /// The name of the cat.
-    #[structopt(long, short)]
-    pub name: String,
-    /// The age of the cat, in years.
-    #[structopt(long, short)]
-    pub age: u32,
-    /// The breed of the cat.
-    ///
-    /// Make sure it's spelled correctly, because cat breeds change too often for the registry to
-    /// know if it is a real breed.
-    #[structopt(long, short)]
-    pub breed: Option<String>,
+pub enum CmdEvent {
+    /// Records an intake or outcome for a cat.
+    Add {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// Whether the cat came in or left: intake or outcome.
+        #[structopt(long, short)]
+        kind: EventKind,
+        /// How it happened: stray, surrender, or transfer for intakes, and adoption, transfer, or
+        /// euthanasia for outcomes. Either can be other.
+        #[structopt(long = "type", short = "t", value_name = "type")]
+        event_type: EventType,
+        /// The day it happened, e.g. 2024-01-31. Defaults to today.
+        #[structopt(long)]
+        date: Option<Date>,
+        /// Why it happened, e.g. `owner moving abroad`.
+        #[structopt(long, short)]
+        reason: Option<String>,
+    },
+    /// Lists the events of a cat, or of every cat, oldest first.
+    List {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+        /// Only show intakes or only show outcomes.
+        #[structopt(long, short)]
+        kind: Option<EventKind>,
+    },
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub enum Age {
-    Range(RangeInclusive<u32>),
-    Concrete(u32),
+#[derive(Debug, StructOpt)]
+pub enum CmdMedical {
+    /// Records a vaccination, vet visit, or other medical event for a cat.
+    Add {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The kind of event: vaccination, checkup, treatment, surgery, or other.
+        #[structopt(long = "type", short = "t", value_name = "type")]
+        event_type: MedicalType,
+        /// What was given or done, e.g. rabies or dental cleaning.
+        #[structopt(long, short)]
+        name: String,
+        /// The day it happened, e.g. 2024-01-31. Defaults to today.
+        #[structopt(long)]
+        date: Option<Date>,
+        /// The day it is next due, such as for a booster.
+        #[structopt(long)]
+        due: Option<Date>,
+    },
+    /// Lists the medical events of a cat, or of every cat, oldest first.
+    List {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+    },
 }
 
-impl FromStr for Age {
-    type Err = Error;
+#[derive(Debug, StructOpt)]
+pub enum CmdFeeding {
+    /// Sets how much of a food a cat gets, replacing what it got of that food before.
+    Set {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The food, e.g. `kitten kibble`.
+        #[structopt(long, short)]
+        food: String,
+        /// How much to give at each meal, e.g. `1/4 cup` or `half a can`.
+        #[structopt(long, short)]
+        amount: String,
+        /// How many meals of it to give a day.
+        #[structopt(long, short, default_value = "2")]
+        times: u32,
+    },
+    /// Stops giving a cat a food.
+    Remove {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The food.
+        #[structopt(long, short)]
+        food: String,
+    },
+    /// Lists what a cat, or every cat, is fed.
+    List {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+    },
+    /// Lists what to feed today, room by room, for the cats still in the shelter's care.
+    Today,
+}
 
-    fn from_str(s: &str) -> Result<Self> {
-        if let Some(divider) = s.find("-") {
-            let lower = s[..divider].parse::<u32>()?;
-            let upper = s[divider + 1..].parse::<u32>()?;
-            Ok(Self::Range(lower..=upper))
-        } else {
-            let age = s.parse::<u32>()?;
-            Ok(Self::Concrete(age))
-        }
+#[derive(Debug, StructOpt)]
+pub enum CmdMeds {
+    /// Starts a cat on a medication, returning the prescription along with its new ID.
+    Add {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The medication, e.g. amoxicillin.
+        #[structopt(long, short)]
+        drug: String,
+        /// How much to give at a time, e.g. `5 mg` or `half a tablet`.
+        #[structopt(long)]
+        dose: String,
+        /// How often to give it: daily, twice-daily, three-times-daily, four-times-daily,
+        /// every-other-day, weekly, or every-N-days.
+        #[structopt(long, short, default_value = "daily")]
+        frequency: Frequency,
+        /// The first day to give it, e.g. 2024-01-31. Defaults to today.
+        #[structopt(long)]
+        start: Option<Date>,
+        /// The last day to give it. Defaults to giving it until it's stopped.
+        #[structopt(long)]
+        end: Option<Date>,
+    },
+    /// Ends a prescription, so that no more doses of it are due.
+    Stop {
+        /// The ID of the prescription.
+        #[structopt(long, short)]
+        prescription: u64,
+        /// The last day it was given. Defaults to today.
+        #[structopt(long)]
+        end: Option<Date>,
+    },
+    /// Lists the prescriptions of a cat, or of every cat.
+    List {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+        /// Include prescriptions that have ended or not yet started.
+        #[structopt(long, short)]
+        all: bool,
+    },
+    /// Lists the prescriptions with doses due today.
+    Due,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CmdLitter {
+    /// Records a litter, setting the parents of each of its kittens.
+    Record {
+        /// The ID of the mother.
+        #[structopt(long, short, required_unless = "father")]
+        mother: Option<u64>,
+        /// The ID of the father.
+        #[structopt(long, short)]
+        father: Option<u64>,
+        /// The ID of a kitten in the litter. May be specified multiple times.
+        #[structopt(
+            long = "kitten",
+            short,
+            value_name = "id",
+            required = true,
+            number_of_values = 1
+        )]
+        kittens: Vec<u64>,
+        /// The day the litter was born, e.g. 2024-01-31. Kittens without a birthdate get this one.
+        #[structopt(long, short)]
+        born: Option<Date>,
+    },
+    /// Lists litters, oldest first.
+    List {
+        /// Only show the litters of the cat with this ID.
+        #[structopt(long, short)]
+        parent: Option<u64>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CmdLocation {
+    /// Adds a location.
+    Add {
+        /// The name of the location, e.g. `kennel 4` or `Sam's house`.
+        #[structopt(long, short)]
+        name: String,
+    },
+    /// Lists every location, along with how many cats are in it.
+    List,
+    /// Removes a location, which must be empty.
+    Remove {
+        /// The name of the location.
+        #[structopt(long, short)]
+        name: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CmdOwner {
+    /// Adds an owner, returning them along with their new ID.
+    Add {
+        /// The owner's name.
+        #[structopt(long, short)]
+        name: String,
+        /// The owner's email address.
+        #[structopt(long, short)]
+        email: Option<String>,
+        /// The owner's phone number.
+        #[structopt(long, short)]
+        phone: Option<String>,
+    },
+    /// Finds owners, or lists every owner when given no options.
+    Find {
+        /// Text to look for in the owner's name, ignoring case.
+        #[structopt(long, short)]
+        name: Option<String>,
+        /// Text to look for in the owner's email address, ignoring case.
+        #[structopt(long, short)]
+        email: Option<String>,
+    },
+    /// Changes an owner's details.
+    Update {
+        /// The ID of the owner.
+        #[structopt(long, short)]
+        id: u64,
+        /// The owner's new name.
+        #[structopt(long, short)]
+        name: Option<String>,
+        /// The owner's new email address.
+        #[structopt(long, short)]
+        email: Option<String>,
+        /// The owner's new phone number.
+        #[structopt(long, short)]
+        phone: Option<String>,
+    },
+    /// Removes an owner. Their cats are left without an owner.
+    Delete {
+        /// The ID of the owner.
+        #[structopt(long, short)]
+        id: u64,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CmdPhoto {
+    /// Attaches a photo to a cat. PNG, JPEG, GIF, and WebP images are supported.
+    Add {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The image file.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Lists the photos of a cat, or of every cat.
+    List {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+    },
+    /// Removes a photo.
+    Remove {
+        /// The number of the photo, as shown by `photo list`.
+        #[structopt(long, short)]
+        photo: u64,
+    },
+    /// Writes a photo out to a file.
+    Export {
+        /// The number of the photo, as shown by `photo list`.
+        #[structopt(long, short)]
+        photo: u64,
+        /// The file to write the image to.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+        /// Whether to replace the file if it already exists.
+        #[structopt(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CmdAttachment {
+    /// Attaches a file to a cat, such as a PDF from the vet.
+    Add {
+        /// The ID of the cat.
+        #[structopt(long, short)]
+        id: u64,
+        /// The file to attach.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+        /// What the file is, e.g. `adoption contract`.
+        #[structopt(long, short)]
+        description: Option<String>,
+        /// The file's MIME type, e.g. application/pdf. Defaults to a guess from its contents and
+        /// extension.
+        #[structopt(long)]
+        mime_type: Option<String>,
+        /// Whether to only keep the path of the file instead of a copy of it.
+        ///
+        /// This keeps big files out of the registry, but the attachment is lost if the file is
+        /// moved or deleted.
+        #[structopt(long)]
+        link: bool,
+    },
+    /// Lists the attachments of a cat, or of every cat.
+    List {
+        /// The ID of the cat. Defaults to every cat.
+        #[structopt(long, short)]
+        id: Option<u64>,
+    },
+    /// Removes an attachment. Linked files are left where they are.
+    Remove {
+        /// The number of the attachment, as shown by `attachment list`.
+        #[structopt(long, short)]
+        attachment: u64,
+    },
+    /// Writes an attachment out to a file.
+    Export {
+        /// The number of the attachment, as shown by `attachment list`.
+        #[structopt(long, short)]
+        attachment: u64,
+        /// The file to write the attachment to.
+        #[structopt(long, short, parse(from_os_str))]
+        file: PathBuf,
+        /// Whether to replace the file if it already exists.
+        #[structopt(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum CmdField {
+    /// Adds a custom field.
+    Define {
+        /// The name of the field, e.g. `floof-level`.
+        #[structopt(long, short)]
+        name: String,
+        /// The type of the field's values: text, int, number, bool, or date.
+        #[structopt(
+            long = "type",
+            short = "t",
+            value_name = "type",
+            default_value = "text"
+        )]
+        field_type: FieldType,
+    },
+    /// Lists every custom field.
+    List,
+    /// Removes a custom field, along with its value for every cat.
+    Remove {
+        /// The name of the field.
+        #[structopt(long, short)]
+        name: String,
+    },
+}
+
+// Only one of these is ever made, so the size of `Save` doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, StructOpt)]
+pub enum CmdSearch {
+    /// Saves a set of `find` options under a name, replacing any saved search with that name.
+    Save {
+        /// The name to save the search as.
+        #[structopt(long = "as", value_name = "name")]
+        save_as: String,
+        #[structopt(flatten)]
+        cmd: CmdFind,
+    },
+    /// Runs a saved search, as if its options had been passed to `find`.
+    Run {
+        /// The name of the saved search.
+        #[structopt(long, short)]
+        name: String,
+    },
+    /// Lists every saved search.
+    List,
+    /// Removes a saved search.
+    Remove {
+        /// The name of the saved search.
+        #[structopt(long, short)]
+        name: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdImport {
+    /// The file to read from, or - for stdin.
+    #[structopt(long, short, parse(from_os_str))]
+    pub file: PathBuf,
+    /// The format to read, either json or csv.
+    ///
+    /// Defaults to the file's extension, or json if that isn't recognized.
+    #[structopt(long)]
+    pub format: Option<FileFormat>,
+    /// What to do when an imported cat already exists: skip, overwrite, or merge.
+    ///
+    /// Merging keeps the existing cat's values, only filling in the ones it is missing.
+    #[structopt(long, default_value = "skip")]
+    pub on_conflict: OnConflict,
+    /// How to tell whether an imported cat already exists: id, or name-breed.
+    ///
+    /// Cats imported by name and breed are given new IDs.
+    #[structopt(long, default_value = "id")]
+    pub key: ImportKey,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdExport {
+    /// The file to write to. Defaults to stdout.
+    #[structopt(long, short, parse(from_os_str))]
+    pub file: Option<PathBuf>,
+    /// The format to write in: json, csv, or sql.
+    ///
+    /// Defaults to the file's extension, or json if that isn't recognized.
+    /// sql writes a dump of the whole database, including the trash and history, that the sqlite3
+    /// shell can load with `.read`.
+    #[structopt(long)]
+    pub format: Option<FileFormat>,
+}
+
+impl Cmd {
+    /// Whether the command can be run by a `batch` script, all of which runs in one transaction.
+    ///
+    /// SQLite can't vacuum, back up, or rekey inside a transaction, and restoring from a backup
+    /// replaces the whole registry.
+    pub fn batchable(&self) -> bool {
+        !matches!(
+            self,
+            Cmd::Maintenance { .. }
+                | Cmd::Backup { .. }
+                | Cmd::Rekey { .. }
+                | Cmd::Restore { file: Some(_), .. }
+                | Cmd::Profile { .. }
+                | Cmd::Batch { .. }
+        )
+    }
+
+    /// Whether the command changes the contents of the registry.
+    pub fn writes(&self) -> bool {
+        match self {
+            Cmd::Add { .. }
+            | Cmd::Delete { .. }
+            | Cmd::Restore { .. }
+            | Cmd::Undo { .. }
+            | Cmd::Weigh { .. }
+            | Cmd::Import { .. }
+            | Cmd::Rekey { .. }
+            | Cmd::Maintenance { .. }
+            | Cmd::Merge { .. }
+            | Cmd::Attach { .. }
+            | Cmd::Batch { .. } => true,
+            Cmd::Field { cmd } => !matches!(cmd, CmdField::List),
+            Cmd::Owner { cmd } => !matches!(cmd, CmdOwner::Find { .. }),
+            Cmd::Medical { cmd } => matches!(cmd, CmdMedical::Add { .. }),
+            Cmd::Event { cmd } => matches!(cmd, CmdEvent::Add { .. }),
+            Cmd::Meds { cmd } => matches!(cmd, CmdMeds::Add { .. } | CmdMeds::Stop { .. }),
+            Cmd::Assign { .. } | Cmd::Bond { .. } | Cmd::Adopt { .. } | Cmd::Return { .. } => true,
+            Cmd::MarkDeceased { .. } | Cmd::Move { .. } => true,
+            Cmd::Location { cmd } => !matches!(cmd, CmdLocation::List),
+            Cmd::Feeding { cmd } => {
+                matches!(cmd, CmdFeeding::Set { .. } | CmdFeeding::Remove { .. })
+            }
+            Cmd::Litter { cmd } => matches!(cmd, CmdLitter::Record { .. }),
+            Cmd::Photo { cmd } => matches!(cmd, CmdPhoto::Add { .. } | CmdPhoto::Remove { .. }),
+            Cmd::Attachment { cmd } => {
+                matches!(
+                    cmd,
+                    CmdAttachment::Add { .. } | CmdAttachment::Remove { .. }
+                )
+            }
+            Cmd::Doctor { fix } => *fix,
+            Cmd::Update { cmd } => !cmd.explain,
+            Cmd::Search { cmd, .. } => matches!(
+                cmd,
+                Some(CmdSearch::Save { .. }) | Some(CmdSearch::Remove { .. })
+            ),
+            Cmd::Find { .. }
+            | Cmd::Get { .. }
+            | Cmd::History { .. }
+            | Cmd::Weights { .. }
+            | Cmd::Export { .. }
+            | Cmd::Backup { .. }
+            | Cmd::Diff { .. }
+            | Cmd::Stats { .. }
+            | Cmd::Pedigree { .. }
+            | Cmd::Related { .. }
+            | Cmd::Expiring { .. }
+            | Cmd::Attachments { .. }
+            | Cmd::Breeds
+            | Cmd::Profile { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdUpdate {
+    /// The ID of the cat to update.
+    #[structopt(long, short, required_unless_one = CatFilter::OPTIONS)]
+    pub id: Option<u64>,
+    #[structopt(flatten)]
+    pub filter: CatFilter,
+    /// The cat's new name.
+    #[structopt(long, short)]
+    pub name: Option<String>,
+    /// The cat's new age.
+    ///
+    /// This replaces the cat's birthdate, if it has one.
+    #[structopt(long, short)]
+    pub age: Option<u32>,
+    /// The cat's new birthdate, e.g. 2020-05-17, from which its age is kept up to date.
+    #[structopt(long, conflicts_with = "age")]
+    pub birthdate: Option<Date>,
+    /// The cat's new weight, e.g. 4.2kg or 9.3lb.
+    #[structopt(long)]
+    pub weight: Option<Weight>,
+    /// The cat's sex: m, f, or unknown.
+    #[structopt(long)]
+    pub sex: Option<Sex>,
+    /// Marks the cat as spayed or neutered.
+    #[structopt(long)]
+    pub fixed: bool,
+    /// Marks the cat as not spayed or neutered.
+    #[structopt(long, conflicts_with = "fixed")]
+    pub not_fixed: bool,
+    /// The cat's new coat color, e.g. black or orange.
+    #[structopt(long)]
+    pub color: Option<String>,
+    /// The cat's new coat pattern, e.g. tabby or tortoiseshell.
+    #[structopt(long)]
+    pub pattern: Option<String>,
+    /// The cat's new microchip number, which no other cat may have.
+    #[structopt(long, conflicts_with_all = CatFilter::OPTIONS)]
+    pub microchip: Option<Microchip>,
+    /// The cat's new notes, which replace any it already has.
+    #[structopt(long)]
+    pub notes: Option<String>,
+    /// What the cat can't or shouldn't eat, e.g. `renal diet, no chicken`.
+    #[structopt(long)]
+    pub diet: Option<String>,
+    /// The cat's new license number, from wherever it's registered.
+    #[structopt(long)]
+    pub license_number: Option<String>,
+    /// The day the cat's license runs out, e.g. 2025-06-30.
+    #[structopt(long)]
+    pub license_expires: Option<Date>,
+    /// A line to add to the end of the cat's notes, which is dated today.
+    #[structopt(long, conflicts_with = "notes")]
+    pub append_notes: Option<String>,
+    /// Where the cat now is in the adoption process: intake, available, or fostered.
+    ///
+    /// Adopted and returned cats are marked as such by `adopt` and `return`.
+    #[structopt(long, possible_values = &["intake", "available", "fostered"])]
+    pub status: Option<Status>,
+    /// Marks the cat as getting along with dogs.
+    #[structopt(long)]
+    pub good_with_dogs: bool,
+    /// Marks the cat as not getting along with dogs.
+    #[structopt(long, conflicts_with = "good-with-dogs")]
+    pub not_good_with_dogs: bool,
+    /// Marks the cat as getting along with children.
+    #[structopt(long)]
+    pub good_with_kids: bool,
+    /// Marks the cat as not getting along with children.
+    #[structopt(long, conflicts_with = "good-with-kids")]
+    pub not_good_with_kids: bool,
+    /// How energetic the cat is, from 1 for a couch potato to 5 for a whirlwind.
+    #[structopt(long, possible_values = &["1", "2", "3", "4", "5"])]
+    pub energy: Option<u8>,
+    /// Marks the cat as using a litter box.
+    #[structopt(long)]
+    pub litter_trained: bool,
+    /// Marks the cat as not using a litter box yet.
+    #[structopt(long, conflicts_with = "litter-trained")]
+    pub not_litter_trained: bool,
+    /// A custom field to set, e.g. `floof-level=11`. May be specified multiple times.
+    #[structopt(long, value_name = "field=value", number_of_values = 1)]
+    pub set: Vec<Assignment>,
+    /// A custom field to clear. May be specified multiple times.
+    #[structopt(long, value_name = "field", number_of_values = 1)]
+    pub unset: Vec<String>,
+    /// The cat's new breed.
+    ///
+    /// Make sure it's spelled correctly, because cat breeds change too often for the registry to
+    /// know if it is a real breed.
+    #[structopt(long, short)]
+    pub breed: Option<String>,
+    /// Whether to show the SQL that would make the change and how SQLite would run it, instead of
+    /// making it.
+    #[structopt(long, conflicts_with_all = CatFilter::OPTIONS)]
+    pub explain: bool,
+    /// Whether to print only the cat's ID, for passing to other commands.
+    #[structopt(long, short, conflicts_with = "explain")]
+    pub quiet: bool,
+    /// Whether to update the cats picked by the `--where-` options without asking first, when
+    /// there's a terminal to ask on.
+    #[structopt(long, short)]
+    pub yes: bool,
+}
+
+// Which cats `update` and `delete` change, instead of `--id`. This is flattened into both
+// commands, so a doc comment here would replace their own descriptions in `--help`.
+#[derive(Debug, Default, StructOpt)]
+pub struct CatFilter {
+    /// Picks the cats with this ID, or in this range, e.g. 100-200.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_id: Option<Vec<Span<u64>>>,
+    /// Picks the cats with this name.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_name: Option<Vec<String>>,
+    /// Picks the cats of this age, or in this range, e.g. 5-12.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_age: Option<Vec<Age>>,
+    /// Picks the cats of this breed.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_breed: Option<Vec<String>>,
+    /// Picks the cats that don't have a set breed.
+    #[structopt(long, conflicts_with_all = &["id", "where-breed"])]
+    pub where_no_breed: bool,
+    /// Picks the cats of this sex: m, f, or unknown.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_sex: Option<Vec<Sex>>,
+    /// Picks the cats with this coat color.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_color: Option<Vec<String>>,
+    /// Picks the cats with this coat pattern.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_pattern: Option<Vec<String>>,
+    /// Picks the cats with the owner with this ID.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_owner: Option<Vec<u64>>,
+    /// Picks the cats in this room, kennel, or foster home.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_location: Option<Vec<String>>,
+    /// Picks the cats at this point in the adoption process.
+    #[structopt(long, use_delimiter = true, conflicts_with = "id")]
+    pub where_status: Option<Vec<Status>>,
+    /// Picks the cats meeting this condition on a custom field, e.g. `floof-level>=10`.
+    /// May be specified multiple times.
+    #[structopt(
+        long,
+        value_name = "condition",
+        number_of_values = 1,
+        conflicts_with = "id"
+    )]
+    pub where_field: Option<Vec<Condition>>,
+    /// Picks the cats matching this query, like `find --query`.
+    #[structopt(long, conflicts_with = "id")]
+    pub where_query: Option<Query>,
+    /// How `--where-name` and `--where-breed` are matched: exact, prefix, suffix, or contains.
+    ///
+    /// Defaults to exact, whatever the config file says for `find`.
+    #[structopt(long, value_name = "mode")]
+    pub where_match: Option<MatchMode>,
+}
+
+impl CatFilter {
+    /// Every option that picks cats, any of which can stand in for `--id`.
+    pub const OPTIONS: &'static [&'static str] = &[
+        "where-id",
+        "where-name",
+        "where-age",
+        "where-breed",
+        "where-no-breed",
+        "where-sex",
+        "where-color",
+        "where-pattern",
+        "where-owner",
+        "where-location",
+        "where-status",
+        "where-field",
+        "where-query",
+    ];
+
+    /// The search for the cats to change.
+    pub fn into_find(self) -> CmdFind {
+        CmdFind {
+            id: self.where_id,
+            name: self.where_name,
+            age: self.where_age,
+            breed: self.where_breed,
+            no_breed: self.where_no_breed,
+            sex: self.where_sex,
+            color: self.where_color,
+            pattern: self.where_pattern,
+            owner: self.where_owner,
+            location: self.where_location,
+            status: self.where_status,
+            conditions: self.where_field,
+            query: self.where_query,
+            match_mode: self.where_match,
+            ..CmdFind::default()
+        }
+    }
+}
+
+// Saved searches are stored as JSON, and older ones lack any options added since.
+#[derive(Debug, Default, StructOpt, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CmdFind {
+    /// The ID of the cat.
+    ///
+    /// You can specify a range, e.g. 100-200
+    #[structopt(long, short, use_delimiter = true)]
+    pub id: Option<Vec<Span<u64>>>,
+    /// The name of the cat.
+    #[structopt(long, short, use_delimiter = true)]
+    pub name: Option<Vec<String>>,
+    /// The age of the cat, in years.
+    ///
+    /// You can specify a range, e.g. 5-12
+    #[structopt(long, short, use_delimiter = true)]
+    pub age: Option<Vec<Age>>,
+    /// The weight of the cat, e.g. 4.2kg.
+    ///
+    /// You can specify a range, e.g. 3kg-5kg or 8lb-10lb
+    #[structopt(long, use_delimiter = true)]
+    pub weight: Option<Vec<Span<Weight>>>,
+    /// The breed of the cat.
+    #[structopt(long, short, use_delimiter = true)]
+    pub breed: Option<Vec<String>>,
+    /// Whether to search for cats that don't have a set breed.
+    #[structopt(long, conflicts_with = "breed")]
+    pub no_breed: bool,
+    /// The sex of the cat: m, f, or unknown.
+    #[structopt(long, use_delimiter = true)]
+    pub sex: Option<Vec<Sex>>,
+    /// Whether to search for cats that have been spayed or neutered.
+    #[structopt(long)]
+    pub fixed: bool,
+    /// Whether to leave out cats that have been spayed or neutered.
+    #[structopt(long, conflicts_with = "fixed")]
+    pub not_fixed: bool,
+    /// The color of the cat's coat.
+    #[structopt(long, use_delimiter = true)]
+    pub color: Option<Vec<String>>,
+    /// The pattern of the cat's coat.
+    #[structopt(long, use_delimiter = true)]
+    pub pattern: Option<Vec<String>>,
+    /// The number of the cat's microchip.
+    #[structopt(long, use_delimiter = true)]
+    pub microchip: Option<Vec<Microchip>>,
+    /// The ID of the cat's owner. May be specified multiple times.
+    #[structopt(long, use_delimiter = true)]
+    pub owner: Option<Vec<u64>>,
+    /// Whether to only show cats that have at least one photo.
+    #[structopt(long)]
+    pub has_photo: bool,
+    /// The name of the room, kennel, or foster home the cat is in. May be specified multiple times.
+    #[structopt(long, use_delimiter = true)]
+    pub location: Option<Vec<String>>,
+    /// Only show cats that came in this way: stray, surrender, transfer, or other.
+    #[structopt(long, value_name = "type", use_delimiter = true)]
+    pub intake: Option<Vec<EventType>>,
+    /// Only show cats that left this way: adoption, transfer, euthanasia, or other.
+    #[structopt(long, value_name = "type", use_delimiter = true)]
+    pub outcome: Option<Vec<EventType>>,
+    /// Where the cat is in the adoption process: intake, available, fostered, adopted, or
+    /// returned.
+    #[structopt(long, use_delimiter = true)]
+    pub status: Option<Vec<Status>>,
+    /// Whether to only show cats known to get along with dogs.
+    #[structopt(long)]
+    pub good_with_dogs: bool,
+    /// Whether to only show cats known to get along with children.
+    #[structopt(long)]
+    pub good_with_kids: bool,
+    /// How energetic the cat is, from 1 to 5.
+    ///
+    /// You can specify a range, e.g. 1-3
+    #[structopt(long, use_delimiter = true)]
+    pub energy: Option<Vec<Span<u8>>>,
+    /// Whether to only show cats known to use a litter box.
+    #[structopt(long)]
+    pub litter_trained: bool,
+    /// Only show the mother and father of the cat with this ID. May be specified multiple times.
+    #[structopt(long, use_delimiter = true)]
+    pub parent: Option<Vec<u64>>,
+    /// Only show the kittens of the cat with this ID. May be specified multiple times.
+    #[structopt(long, use_delimiter = true)]
+    pub offspring_of: Option<Vec<u64>>,
+    /// A condition on a custom field, e.g. `floof-level>=10`. May be specified multiple times.
+    ///
+    /// Fields can be compared with `=`, `!=`, `<`, `<=`, `>`, and `>=`.
+    #[structopt(long = "where", value_name = "condition", number_of_values = 1)]
+    #[serde(rename = "where")]
+    pub conditions: Option<Vec<Condition>>,
+    /// Text to look for in the name, the breed, the notes, and any other text about the cat, e.g.
+    /// `coon`.
+    ///
+    /// Unlike `--name` and `--breed`, this finds the text anywhere in them, unless `--match` says
+    /// otherwise. May be specified multiple times.
+    #[structopt(long, short)]
+    pub text: Option<Vec<String>>,
+    /// Conditions on the name, age, and breed, combined with AND, OR, NOT, and parentheses.
+    ///
+    /// For example, `age > 3 AND (breed:tabby OR breed:siamese) AND NOT name:Max`.
+    /// Ages can be compared with `:`, `!=`, `<`, `<=`, `>`, and `>=`, names and breeds with `:` and
+    /// `!=`. Values with spaces go in double quotes.
+    #[structopt(long)]
+    pub query: Option<Query>,
+    /// Whether to show cats that match any of the name, age, and breed, instead of all of them.
+    ///
+    /// Exclusions like `--not-name` still apply to every cat.
+    #[structopt(long, conflicts_with = "all")]
+    pub any: bool,
+    /// Whether to only show cats that match all of the name, age, and breed.
+    ///
+    /// This is the default, unless the config file says otherwise.
+    #[structopt(long)]
+    pub all: bool,
+    /// A name to leave out of the results.
+    #[structopt(long, use_delimiter = true)]
+    pub not_name: Option<Vec<String>>,
+    /// An age or range of ages to leave out of the results, e.g. 0-1.
+    #[structopt(long, use_delimiter = true)]
+    pub not_age: Option<Vec<Age>>,
+    /// A breed to leave out of the results. Cats without a breed are still included.
+    #[structopt(long, use_delimiter = true)]
+    pub not_breed: Option<Vec<String>>,
+    /// Only show cats added on or after this date, e.g. 2024-01-31, in UTC.
+    ///
+    /// A time can follow the date, e.g. `2024-01-31 13:45`.
+    #[structopt(long)]
+    pub added_since: Option<Timestamp>,
+    /// Only show cats whose name, age, or breed changed on or after this date, e.g. 2024-01-31.
+    #[structopt(long)]
+    pub updated_since: Option<Timestamp>,
+    /// Only show cats added in the last so many minutes, hours, days, weeks, months, or years.
+    ///
+    /// Written like `30min`, `12h`, `7d`, `2w`, `6mo`, or `1y`.
+    #[structopt(long, value_name = "period", conflicts_with = "added-since")]
+    pub added_within: Option<Period>,
+    /// Only show cats whose name, age, or breed changed in the last so long, e.g. `2w`.
+    #[structopt(long, value_name = "period", conflicts_with = "updated-since")]
+    pub updated_within: Option<Period>,
+    /// Whether to search the trash instead of the registry.
+    #[structopt(long)]
+    pub deleted: bool,
+    /// Whether to include cats that have died, which are left out by default.
+    #[structopt(long, conflicts_with = "deceased-only")]
+    pub include_deceased: bool,
+    /// Whether to only show cats that have died.
+    #[structopt(long)]
+    pub deceased_only: bool,
+    /// How the name and breed are matched: exact, prefix, suffix, or contains. Defaults to exact.
+    ///
+    /// Matching is case insensitive, unless `--case-sensitive` is passed.
+    #[structopt(long = "match", value_name = "mode", conflicts_with_all = &["fuzzy", "regex", "glob"])]
+    pub match_mode: Option<MatchMode>,
+    /// Whether to match names and breeds that contain the given text, like `--match contains`.
+    #[structopt(long, short)]
+    pub fuzzy: bool,
+    /// Matches names and breeds with up to this many typos, e.g. `Whiskres` for `Whiskers` with 2.
+    ///
+    /// A typo is a character that was added, left out, or mistyped. Matching is case insensitive.
+    #[structopt(long, value_name = "n", conflicts_with_all = &["fuzzy", "regex", "glob", "match-mode"])]
+    pub fuzzy_distance: Option<u32>,
+    /// Whether the name and breed are regular expressions, e.g. `^Mr\.?`, to search for.
+    ///
+    /// Matching is case sensitive unless the expression starts with `(?i)`.
+    #[structopt(long, conflicts_with = "fuzzy")]
+    pub regex: bool,
+    /// Whether the name and breed are globs, where `*` matches anything and `?` matches any one
+    /// character, e.g. `Mr*`.
+    ///
+    /// Every other character only matches itself.
+    #[structopt(long, conflicts_with_all = &["fuzzy", "regex"])]
+    pub glob: bool,
+    /// Whether names and breeds have to match the case of the ones given, e.g. `Tom` but not `tom`.
+    #[structopt(long, conflicts_with_all = &["regex", "fuzzy-distance"])]
+    pub case_sensitive: bool,
+    /// The field to sort by: id, name, age, or breed. Defaults to id.
+    #[structopt(long)]
+    pub sort: Option<SortField>,
+    /// Whether to sort from highest to lowest instead.
+    #[structopt(long)]
+    pub desc: bool,
+    /// The most cats to show.
+    #[structopt(long)]
+    pub limit: Option<u64>,
+    /// The number of cats to skip before the first one shown.
+    #[structopt(long)]
+    pub offset: Option<u64>,
+    /// Shows only the N oldest cats, like `--sort age --desc --limit N`.
+    #[structopt(
+        long,
+        value_name = "n",
+        conflicts_with_all = &["sort", "desc", "limit", "page", "count", "youngest", "newest"]
+    )]
+    pub oldest: Option<u64>,
+    /// Shows only the N youngest cats, like `--sort age --limit N`.
+    #[structopt(
+        long,
+        value_name = "n",
+        conflicts_with_all = &["sort", "desc", "limit", "page", "count", "newest"]
+    )]
+    pub youngest: Option<u64>,
+    /// Shows only the N cats added most recently.
+    #[structopt(
+        long,
+        value_name = "n",
+        conflicts_with_all = &["sort", "desc", "limit", "page", "count"]
+    )]
+    pub newest: Option<u64>,
+    /// The page of results to show, starting from 1, where each page has `--limit` cats.
+    #[structopt(long, requires = "limit", conflicts_with = "offset")]
+    pub page: Option<u64>,
+    /// Whether to only show how many cats match.
+    #[structopt(long, short, conflicts_with_all = &["limit", "offset"])]
+    pub count: bool,
+    /// Whether to exit with code 2 if no cats match, after printing the (empty) results.
+    #[structopt(long)]
+    #[serde(skip)]
+    pub fail_if_empty: bool,
+    /// Whether to show each cat's first photo, in terminals that can show images and in HTML.
+    ///
+    /// Sixel isn't supported, so those terminals get the photo's name, as with `get --show-photo`.
+    #[structopt(long, conflicts_with = "count")]
+    #[serde(skip)]
+    pub show_photo: bool,
+    /// Whether to show the SQL for the search and how SQLite would run it, instead of running it.
+    #[structopt(long)]
+    #[serde(skip)]
+    pub explain: bool,
+    /// Whether to print only the IDs of the cats that match, one per line, for passing to other
+    /// commands like `xargs -I{} cats update -i {}`.
+    #[structopt(long, short, conflicts_with_all = &["count", "explain"])]
+    #[serde(skip)]
+    pub quiet: bool,
+    /// Whether to end the table with how many cats there are and their average age.
+    #[structopt(long, conflicts_with_all = &["count", "explain", "show-photo"])]
+    #[serde(skip)]
+    pub summary: bool,
+}
+
+// Cats added with `--from` are read into this too, keyed by the names of the options.
+#[derive(Debug, Clone, StructOpt, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CmdAdd {
+    /// The name of the cat.
+    #[structopt(long, short, required_unless_one = &["from", "cat"])]
+    pub name: Option<String>,
+    /// The age of the cat, in years.
+    ///
+    /// An age goes stale as the cat gets older, so prefer `--birthdate` if you know it.
+    #[structopt(long, short, required_unless_one = &["birthdate", "from", "cat"])]
+    pub age: Option<u32>,
+    /// The day the cat was born, e.g. 2020-05-17, from which its age is kept up to date.
+    #[structopt(long, conflicts_with = "age")]
+    pub birthdate: Option<Date>,
+    /// The weight of the cat, e.g. 4.2kg or 9.3lb.
+    #[structopt(long)]
+    pub weight: Option<Weight>,
+    /// The cat's sex: m, f, or unknown.
+    #[structopt(long, default_value = "unknown")]
+    #[serde(default)]
+    pub sex: Sex,
+    /// Whether the cat has been spayed or neutered.
+    #[structopt(long)]
+    #[serde(default)]
+    pub fixed: bool,
+    /// The color of the cat's coat, e.g. black or orange.
+    #[structopt(long)]
+    pub color: Option<String>,
+    /// The pattern of the cat's coat, e.g. tabby or tortoiseshell.
+    #[structopt(long)]
+    pub pattern: Option<String>,
+    /// The number of the cat's microchip, which no other cat may have.
+    #[structopt(long)]
+    pub microchip: Option<Microchip>,
+    /// Anything else worth knowing about the cat, e.g. its temperament or medical needs.
+    #[structopt(long)]
+    pub notes: Option<String>,
+    /// What the cat can't or shouldn't eat, e.g. `renal diet, no chicken`.
+    #[structopt(long)]
+    pub diet: Option<String>,
+    /// The cat's license number, from wherever it's registered.
+    #[structopt(long)]
+    pub license_number: Option<String>,
+    /// The day the cat's license runs out, e.g. 2025-06-30.
+    #[structopt(long)]
+    pub license_expires: Option<Date>,
+    /// Where the cat is in the adoption process: intake, available, or fostered.
+    ///
+    /// Adopted and returned cats are marked as such by `adopt` and `return`.
+    #[structopt(long, default_value = "intake", possible_values = &["intake", "available", "fostered"])]
+    #[serde(default)]
+    pub status: Status,
+    /// A custom field to set, e.g. `floof-level=11`. May be specified multiple times.
+    #[structopt(long, value_name = "field=value", number_of_values = 1)]
+    #[serde(default)]
+    pub set: Vec<Assignment>,
+    /// The breed of the cat.
+    ///
+    /// Make sure it's spelled correctly, because cat breeds change too often for the registry to
+    /// know if it is a real breed.
+    #[structopt(long, short)]
+    pub breed: Option<String>,
+    /// Whether to print only the new cat's ID, for passing to other commands.
+    #[structopt(long, short)]
+    #[serde(skip)]
+    pub quiet: bool,
+    /// Adds every cat in a file instead, or - for stdin, all at once.
+    ///
+    /// Each cat has the options above as its keys, spelled like `license_number`, e.g.
+    /// `{"name": "Tom", "age": 3, "weight": "4.2kg", "fixed": true}`. In CSV, each is a column
+    /// instead, and custom fields can't be set.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &[
+            "name", "age", "birthdate", "weight", "fixed", "color", "pattern", "microchip",
+            "notes", "diet", "license-number", "license-expires", "set", "breed",
+        ],
+    )]
+    #[serde(skip)]
+    pub from: Option<PathBuf>,
+    /// Adds several cats at once, written like `Tom,3,Siamese` with the breed optional. May be
+    /// specified multiple times.
+    ///
+    /// The age may be a birthdate instead, e.g. `Tom,2020-05-17`. The other options above apply to
+    /// every cat, and if any of them can't be added then none are.
+    #[structopt(
+        long,
+        value_name = "name,age,breed",
+        number_of_values = 1,
+        conflicts_with_all = &["name", "age", "birthdate", "microchip", "from"],
+    )]
+    #[serde(skip)]
+    pub cat: Vec<CompactCat>,
+    /// The format of `--from`: json, ndjson, or csv.
+    ///
+    /// Defaults to the file's extension, or json if that isn't recognized. json is a list of cats,
+    /// while ndjson has one cat per line.
+    #[structopt(long, requires = "from")]
+    #[serde(skip)]
+    pub format: Option<FileFormat>,
+}
+
+impl CmdAdd {
+    /// The cat's age as of today, however it was given.
+    pub fn current_age(&self) -> u32 {
+        match self.birthdate {
+            Some(birthdate) => birthdate.years_until(Date::today()),
+            None => self.age.unwrap_or_default(),
+        }
+    }
+}
+
+/// A cat given to `add --cat`, written like `Tom,3,Siamese`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CompactCat {
+    pub name: String,
+    pub age: Option<u32>,
+    pub birthdate: Option<Date>,
+    pub breed: Option<String>,
+}
+
+impl FromStr for CompactCat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid cat `{}`, expected something like Tom,3,Siamese", s);
+        let mut parts = s.split(',').map(str::trim);
+        let name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(invalid)?;
+        let age = parts.next().ok_or_else(invalid)?;
+        let breed = parts.next().filter(|breed| !breed.is_empty());
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        let (age, birthdate) = match age.parse::<u32>() {
+            Ok(age) => (Some(age), None),
+            Err(_) => (None, Some(age.parse::<Date>().map_err(|_| invalid())?)),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            age,
+            birthdate,
+            breed: breed.map(str::to_string),
+        })
+    }
+}
+
+/// A single number or an inclusive range of them, written like `5` or `5-12`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Span<T> {
+    Range(RangeInclusive<T>),
+    Concrete(T),
+}
+
+pub type Age = Span<u32>;
+
+impl<T: FromStr> FromStr for Span<T>
+where
+    Error: From<T::Err>,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(divider) = s.find("-") {
+            let lower = s[..divider].parse::<T>()?;
+            let upper = s[divider + 1..].parse::<T>()?;
+            Ok(Self::Range(lower..=upper))
+        } else {
+            let value = s.parse::<T>()?;
+            Ok(Self::Concrete(value))
+        }
+    }
+}
+
+impl<T: Display> Display for Span<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Range(range) => write!(f, "{}-{}", range.start(), range.end()),
+            Self::Concrete(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+// Spans are written the same way as on the command line.
+impl<T: Display> Serialize for Span<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, T: FromStr> Deserialize<'de> for Span<T>
+where
+    Error: From<T::Err>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A weight, stored as a whole number of grams no matter what unit it was written in.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Weight {
+    pub grams: u32,
+}
+
+impl FromStr for Weight {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid weight `{}`, expected something like 4.2kg or 9.3lb",
+                s
+            )
+        };
+        let pattern =
+            Regex::new(r"^(\d+(?:\.\d+)?)\s*([a-zA-Z]+)$").expect("the weight pattern is valid");
+        let captures = pattern.captures(s.trim()).ok_or_else(invalid)?;
+        let amount = captures[1].parse::<f64>().map_err(|_| invalid())?;
+        let grams_per_unit = match &*captures[2].to_ascii_lowercase() {
+            "g" => 1.0,
+            "kg" => 1000.0,
+            "lb" | "lbs" => 453.592_37,
+            "oz" => 28.349_523_125,
+            unit => bail!("Unknown unit `{}`, expected kg, g, lb, or oz", unit),
+        };
+        let grams = (amount * grams_per_unit).round();
+        if grams > f64::from(u32::MAX) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            grams: grams as u32,
+        })
+    }
+}
+
+// Weights are always shown in kilograms, however they were entered.
+impl Display for Weight {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}kg", f64::from(self.grams) / 1000.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Weight {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+impl From<Weight> for u64 {
+    fn from(weight: Weight) -> Self {
+        weight.grams.into()
+    }
+}
+
+/// The number on a microchip, without the spaces and dashes scanners and paperwork add.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Microchip(String);
+
+impl Microchip {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Microchip {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let number = s
+            .chars()
+            .filter(|&c| c != ' ' && c != '-')
+            .collect::<String>()
+            .to_ascii_uppercase();
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!(
+                "Invalid microchip number `{}`, expected something like 985112000123456",
+                s
+            );
+        }
+        Ok(Self(number))
+    }
+}
+
+impl Display for Microchip {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Microchip {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Microchip {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A point in time in UTC, stored the way SQLite's `datetime` writes it, e.g. `2024-01-31 13:45:00`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Timestamp(String);
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid date `{}`, expected something like 2024-01-31 or 2024-01-31 13:45",
+                s
+            )
+        };
+        let pattern = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})(?:[ T](\d{2}):(\d{2})(?::(\d{2}))?)?$")
+            .expect("the timestamp pattern is valid");
+        let captures = pattern.captures(s.trim()).ok_or_else(invalid)?;
+        let part = |i: usize| {
+            captures
+                .get(i)
+                .map_or(0, |m| m.as_str().parse().unwrap_or(0))
+        };
+        // The day is checked like any other date, so that there's no February 31st.
+        let date: Date = format!("{}-{}-{}", &captures[1], &captures[2], &captures[3])
+            .parse()
+            .map_err(|_| invalid())?;
+        let (hour, minute, second) = (part(4), part(5), part(6));
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid());
+        }
+        Ok(Self(format!(
+            "{} {:02}:{:02}:{:02}",
+            date, hour, minute, second
+        )))
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A calendar day, e.g. `2020-05-17`, stored the way SQLite's `date` writes it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Date {
+    year: u32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// Today in UTC, which is also what SQLite means by `now`.
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        // Howard Hinnant's `civil_from_days`, where years start in March so leap days come last.
+        let days = seconds / 86_400 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = era * 400 + year_of_era + u64::from(month <= 2);
+        Self {
+            year: year as u32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    /// How many birthdays something born on this day has had by `later`.
+    ///
+    /// Someone born on February 29 has their birthday on March 1 in other years.
+    pub fn years_until(self, later: Date) -> u32 {
+        let years = later.year.saturating_sub(self.year);
+        if (later.month, later.day) < (self.month, self.day) {
+            years.saturating_sub(1)
+        } else {
+            years
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid date `{}`, expected something like 2020-05-17", s);
+        let pattern = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})$").expect("the date pattern is valid");
+        let captures = pattern.captures(s.trim()).ok_or_else(invalid)?;
+        let part = |i: usize| captures[i].parse().unwrap_or(0);
+        let date = Self {
+            year: part(1),
+            month: part(2),
+            day: part(3),
+        };
+        // Every fourth year is a leap year, except for centuries that aren't a multiple of 400.
+        let leap = matches!(
+            (date.year % 4, date.year % 100, date.year % 400),
+            (0, 1.., _) | (_, _, 0)
+        );
+        let days = match date.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if !(1..=12).contains(&date.month) || !(1..=days).contains(&date.day) {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A length of time counting back from now, e.g. `2w`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Period {
+    amount: u32,
+    unit: PeriodUnit,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PeriodUnit {
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+impl Period {
+    /// The period as a modifier for SQLite's `datetime`, e.g. `-14 days`.
+    ///
+    /// SQLite takes care of months and years having different lengths.
+    pub fn modifier(self) -> String {
+        let (amount, unit) = match self.unit {
+            PeriodUnit::Minutes => (self.amount, "minutes"),
+            PeriodUnit::Hours => (self.amount, "hours"),
+            PeriodUnit::Days => (self.amount, "days"),
+            PeriodUnit::Weeks => (self.amount.saturating_mul(7), "days"),
+            PeriodUnit::Months => (self.amount, "months"),
+            PeriodUnit::Years => (self.amount, "years"),
+        };
+        format!("-{} {}", amount, unit)
+    }
+
+    /// The period as a modifier counting forward from now instead, e.g. `+14 days`.
+    pub fn ahead(self) -> String {
+        self.modifier().replacen('-', "+", 1)
+    }
+}
+
+impl FromStr for Period {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let amount = match s[..digits].parse() {
+            Ok(amount) => amount,
+            Err(_) => bail!("Invalid period `{}`, expected something like 7d or 2w", s),
+        };
+        let unit = match s[digits..].trim_start() {
+            "min" => PeriodUnit::Minutes,
+            "h" => PeriodUnit::Hours,
+            "d" => PeriodUnit::Days,
+            "w" => PeriodUnit::Weeks,
+            "mo" => PeriodUnit::Months,
+            "y" => PeriodUnit::Years,
+            unit => bail!(
+                "Unknown unit `{}` in `{}`, expected min, h, d, w, mo, or y",
+                unit,
+                s
+            ),
+        };
+        Ok(Self { amount, unit })
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            PeriodUnit::Minutes => "min",
+            PeriodUnit::Hours => "h",
+            PeriodUnit::Days => "d",
+            PeriodUnit::Weeks => "w",
+            PeriodUnit::Months => "mo",
+            PeriodUnit::Years => "y",
+        };
+        write!(f, "{}{}", self.amount, unit)
+    }
+}
+
+impl Serialize for Period {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Period {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FileFormat {
+    Json,
+    /// One JSON value per line.
+    Ndjson,
+    Csv,
+    Sql,
+}
+
+impl FileFormat {
+    /// Picks the format matching a file's extension, if there is one.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "json" => Ok(Self::Json),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            "sql" => Ok(Self::Sql),
+            _ => bail!("Unknown format `{}`, expected json, ndjson, csv, or sql", s),
+        }
+    }
+}
+
+/// How the results of a command are shown.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Tables meant for people to read.
+    Table,
+    /// One line of space-separated values per result, for shell scripts.
+    Plain,
+    /// A `key: value` line for each field of each result, for screen readers and diffs.
+    #[serde(rename = "plain-labeled")]
+    PlainLabeled,
+    Json,
+    /// CSV with a header row, for spreadsheets.
+    Csv,
+    /// Tab-separated values with a header row, for `cut` and `awk`.
+    Tsv,
+    /// A TOML document, to paste into config files.
+    Toml,
+    /// A GitHub-flavored markdown table, to paste into issues and wikis.
+    Markdown,
+    /// A standalone HTML page with a table, to email or put on a website.
+    Html,
+}
+
+impl OutputFormat {
+    /// The format to use when none was asked for, which depends on whether anyone is watching.
+    pub fn detect() -> Self {
+        if atty::is(atty::Stream::Stdout) {
+            Self::Table
+        } else {
+            Self::Plain
+        }
+    }
+}
+
+/// How tables are drawn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableStyle {
+    /// ASCII borders around and between every row.
+    #[default]
+    Ascii,
+    /// Box-drawing characters instead of ASCII.
+    Unicode,
+    /// No lines at all, only columns lined up, for pasting into chat.
+    Borderless,
+    /// ASCII borders, but not between rows.
+    Compact,
+}
+
+impl FromStr for TableStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "ascii" => Ok(Self::Ascii),
+            "unicode" => Ok(Self::Unicode),
+            "borderless" => Ok(Self::Borderless),
+            "compact" => Ok(Self::Compact),
+            _ => bail!(
+                "Unknown table style `{}`, expected ascii, unicode, borderless, or compact",
+                s
+            ),
+        }
+    }
+}
+
+/// A language that output can be printed in, for `--lang`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Lang {
+    #[default]
+    English,
+    Spanish,
+    German,
+}
+
+impl Lang {
+    /// The language the environment asks for, looked up the same way gettext does, or English if
+    /// it asks for one there's no translation for.
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| locale.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl FromStr for Lang {
+    type Err = Error;
+
+    /// Parses a language code, or a whole locale like `es_MX.UTF-8`, of which only the language
+    /// matters.
+    fn from_str(s: &str) -> Result<Self> {
+        let language = s.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        match &*language.to_ascii_lowercase() {
+            "en" | "c" | "posix" => Ok(Self::English),
+            "es" => Ok(Self::Spanish),
+            "de" => Ok(Self::German),
+            _ => bail!("Unknown language `{}`, expected en, es, or de", s),
+        }
+    }
+}
+
+/// When to color output, for `--color`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output. `NO_COLOR` only turns off colors that weren't asked for outright.
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => {
+                env::var_os("NO_COLOR").unwrap_or_default().is_empty()
+                    && atty::is(atty::Stream::Stdout)
+            }
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => bail!(
+                "Unknown color choice `{}`, expected auto, always, or never",
+                s
+            ),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "table" => Ok(Self::Table),
+            "plain" => Ok(Self::Plain),
+            "plain-labeled" | "labeled" => Ok(Self::PlainLabeled),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "toml" => Ok(Self::Toml),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => bail!(
+                "Unknown format `{}`, expected table, plain, plain-labeled, json, csv, tsv, toml, \
+                markdown, or html",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Backend {
+    Sqlite,
+    Json,
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "sqlite" => Ok(Self::Sqlite),
+            "json" => Ok(Self::Json),
+            _ => bail!("Unknown backend `{}`, expected sqlite or json", s),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    Id,
+    Name,
+    Age,
+    Breed,
+}
+
+impl SortField {
+    pub fn column(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::Age => "age",
+            Self::Breed => "breed",
+        }
+    }
+}
+
+impl FromStr for SortField {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "id" => Ok(Self::Id),
+            "name" => Ok(Self::Name),
+            "age" => Ok(Self::Age),
+            "breed" => Ok(Self::Breed),
+            _ => bail!("Unknown field `{}`, expected id, name, age, or breed", s),
+        }
+    }
+}
+
+/// A column of cats' output, for `--fields`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Column {
+    /// One of `CAT_RECORD_COLUMNS`, by its index.
+    Record(usize),
+    /// A custom field.
+    Field(String),
+}
+
+impl Column {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Record(index) => CAT_RECORD_COLUMNS[*index],
+            Self::Field(field) => field,
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(field) = s.strip_prefix("fields.") {
+            return Ok(Self::Field(field.to_string()));
+        }
+        let column = s.to_ascii_lowercase();
+        match CAT_RECORD_COLUMNS.iter().position(|&name| name == column) {
+            Some(index) => Ok(Self::Record(index)),
+            None => bail!(
+                "Unknown column `{}`, expected one of {}, or `fields.<name>`",
+                s,
+                CAT_RECORD_COLUMNS.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sex {
+    Male,
+    Female,
+    #[default]
+    Unknown,
+}
+
+impl Sex {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Male => "male",
+            Self::Female => "female",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl FromStr for Sex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "m" | "male" => Ok(Self::Male),
+            "f" | "female" => Ok(Self::Female),
+            "unknown" => Ok(Self::Unknown),
+            _ => bail!("Unknown sex `{}`, expected m, f, or unknown", s),
+        }
+    }
+}
+
+impl Display for Sex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where a cat is in the adoption process.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Newly arrived, and not yet ready to be adopted.
+    #[default]
+    Intake,
+    Available,
+    /// Living with a foster family until it's adopted.
+    Fostered,
+    Adopted,
+    /// Adopted, and then brought back.
+    Returned,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Intake => "intake",
+            Self::Available => "available",
+            Self::Fostered => "fostered",
+            Self::Adopted => "adopted",
+            Self::Returned => "returned",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "intake" => Ok(Self::Intake),
+            "available" => Ok(Self::Available),
+            "fostered" => Ok(Self::Fostered),
+            "adopted" => Ok(Self::Adopted),
+            "returned" => Ok(Self::Returned),
+            _ => bail!(
+                "Unknown status `{}`, expected intake, available, fostered, adopted, or returned",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether an event is a cat coming into the shelter or leaving it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Intake,
+    Outcome,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Intake => "intake",
+            Self::Outcome => "outcome",
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "intake" => Ok(Self::Intake),
+            "outcome" => Ok(Self::Outcome),
+            _ => bail!("Unknown event kind `{}`, expected intake or outcome", s),
+        }
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventType {
+    Stray,
+    Surrender,
+    Transfer,
+    Adoption,
+    Euthanasia,
+    Other,
+}
+
+impl EventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stray => "stray",
+            Self::Surrender => "surrender",
+            Self::Transfer => "transfer",
+            Self::Adoption => "adoption",
+            Self::Euthanasia => "euthanasia",
+            Self::Other => "other",
+        }
+    }
+
+    /// Whether this is a way for a cat to come in, if `kind` is an intake, or to leave otherwise.
+    pub fn is_kind(self, kind: EventKind) -> bool {
+        match self {
+            Self::Stray | Self::Surrender => kind == EventKind::Intake,
+            Self::Adoption | Self::Euthanasia => kind == EventKind::Outcome,
+            Self::Transfer | Self::Other => true,
+        }
+    }
+}
+
+impl FromStr for EventType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "stray" => Ok(Self::Stray),
+            "surrender" => Ok(Self::Surrender),
+            "transfer" => Ok(Self::Transfer),
+            "adoption" => Ok(Self::Adoption),
+            "euthanasia" => Ok(Self::Euthanasia),
+            "other" => Ok(Self::Other),
+            _ => bail!(
+                "Unknown event type `{}`, expected stray, surrender, transfer, adoption, \
+                euthanasia, or other",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for EventType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MedicalType {
+    Vaccination,
+    Checkup,
+    Treatment,
+    Surgery,
+    Other,
+}
+
+impl MedicalType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Vaccination => "vaccination",
+            Self::Checkup => "checkup",
+            Self::Treatment => "treatment",
+            Self::Surgery => "surgery",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl FromStr for MedicalType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "vaccination" | "vaccine" => Ok(Self::Vaccination),
+            "checkup" => Ok(Self::Checkup),
+            "treatment" => Ok(Self::Treatment),
+            "surgery" => Ok(Self::Surgery),
+            "other" => Ok(Self::Other),
+            _ => bail!(
+                "Unknown medical event type `{}`, expected vaccination, checkup, treatment, \
+                surgery, or other",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for MedicalType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How often a medication is given: some number of times a day, every so many days.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Frequency {
+    pub times_per_day: u32,
+    pub every_days: u32,
+}
+
+impl FromStr for Frequency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (times_per_day, every_days) = match &*s.to_ascii_lowercase() {
+            "daily" | "once-daily" | "sid" => (1, 1),
+            "twice-daily" | "bid" => (2, 1),
+            "three-times-daily" | "tid" => (3, 1),
+            "four-times-daily" | "qid" => (4, 1),
+            "every-other-day" | "eod" => (1, 2),
+            "weekly" => (1, 7),
+            lower => match lower
+                .strip_prefix("every-")
+                .and_then(|days| days.strip_suffix("-days"))
+                .and_then(|days| days.parse().ok())
+            {
+                Some(days) if days > 0 => (1, days),
+                _ => bail!(
+                    "Unknown frequency `{}`, expected daily, twice-daily, three-times-daily, \
+                    four-times-daily, every-other-day, weekly, or every-N-days",
+                    s
+                ),
+            },
+        };
+        Ok(Self {
+            times_per_day,
+            every_days,
+        })
+    }
+}
+
+impl Display for Frequency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.times_per_day, self.every_days) {
+            (1, 1) => f.write_str("daily"),
+            (2, 1) => f.write_str("twice-daily"),
+            (3, 1) => f.write_str("three-times-daily"),
+            (4, 1) => f.write_str("four-times-daily"),
+            (times, 1) => write!(f, "{} times daily", times),
+            (1, 2) => f.write_str("every-other-day"),
+            (1, 7) => f.write_str("weekly"),
+            (1, days) => write!(f, "every-{}-days", days),
+            (times, days) => write!(f, "{} times every {} days", times, days),
+        }
+    }
+}
+
+/// The type of a custom field's values.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    Text,
+    Int,
+    Number,
+    Bool,
+    Date,
+}
+
+impl FieldType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Int => "int",
+            Self::Number => "number",
+            Self::Bool => "bool",
+            Self::Date => "date",
+        }
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "text" => Ok(Self::Text),
+            "int" => Ok(Self::Int),
+            "number" => Ok(Self::Number),
+            "bool" => Ok(Self::Bool),
+            "date" => Ok(Self::Date),
+            _ => bail!(
+                "Unknown field type `{}`, expected text, int, number, bool, or date",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A value for a custom field, written like `floof-level=11`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Assignment {
+    pub field: String,
+    pub value: String,
+}
+
+impl FromStr for Assignment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((field, value)) if !field.trim().is_empty() => Ok(Self {
+                field: field.trim().to_string(),
+                value: value.trim().to_string(),
+            }),
+            _ => bail!(
+                "Invalid field assignment `{}`, expected something like floof-level=11",
+                s
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Assignment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+        }
+    }
+}
+
+/// A comparison against a custom field, written like `floof-level>=10`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Condition {
+    pub field: String,
+    pub operator: Operator,
+    pub value: String,
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid condition `{}`, expected something like floof-level>=10",
+                s
+            )
+        };
+        let start = s.find(['=', '!', '<', '>']).ok_or_else(invalid)?;
+        let (field, rest) = s.split_at(start);
+        // Two-character operators go first, so `>=` isn't read as `>` followed by `=10`.
+        let operator = [
+            Operator::LessOrEqual,
+            Operator::GreaterOrEqual,
+            Operator::NotEqual,
+            Operator::Equal,
+            Operator::Less,
+            Operator::Greater,
+        ]
+        .iter()
+        .copied()
+        .find(|operator| rest.starts_with(operator.as_str()))
+        .ok_or_else(invalid)?;
+        let (field, value) = (field.trim(), rest[operator.as_str().len()..].trim());
+        if field.is_empty() || value.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            field: field.to_string(),
+            operator,
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.field, self.operator.as_str(), self.value)
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    Exact,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+impl FromStr for MatchMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "exact" => Ok(Self::Exact),
+            "prefix" => Ok(Self::Prefix),
+            "suffix" => Ok(Self::Suffix),
+            "contains" => Ok(Self::Contains),
+            _ => bail!(
+                "Unknown match mode `{}`, expected exact, prefix, suffix, or contains",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GroupBy {
+    Breed,
+    Age,
+    NameInitial,
+}
+
+impl FromStr for GroupBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &*s.to_ascii_lowercase() {
+            "breed" => Ok(Self::Breed),
+            "age" => Ok(Self::Age),
+            "name-initial" => Ok(Self::NameInitial),
+            _ => bail!(
+                "Unknown grouping `{}`, expected breed, age, or name-initial",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OnConflict {
+    Skip,
+    Overwrite,
+    Merge,
+}
+
+impl FromStr for OnConflict {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "merge" => Ok(Self::Merge),
+            _ => bail!(
+                "Unknown strategy `{}`, expected skip, overwrite, or merge",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MergeStrategy {
+    Ask,
+    Skip,
+    KeepBoth,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ask" => Ok(Self::Ask),
+            "skip" => Ok(Self::Skip),
+            "keep-both" => Ok(Self::KeepBoth),
+            _ => bail!("Unknown strategy `{}`, expected ask, skip, or keep-both", s),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImportKey {
+    Id,
+    NameBreed,
+}
+
+impl FromStr for ImportKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "id" => Ok(Self::Id),
+            "name-breed" => Ok(Self::NameBreed),
+            _ => bail!("Unknown key `{}`, expected id or name-breed", s),
+        }
+    }
+}
+
+/// Splits a line of a `batch` script into words like a shell would, so that quoted text like
+/// `--notes "Shy at first"` stays together and a backslash escapes the next character.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("A backslash ends the line"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(quote) = quote {
+        bail!("A {} quote is never closed", quote);
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_normalized() {
+        let timestamp = |s: &str| s.parse::<Timestamp>().map(|t| t.to_string()).ok();
+        assert_eq!(
+            timestamp("2024-01-31").as_deref(),
+            Some("2024-01-31 00:00:00")
+        );
+        assert_eq!(
+            timestamp(" 2024-01-31T13:45 ").as_deref(),
+            Some("2024-01-31 13:45:00")
+        );
+        assert_eq!(
+            timestamp("2024-02-29 23:59:59").as_deref(),
+            Some("2024-02-29 23:59:59")
+        );
+        assert_eq!(timestamp("2024-02-31"), None);
+        assert_eq!(timestamp("2023-02-29"), None);
+        assert_eq!(timestamp("2024-01-31 24:00"), None);
+        assert_eq!(timestamp("2024-01-31 13:60"), None);
+        assert_eq!(timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn dates_only_have_real_days() {
+        let valid = |s: &str| s.parse::<Date>().is_ok();
+        assert!(valid("2024-02-29"));
+        assert!(valid("2000-02-29"));
+        assert!(!valid("2023-02-29"));
+        assert!(!valid("1900-02-29"));
+        assert!(!valid("2024-02-31"));
+        assert!(!valid("2024-04-31"));
+        assert!(valid("2024-12-31"));
+        assert!(!valid("2024-13-01"));
+        assert!(!valid("2024-01-00"));
+        assert!(!valid("2024-1-1"));
+    }
+
+    #[test]
+    fn leap_day_birthdays_are_on_march_first() {
+        let date = |s: &str| s.parse::<Date>().unwrap();
+        let born = date("2020-02-29");
+        assert_eq!(born.years_until(date("2021-02-28")), 0);
+        assert_eq!(born.years_until(date("2021-03-01")), 1);
+        assert_eq!(born.years_until(date("2024-02-29")), 4);
+        assert_eq!(date("2020-05-17").years_until(date("2019-01-01")), 0);
+    }
+
+    #[test]
+    fn today_is_a_valid_date() {
+        let today = Date::today();
+        assert_eq!(today.to_string().parse::<Date>().unwrap(), today);
     }
 }
@@ -7,31 +7,165 @@
 // The module separation is good enough to have a place to put code without having a god-file.
 // However, in a real project I would further separate the modules, so that cmds does not interact with args.
 
-use crate::args::{Age, CmdAdd, CmdFind, CmdUpdate};
-use crate::Printable;
-use anyhow::Result;
-use itertools::Itertools;
+use crate::args::{
+    CatFilter, CmdAdd, CmdAttachment, CmdEvent, CmdExport, CmdFeeding, CmdField, CmdFind,
+    CmdImport, CmdLitter, CmdLocation, CmdMaintenance, CmdMedical, CmdMeds, CmdOwner, CmdPhoto,
+    CmdProfile, CmdSearch, CmdUpdate, Column, Date, EventKind, FieldType, FileFormat, Frequency,
+    GroupBy, ImportKey, MatchMode, MergeStrategy, Microchip, OnConflict, Period, Sex, Span, Status,
+    Weight,
+};
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::store::{
+    atomic, count_stmt, find_stmt, insert, levenshtein, matcher, no_such_field, overwrite, record,
+    record_entry, update_stmt, Action, CatStore, SqliteStore, AGE, CATS_WITH_DETAILS,
+};
+use crate::template::Template;
+use crate::terminal::Style;
+use crate::{migrations, paths, terminal};
+use crate::{Dialect, Printable};
+use anyhow::{anyhow, bail, Context, Error, Result};
+use itertools::{EitherOrBoth, Itertools};
 use prettytable::Table;
-use rusqlite::{Connection, Row, ToSql};
-use std::borrow::Cow;
-use std::fmt::Display;
-use std::io;
-use std::iter;
+use regex::Regex;
+use rusqlite::backup::Progress;
+use rusqlite::types::{Type, Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Connection, DatabaseName, OpenFlags, OptionalExtension, Row};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
 
-pub fn add(conn: &Connection, cmd: CmdAdd) -> Result<Cat> {
-    Ok(conn.query_row(
-        "INSERT INTO cats (name, age, breed) VALUES (?, ?, ?) RETURNING *",
-        params![cmd.name, cmd.age, cmd.breed],
-        Cat::from_row,
-    )?)
+/// The error for a cat ID that isn't in the registry, or has been deleted.
+#[derive(Debug)]
+pub struct NoSuchCat(pub u64);
+
+impl Display for NoSuchCat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "There is no cat with ID {}", self.0)
+    }
+}
+
+impl StdError for NoSuchCat {}
+
+pub fn add(store: &dyn CatStore, cmd: CmdAdd) -> Result<Cat> {
+    check_birthdate(cmd.birthdate)?;
+    store.add(&cmd)
+}
+
+fn check_birthdate(birthdate: Option<Date>) -> Result<()> {
+    match birthdate {
+        Some(birthdate) if birthdate > Date::today() => bail!("{} is in the future", birthdate),
+        _ => Ok(()),
+    }
+}
+
+/// Adds every cat in `--cat` or `--from` in one transaction, so that either all of them are added
+/// or none are.
+pub fn add_all(conn: &Connection, cmd: CmdAdd) -> Result<Vec<Cat>> {
+    let cats = if cmd.cat.is_empty() {
+        read_cats(&cmd)?
+    } else {
+        cmd.cat
+            .iter()
+            .map(|cat| CmdAdd {
+                name: Some(cat.name.clone()),
+                age: cat.age,
+                birthdate: cat.birthdate,
+                breed: cat.breed.clone().or_else(|| cmd.breed.clone()),
+                ..cmd.clone()
+            })
+            .collect()
+    };
+    let store = SqliteStore::new(conn);
+    atomic(conn, || {
+        cats.iter()
+            .enumerate()
+            .map(|(i, cat)| {
+                check_birthdate(cat.birthdate)
+                    .and_then(|_| store.add(cat))
+                    .with_context(|| {
+                        format!(
+                            "Couldn't add cat {}, {}",
+                            i + 1,
+                            cat.name.as_deref().unwrap_or("")
+                        )
+                    })
+            })
+            .collect()
+    })
+}
+
+/// Reads the cats in `--from`.
+fn read_cats(cmd: &CmdAdd) -> Result<Vec<CmdAdd>> {
+    let from = cmd.from.as_deref().unwrap_or_else(|| Path::new("-"));
+    let format = cmd
+        .format
+        .or_else(|| FileFormat::from_path(from))
+        .unwrap_or(FileFormat::Json);
+    let input: Box<dyn BufRead> = if from == Path::new("-") {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(
+            File::open(from).with_context(|| format!("Couldn't read {}", from.display()))?,
+        ))
+    };
+    let mut cats: Vec<CmdAdd> = match format {
+        FileFormat::Json => serde_json::from_reader(input)?,
+        FileFormat::Ndjson => input
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str(&line?).with_context(|| format!("Cat {} is invalid", i + 1))
+            })
+            .collect::<Result<_>>()?,
+        FileFormat::Csv => csv::Reader::from_reader(input)
+            .deserialize()
+            .collect::<csv::Result<_>>()?,
+        FileFormat::Sql => bail!("Cats can only be added from json, ndjson, or csv"),
+    };
+    // These are required on the command line, which can't check the file for them.
+    for (i, cat) in cats.iter_mut().enumerate() {
+        if cat.name.is_none() {
+            bail!("Cat {} has no name", i + 1);
+        }
+        if cat.age.is_some() && cat.birthdate.is_some() {
+            bail!("Cat {} has both an age and a birthdate", i + 1);
+        }
+        if cat.age.is_none() && cat.birthdate.is_none() {
+            bail!("Cat {} has neither an age nor a birthdate", i + 1);
+        }
+        if !matches!(
+            cat.status,
+            Status::Intake | Status::Available | Status::Fostered
+        ) {
+            bail!(
+                "Cat {} can't be added as {}, expected intake, available, or fostered",
+                i + 1,
+                cat.status
+            );
+        }
+        // The config file's default breed was applied to `cmd`, the same as for a single cat.
+        if cat.breed.is_none() {
+            cat.breed = cmd.breed.clone();
+        }
+    }
+    Ok(cats)
 }
 
 impl Printable for Option<Cat> {
-    fn print_display(&self) {
+    fn print_display(&self, style: Style) {
         if let Some(cat) = self {
-            cat.print_display()
+            cat.print_display(style)
         } else {
-            println!("No such cat exists")
+            println!("{}", tr("No such cat exists"))
         }
     }
     fn print_plain(&self) {
@@ -46,190 +180,5584 @@ impl Printable for Option<Cat> {
             println!("{{}}")
         }
     }
+    fn print_toml(&self) {
+        if let Some(cat) = self {
+            cat.print_toml()
+        }
+    }
+    fn cats(&self) -> Option<Vec<&Cat>> {
+        Some(self.iter().collect())
+    }
+    fn print_template(&self, template: &Template) {
+        if let Some(cat) = self {
+            cat.print_template(template)
+        }
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_cats(dialect, self.iter().map(|cat| (cat, None)));
+    }
 }
 
-pub fn delete(conn: &Connection, id: u64) -> Result<Option<Cat>> {
-    let mut stmt = conn.prepare("DELETE FROM cats WHERE id = ? RETURNING *")?;
-    let mut rows = stmt.query_map([id], Cat::from_row)?;
-    Ok(rows.next().transpose()?)
+pub fn delete(store: &dyn CatStore, id: u64, purge: bool, yes: bool) -> Result<Option<Cat>> {
+    if !yes {
+        confirm_delete(store, &[id], purge)?;
+    }
+    store.delete(id, purge)
 }
 
-pub fn get(conn: &Connection, id: &[u64]) -> Result<Vec<Cat>> {
-    let mut stmt = String::from("SELECT * FROM cats WHERE ");
-    stmt.push_str(&id.iter().map(|_| "id = ?").join(" OR "));
-    conn.prepare(&stmt)?
-        .query_map(rusqlite::params_from_iter(id), Cat::from_row)?
-        .map(|res| Ok(res?))
-        .collect()
+/// Deletes several cats in one transaction, or none of them if any don't exist.
+pub fn delete_many(conn: &Connection, ids: &[u64], purge: bool, yes: bool) -> Result<Vec<Cat>> {
+    let store = SqliteStore::new(conn);
+    if !yes {
+        confirm_delete(&store, ids, purge)?;
+    }
+    atomic(conn, || {
+        ids.iter()
+            .map(|&id| {
+                store
+                    .delete(id, purge)?
+                    .ok_or_else(|| anyhow!(NoSuchCat(id)))
+            })
+            .collect()
+    })
 }
 
-pub fn find(conn: &Connection, cmd: CmdFind) -> Result<Vec<Cat>> {
-    let mut params_owned = Vec::new();
-    let mut params = Vec::new();
-    let fuzzy = cmd.fuzzy;
-    let name_clause = cmd.name.map(|names| {
-        let len = names.len();
-        if !fuzzy {
-            params_owned.extend(names);
-            format!(
-                "name IN ({})",
-                iter::repeat("?").take(len).join(", ")
-            )
-        } else {
-            params_owned.extend(names.into_iter().map(|name| format!("%{}%", name)));
-            format!("({})", iter::repeat("name LIKE ?").take(len).join(" OR "))
+/// Deletes every cat matching `filter` in one transaction, once the user agrees to it.
+pub fn delete_all(
+    conn: &Connection,
+    filter: CatFilter,
+    purge: bool,
+    yes: bool,
+) -> Result<Vec<Cat>> {
+    let store = SqliteStore::new(conn);
+    let cats = store.find(&filter.into_find())?;
+    if cats.is_empty() {
+        return Ok(cats);
+    }
+    if !yes {
+        // Unlike deleting by ID, a filter can match far more cats than expected, so this is never
+        // done without asking.
+        if !atty::is(atty::Stream::Stdin) {
+            bail!(
+                "Deleting {} cats needs --yes, since there's no terminal to confirm it on",
+                cats.len()
+            );
         }
-    });
-    let breed_clause = cmd.breed.map(|breeds| {
-        let len = breeds.len();
-        if !fuzzy {
-            params_owned.extend(breeds);
-            format!(
-                "breed IN ({})",
-                iter::repeat("?").take(len).join(", ")
-            )
+        confirm_cats(
+            &cats,
+            if purge {
+                "Permanently delete"
+            } else {
+                "Delete"
+            },
+        )?;
+    }
+    atomic(conn, || {
+        cats.iter()
+            .filter_map(|cat| store.delete(cat.id, purge).transpose())
+            .collect()
+    })
+}
+
+/// Asks whether to delete the cats with these IDs, if there's a terminal to ask on and any of them
+/// exist.
+fn confirm_delete(store: &dyn CatStore, ids: &[u64], purge: bool) -> Result<()> {
+    if !atty::is(atty::Stream::Stdin) {
+        return Ok(());
+    }
+    let mut cats = store.get(ids)?;
+    if purge {
+        cats.extend(store.find(&CmdFind {
+            id: Some(ids.iter().map(|&id| Span::Concrete(id)).collect()),
+            deleted: true,
+            ..CmdFind::default()
+        })?);
+    }
+    if cats.is_empty() {
+        return Ok(());
+    }
+    confirm_cats(
+        &cats,
+        if purge {
+            "Permanently delete"
         } else {
-            params_owned.extend(breeds.into_iter().map(|breed| format!("%{}%", breed)));
-            format!("({})", iter::repeat("breed LIKE ?").take(len).join(" OR "))
-        }
-    });
-    params.extend(params_owned.iter().map(|x| x as &dyn ToSql));
-    let age_clause = cmd.age.as_ref().map(|ages| {
-        format!(
-            "({})",
-            ages.iter()
-                .map(|age| {
-                    match age {
-                        Age::Concrete(age) => {
-                            params.push(age);
-                            "age = ?"
-                        }
-                        Age::Range(range) => {
-                            params.push(range.start());
-                            params.push(range.end());
-                            "age BETWEEN ? AND ?"
-                        }
-                    }
-                })
-                .join(" OR ")
-        )
-    });
-    let no_breed_clause = cmd.no_breed.then(|| "breed ISNULL");
-    let clauses = [name_clause.as_deref(), age_clause.as_deref(), breed_clause.as_deref(), no_breed_clause]
-        .iter()
-        .flatten()
-        .join(" AND ");
-    let stmt = if clauses.is_empty() {
-        Cow::Borrowed("SELECT * FROM cats")
-    } else {
-        Cow::Owned(format!("SELECT * FROM cats WHERE {}", clauses))
+            "Delete"
+        },
+    )
+}
+
+/// Lists the cats about to be changed and asks whether to go ahead, failing if not.
+fn confirm_cats(cats: &[Cat], action: &str) -> Result<()> {
+    for cat in cats {
+        eprintln!(
+            "#{} {} ({}, {})",
+            cat.id,
+            cat.name,
+            cat.age,
+            cat.breed.as_deref().unwrap_or("no breed")
+        );
+    }
+    let question = match cats {
+        [_] => format!("{} this cat?", action),
+        _ => format!("{} these {} cats?", action, cats.len()),
     };
-    conn.prepare(&stmt)?
-        .query_map(&*params, Cat::from_row)?
-        .map(|res| Ok(res?))
-        .collect()
+    if !confirm(&question)? {
+        bail!("Nothing was changed");
+    }
+    Ok(())
 }
 
-pub fn update(conn: &Connection, cmd: CmdUpdate) -> Result<Option<Cat>> {
-    let mut stmt = String::from("UPDATE cats SET ");
-    let mut params = Vec::new();
-    let name_clause = cmd.name.as_ref().map(|name| {
-        params.push(name as &dyn ToSql);
-        "name = ?"
-    });
-    let age_clause = cmd.age.as_ref().map(|age| {
-        params.push(age);
-        "age = ?"
-    });
-    let breed_clause = cmd.breed.as_ref().map(|breed| {
-        params.push(breed);
-        "breed = ?"
-    });
-    stmt.push_str(
-        &[name_clause, age_clause, breed_clause]
-            .iter()
-            .flatten()
-            .join(", "),
-    );
-    stmt.push_str(" WHERE id = ? RETURNING *");
-    params.push(&cmd.id);
-    let mut stmt = conn.prepare(&stmt)?;
-    let mut rows = stmt.query_map(&*params, Cat::from_row)?;
-    Ok(rows.next().transpose()?)
+pub fn restore(conn: &Connection, id: u64) -> Result<Option<Cat>> {
+    atomic(conn, || {
+        let cat = conn
+            .query_row(
+                "UPDATE cats SET deleted_at = NULL WHERE id = ? AND deleted_at NOTNULL RETURNING *",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?;
+        if let Some(cat) = &cat {
+            record(conn, Action::Restore, None, Some(cat))?;
+        }
+        Ok(cat)
+    })
+}
+
+pub fn get(store: &dyn CatStore, id: &[u64]) -> Result<Vec<Cat>> {
+    store.get(id)
 }
 
 #[derive(Debug, Serialize)]
-pub struct Cat {
+pub struct Event {
+    pub id: u64,
+    pub cat_id: u64,
+    pub kind: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub date: String,
+    pub reason: Option<String>,
+}
+
+impl Event {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            cat_id: row.get("cat_id")?,
+            kind: row.get("kind")?,
+            event_type: row.get("type")?,
+            date: row.get("date")?,
+            reason: row.get("reason")?,
+        })
+    }
+}
+
+pub fn event(conn: &Connection, cmd: CmdEvent) -> Result<Vec<Event>> {
+    match cmd {
+        CmdEvent::Add {
+            id,
+            kind,
+            event_type,
+            date,
+            reason,
+        } => {
+            if !event_type.is_kind(kind) {
+                bail!("A cat can't have an {} of type {}", kind, event_type);
+            }
+            let today = Date::today();
+            let date = date.unwrap_or(today);
+            if date > today {
+                bail!("{} is in the future", date);
+            }
+            atomic(conn, || {
+                conn.query_row(
+                    "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+                let event = conn.query_row(
+                    "INSERT INTO events (cat_id, kind, type, date, reason) VALUES (?, ?, ?, ?, ?) \
+                    RETURNING *",
+                    params![
+                        id,
+                        kind.as_str(),
+                        event_type.as_str(),
+                        date.to_string(),
+                        reason
+                    ],
+                    Event::from_row,
+                )?;
+                Ok(vec![event])
+            })
+        }
+        CmdEvent::List { id, kind } => conn
+            .prepare(
+                "SELECT * FROM events WHERE (?1 ISNULL OR cat_id = ?1) AND (?2 ISNULL OR kind = ?2) \
+                ORDER BY date, id",
+            )?
+            .query_map(
+                params![id, kind.map(EventKind::as_str)],
+                Event::from_row,
+            )?
+            .map(|res| Ok(res?))
+            .collect(),
+    }
+}
+
+impl Printable for Vec<Event> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No events found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["#", "Cat", "Date", "Kind", "Type", "Reason"]));
+        for event in self {
+            table.add_row(row![
+                event.id,
+                event.cat_id,
+                event.date,
+                event.kind,
+                event.event_type,
+                event.reason.as_deref().unwrap_or(tr("<none>"))
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for event in self {
+            println!(
+                "{} {} {} {} {} {}",
+                event.id,
+                event.cat_id,
+                event.date,
+                event.kind,
+                event.event_type,
+                event.reason.as_deref().unwrap_or("<none>")
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("events", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MedicalEvent {
     pub id: u64,
+    pub cat_id: u64,
+    #[serde(rename = "type")]
+    pub event_type: String,
     pub name: String,
-    pub age: u32,
-    pub breed: Option<String>,
+    pub date: String,
+    pub due: Option<String>,
 }
 
-impl Cat {
+impl MedicalEvent {
     fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
         Ok(Self {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            age: row.get(2)?,
-            breed: row.get(3)?,
+            id: row.get("id")?,
+            cat_id: row.get("cat_id")?,
+            event_type: row.get("type")?,
+            name: row.get("name")?,
+            date: row.get("date")?,
+            due: row.get("due")?,
         })
     }
+
+    /// The due date, pointing out when it has already passed.
+    fn describe_due(&self, today: &str) -> String {
+        match &self.due {
+            Some(due) if due.as_str() < today => format!("{} (overdue)", due),
+            Some(due) => due.clone(),
+            None => "<none>".to_string(),
+        }
+    }
 }
 
-impl Printable for Cat {
-    fn print_display(&self) {
-        let mut table = table!([
-            self.id,
-            self.name,
-            self.age,
-            self.breed.as_deref().unwrap_or("<none>")
-        ]);
-        table.set_titles(["ID", "Name", "Age", "Breed"].iter().collect());
-        table.printstd();
+pub fn medical(conn: &Connection, cmd: CmdMedical) -> Result<Vec<MedicalEvent>> {
+    match cmd {
+        CmdMedical::Add {
+            id,
+            event_type,
+            name,
+            date,
+            due,
+        } => {
+            let today = Date::today();
+            let date = date.unwrap_or(today);
+            if date > today {
+                bail!("{} is in the future", date);
+            }
+            if let Some(due) = due {
+                if due < date {
+                    bail!("The due date {} is before {}", due, date);
+                }
+            }
+            atomic(conn, || {
+                conn.query_row(
+                    "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+                let event = conn.query_row(
+                    "INSERT INTO medical_events (cat_id, type, name, date, due) \
+                    VALUES (?, ?, ?, ?, ?) RETURNING *",
+                    params![
+                        id,
+                        event_type.as_str(),
+                        name,
+                        date.to_string(),
+                        due.map(|due| due.to_string())
+                    ],
+                    MedicalEvent::from_row,
+                )?;
+                Ok(vec![event])
+            })
+        }
+        CmdMedical::List { id } => conn
+            .prepare(
+                "SELECT * FROM medical_events WHERE ?1 ISNULL OR cat_id = ?1 \
+                ORDER BY cat_id, date, id",
+            )?
+            .query_map([id], MedicalEvent::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+    }
+}
+
+impl Printable for Vec<MedicalEvent> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No medical events found"));
+            return;
+        }
+        let today = Date::today().to_string();
+        let mut table = Table::new();
+        table.set_titles(titles(&["#", "Cat", "Type", "Name", "Date", "Due"]));
+        for event in self {
+            table.add_row(row![
+                event.id,
+                event.cat_id,
+                event.event_type,
+                event.name,
+                event.date,
+                event.describe_due(&today)
+            ]);
+        }
+        terminal::print_table(table, style);
     }
     fn print_plain(&self) {
-        println!(
-            "{} {} {} {}",
-            self.id,
-            self.name,
-            self.age,
-            self.breed.as_deref().unwrap_or("<none>")
-        )
+        for event in self {
+            println!(
+                "{} {} {} {} {} {}",
+                event.id,
+                event.cat_id,
+                event.event_type,
+                event.name,
+                event.date,
+                event.due.as_deref().unwrap_or("<none>")
+            )
+        }
     }
     fn print_json(&self) {
         serde_json::to_writer(io::stdout(), self).unwrap();
     }
+    fn print_toml(&self) {
+        write_toml("medical_events", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
 }
 
-impl Printable for Vec<Cat> {
-    fn print_display(&self) {
+/// A license or rabies vaccination that has run out or soon will.
+#[derive(Debug, Serialize)]
+pub struct Expiry {
+    pub cat_id: u64,
+    pub cat: String,
+    /// `license` or `rabies`.
+    pub kind: String,
+    /// The license number, or the name the vaccination was recorded under.
+    pub reference: Option<String>,
+    pub expires: String,
+}
+
+impl Expiry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            cat_id: row.get("cat_id")?,
+            cat: row.get("cat")?,
+            kind: row.get("kind")?,
+            reference: row.get("reference")?,
+            expires: row.get("expires")?,
+        })
+    }
+}
+
+/// Lists the licenses and rabies vaccinations of living cats that run out within `within`.
+///
+/// Only a cat's latest rabies vaccination counts, since a booster replaces the one before it.
+pub fn expiring(conn: &Connection, within: Period) -> Result<Vec<Expiry>> {
+    conn.prepare(
+        "SELECT id AS cat_id, name AS cat, 'license' AS kind, license_number AS reference, \
+        license_expires AS expires FROM cats WHERE license_expires <= date('now', ?1) \
+        AND deleted_at ISNULL AND deceased_at ISNULL \
+        UNION ALL \
+        SELECT cats.id, cats.name, 'rabies', medical_events.name, max(due) FROM medical_events \
+        JOIN cats ON cats.id = cat_id WHERE type = 'vaccination' \
+        AND medical_events.name LIKE '%rabies%' AND deleted_at ISNULL AND deceased_at ISNULL \
+        GROUP BY cats.id HAVING max(due) <= date('now', ?1) \
+        ORDER BY expires, cat_id",
+    )?
+    .query_map([within.ahead()], Expiry::from_row)?
+    .map(|res| Ok(res?))
+    .collect()
+}
+
+impl Printable for Vec<Expiry> {
+    fn print_display(&self, style: Style) {
         if self.is_empty() {
-            None::<Cat>.print_display();
+            println!("{}", tr("Nothing is expiring"));
             return;
         }
+        let today = Date::today().to_string();
         let mut table = Table::new();
-        table.set_titles(["ID", "Name", "Age", "Breed"].iter().collect());
-        for cat in self {
-            table.add_row(
-                [
-                    &cat.id as &dyn Display,
-                    &cat.name,
-                    &cat.age,
-                    &cat.breed.as_deref().unwrap_or("<none>"),
-                ]
-                .iter()
-                .collect(),
+        table.set_titles(titles(&["Cat", "Name", "What", "Number", "Expires"]));
+        for expiry in self {
+            table.add_row(row![
+                expiry.cat_id,
+                expiry.cat,
+                expiry.kind,
+                expiry.reference.as_deref().unwrap_or(tr("<none>")),
+                if expiry.expires < today {
+                    format!("{} ({})", expiry.expires, tr("expired"))
+                } else {
+                    expiry.expires.clone()
+                }
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for expiry in self {
+            println!(
+                "{} {} {} {} {}",
+                expiry.cat_id,
+                expiry.cat,
+                expiry.kind,
+                expiry.reference.as_deref().unwrap_or("<none>"),
+                expiry.expires
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("expiring", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Feeding {
+    pub id: u64,
+    pub cat_id: u64,
+    /// The cat's name, so that whoever is feeding can tell the cats apart.
+    pub cat: String,
+    pub food: String,
+    pub amount: String,
+    pub times_per_day: u32,
+    /// What the cat can't or shouldn't eat.
+    pub diet: Option<String>,
+}
+
+impl Feeding {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            cat_id: row.get("cat_id")?,
+            cat: row.get("cat")?,
+            food: row.get("food")?,
+            amount: row.get("amount")?,
+            times_per_day: row.get("times_per_day")?,
+            diet: row.get("diet")?,
+        })
+    }
+}
+
+/// Every column of `feeding_schedule`, along with the cat's name, diet, and location.
+const FEEDING_COLUMNS: &str = "feeding_schedule.*, cats.name AS cat, cats.diet, \
+    locations.name AS location FROM feeding_schedule JOIN cats ON cats.id = cat_id \
+    LEFT JOIN locations ON locations.id = cats.location_id";
+
+pub fn feeding(conn: &Connection, cmd: CmdFeeding) -> Result<Vec<Feeding>> {
+    match cmd {
+        CmdFeeding::Set {
+            id,
+            food,
+            amount,
+            times,
+        } => {
+            if times == 0 {
+                bail!("A cat has to be fed at least once a day, see `cats feeding remove`");
+            }
+            atomic(conn, || {
+                conn.query_row(
+                    "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+                let feeding_id: u64 = conn.query_row(
+                    "INSERT INTO feeding_schedule (cat_id, food, amount, times_per_day) \
+                    VALUES (?, ?, ?, ?) ON CONFLICT (cat_id, food) DO UPDATE \
+                    SET amount = excluded.amount, times_per_day = excluded.times_per_day \
+                    RETURNING id",
+                    params![id, food, amount, times],
+                    |row| row.get(0),
+                )?;
+                let feeding = conn.query_row(
+                    &format!("SELECT {} WHERE feeding_schedule.id = ?", FEEDING_COLUMNS),
+                    [feeding_id],
+                    Feeding::from_row,
+                )?;
+                Ok(vec![feeding])
+            })
+        }
+        CmdFeeding::Remove { id, food } => atomic(conn, || {
+            let feeding = conn
+                .query_row(
+                    &format!("SELECT {} WHERE cat_id = ? AND food = ?", FEEDING_COLUMNS),
+                    params![id, food],
+                    Feeding::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!("Cat #{} isn't fed {}", id, food))?;
+            conn.execute("DELETE FROM feeding_schedule WHERE id = ?", [feeding.id])?;
+            Ok(vec![feeding])
+        }),
+        CmdFeeding::List { id } => conn
+            .prepare(&format!(
+                "SELECT {} WHERE (?1 ISNULL OR cat_id = ?1) AND cats.deleted_at ISNULL \
+                ORDER BY cat_id, food",
+                FEEDING_COLUMNS
+            ))?
+            .query_map([id], Feeding::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+        CmdFeeding::Today => unreachable!("`feeding today` is a report of its own"),
+    }
+}
+
+impl Printable for Vec<Feeding> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No feedings found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&[
+            "#", "Cat", "Name", "Food", "Amount", "Meals", "Diet",
+        ]));
+        for feeding in self {
+            table.add_row(row![
+                feeding.id,
+                feeding.cat_id,
+                feeding.cat,
+                feeding.food,
+                feeding.amount,
+                format!("{}/{}", feeding.times_per_day, tr("day")),
+                feeding.diet.as_deref().unwrap_or(tr("<none>"))
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for feeding in self {
+            println!(
+                "{} {} {} {} {} {}",
+                feeding.id,
+                feeding.cat_id,
+                feeding.cat,
+                feeding.food,
+                feeding.amount,
+                feeding.times_per_day
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("feedings", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+/// The feedings for the cats in one location.
+#[derive(Debug, Serialize)]
+pub struct FeedingRound {
+    /// The name of the location, or `None` for cats whose location isn't known.
+    pub location: Option<String>,
+    pub feedings: Vec<Feeding>,
+}
+
+/// What to feed the cats still in the shelter's care, grouped by where they are.
+pub fn feeding_today(conn: &Connection) -> Result<Vec<FeedingRound>> {
+    let mut rounds = Vec::<FeedingRound>::new();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} WHERE cats.deleted_at ISNULL AND cats.deceased_at ISNULL \
+        AND cats.status != 'adopted' ORDER BY location ISNULL, location, cat_id, food",
+        FEEDING_COLUMNS
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let location: Option<String> = row.get("location")?;
+        let feeding = Feeding::from_row(row)?;
+        match rounds.last_mut() {
+            Some(round) if round.location == location => round.feedings.push(feeding),
+            _ => rounds.push(FeedingRound {
+                location,
+                feedings: vec![feeding],
+            }),
+        }
+    }
+    Ok(rounds)
+}
+
+impl Printable for Vec<FeedingRound> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No cats need feeding"));
+            return;
+        }
+        for round in self {
+            println!(
+                "{}:",
+                round.location.as_deref().unwrap_or(tr("Location unknown"))
             );
+            round.feedings.print_display(style);
         }
-        table.printstd();
     }
     fn print_plain(&self) {
-        for cat in self {
-            cat.print_plain()
+        for round in self {
+            for feeding in &round.feedings {
+                println!(
+                    "{} {} {} {} {} {}",
+                    round.location.as_deref().unwrap_or("<unknown>"),
+                    feeding.cat_id,
+                    feeding.cat,
+                    feeding.food,
+                    feeding.amount,
+                    feeding.times_per_day
+                )
+            }
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("rounds", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(
+            dialect,
+            &[
+                "location",
+                "id",
+                "cat_id",
+                "cat",
+                "food",
+                "amount",
+                "times_per_day",
+                "diet",
+            ],
+            self.iter().flat_map(|round| {
+                round.feedings.iter().map(move |feeding| {
+                    vec![
+                        round.location.clone().unwrap_or_default(),
+                        feeding.id.to_string(),
+                        feeding.cat_id.to_string(),
+                        feeding.cat.clone(),
+                        feeding.food.clone(),
+                        feeding.amount.clone(),
+                        feeding.times_per_day.to_string(),
+                        feeding.diet.clone().unwrap_or_default(),
+                    ]
+                })
+            }),
+        );
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Medication {
+    pub id: u64,
+    pub cat_id: u64,
+    pub drug: String,
+    pub dose: String,
+    pub frequency: String,
+    pub starts_on: String,
+    pub ends_on: Option<String>,
+}
+
+impl Medication {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let frequency = Frequency {
+            times_per_day: row.get("times_per_day")?,
+            every_days: row.get("every_days")?,
+        };
+        Ok(Self {
+            id: row.get("id")?,
+            cat_id: row.get("cat_id")?,
+            drug: row.get("drug")?,
+            dose: row.get("dose")?,
+            frequency: frequency.to_string(),
+            starts_on: row.get("starts_on")?,
+            ends_on: row.get("ends_on")?,
+        })
+    }
+}
+
+pub fn meds(conn: &Connection, cmd: CmdMeds) -> Result<Vec<Medication>> {
+    let today = Date::today().to_string();
+    match cmd {
+        CmdMeds::Add {
+            id,
+            drug,
+            dose,
+            frequency,
+            start,
+            end,
+        } => {
+            let start = start.unwrap_or_else(Date::today);
+            if let Some(end) = end {
+                if end < start {
+                    bail!("The end date {} is before the start date {}", end, start);
+                }
+            }
+            atomic(conn, || {
+                conn.query_row(
+                    "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+                let medication = conn.query_row(
+                    "INSERT INTO medications (cat_id, drug, dose, times_per_day, every_days, \
+                    starts_on, ends_on) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *",
+                    params![
+                        id,
+                        drug,
+                        dose,
+                        frequency.times_per_day,
+                        frequency.every_days,
+                        start.to_string(),
+                        end.map(|end| end.to_string())
+                    ],
+                    Medication::from_row,
+                )?;
+                Ok(vec![medication])
+            })
+        }
+        CmdMeds::Stop { prescription, end } => {
+            let end = end.unwrap_or_else(Date::today).to_string();
+            atomic(conn, || {
+                let medication = conn
+                    .query_row(
+                        "SELECT * FROM medications WHERE id = ?",
+                        [prescription],
+                        Medication::from_row,
+                    )
+                    .optional()?
+                    .ok_or_else(|| anyhow!("There is no prescription with ID {}", prescription))?;
+                if end < medication.starts_on {
+                    bail!(
+                        "Prescription #{} only starts on {}",
+                        prescription,
+                        medication.starts_on
+                    );
+                }
+                if let Some(ends_on) = &medication.ends_on {
+                    if *ends_on <= end {
+                        bail!(
+                            "Prescription #{} already ended on {}",
+                            prescription,
+                            ends_on
+                        );
+                    }
+                }
+                let medication = conn.query_row(
+                    "UPDATE medications SET ends_on = ? WHERE id = ? RETURNING *",
+                    params![end, prescription],
+                    Medication::from_row,
+                )?;
+                Ok(vec![medication])
+            })
+        }
+        CmdMeds::List { id, all } => conn
+            .prepare(
+                "SELECT * FROM medications WHERE (?1 ISNULL OR cat_id = ?1) \
+                AND (?2 OR starts_on <= ?3 AND ifnull(ends_on >= ?3, TRUE)) \
+                ORDER BY cat_id, starts_on, id",
+            )?
+            .query_map(params![id, all, today], Medication::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+        // A dose is due on the start date and every `every_days` days after it.
+        CmdMeds::Due => conn
+            .prepare(
+                "SELECT medications.* FROM medications JOIN cats ON cats.id = cat_id \
+                WHERE deleted_at ISNULL AND deceased_at ISNULL \
+                AND starts_on <= ?1 AND ifnull(ends_on >= ?1, TRUE) \
+                AND CAST(julianday(?1) - julianday(starts_on) AS INTEGER) % every_days = 0 \
+                ORDER BY cat_id, id",
+            )?
+            .query_map([today], Medication::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+    }
+}
+
+impl Printable for Vec<Medication> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No medications found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&[
+            "#",
+            "Cat",
+            "Drug",
+            "Dose",
+            "Frequency",
+            "Start",
+            "End",
+        ]));
+        for medication in self {
+            table.add_row(row![
+                medication.id,
+                medication.cat_id,
+                medication.drug,
+                medication.dose,
+                medication.frequency,
+                medication.starts_on,
+                medication.ends_on.as_deref().unwrap_or(tr("<none>"))
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for medication in self {
+            println!(
+                "{} {} {} {} {} {} {}",
+                medication.id,
+                medication.cat_id,
+                medication.drug,
+                medication.dose,
+                medication.frequency,
+                medication.starts_on,
+                medication.ends_on.as_deref().unwrap_or("<none>")
+            )
         }
     }
     fn print_json(&self) {
         serde_json::to_writer(io::stdout(), self).unwrap();
     }
+    fn print_toml(&self) {
+        write_toml("medications", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Owner {
+    pub id: u64,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// How many cats, not counting the trash, belong to the owner.
+    pub cats: u64,
+}
+
+impl Owner {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            email: row.get("email")?,
+            phone: row.get("phone")?,
+            cats: row.get("cats")?,
+        })
+    }
+}
+
+/// Every column of `owners`, along with how many cats each owner has.
+const OWNER_COLUMNS: &str = "id, name, email, phone, \
+    (SELECT COUNT(*) FROM cats WHERE owner_id = owners.id AND deleted_at ISNULL) AS cats";
+
+fn check_email(email: &Option<String>) -> Result<()> {
+    match email {
+        Some(email) if !email.contains('@') => bail!("Invalid email address `{}`", email),
+        _ => Ok(()),
+    }
+}
+
+pub fn owner(conn: &Connection, cmd: CmdOwner) -> Result<Vec<Owner>> {
+    match cmd {
+        CmdOwner::Add { name, email, phone } => {
+            check_email(&email)?;
+            let owner = conn.query_row(
+                &format!(
+                    "INSERT INTO owners (name, email, phone) VALUES (?, ?, ?) RETURNING {}",
+                    OWNER_COLUMNS
+                ),
+                params![name, email, phone],
+                Owner::from_row,
+            )?;
+            Ok(vec![owner])
+        }
+        CmdOwner::Find { name, email } => conn
+            .prepare(&format!(
+                "SELECT {} FROM owners WHERE (?1 ISNULL OR instr(lower(name), lower(?1))) \
+                AND (?2 ISNULL OR instr(lower(email), lower(?2))) ORDER BY id",
+                OWNER_COLUMNS
+            ))?
+            .query_map(params![name, email], Owner::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+        CmdOwner::Update {
+            id,
+            name,
+            email,
+            phone,
+        } => {
+            check_email(&email)?;
+            let owner = conn
+                .query_row(
+                    &format!(
+                        "UPDATE owners SET name = ifnull(?, name), email = ifnull(?, email), \
+                        phone = ifnull(?, phone) WHERE id = ? RETURNING {}",
+                        OWNER_COLUMNS
+                    ),
+                    params![name, email, phone, id],
+                    Owner::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| no_such_owner(id))?;
+            Ok(vec![owner])
+        }
+        CmdOwner::Delete { id } => atomic(conn, || {
+            let owner = conn
+                .query_row(
+                    &format!("SELECT {} FROM owners WHERE id = ?", OWNER_COLUMNS),
+                    [id],
+                    Owner::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| no_such_owner(id))?;
+            // Trashed cats lose their owner too, so none of them point at a missing owner.
+            let cats = conn
+                .prepare("SELECT * FROM cats WHERE owner_id = ?")?
+                .query_map([id], Cat::from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for old in cats {
+                let new = conn.query_row(
+                    "UPDATE cats SET owner_id = NULL, updated_at = datetime('now') \
+                    WHERE id = ? RETURNING *",
+                    [old.id],
+                    Cat::from_row,
+                )?;
+                record(conn, Action::Update, Some(&old), Some(&new))?;
+            }
+            conn.execute("DELETE FROM owners WHERE id = ?", [id])?;
+            Ok(vec![owner])
+        }),
+    }
+}
+
+fn no_such_owner(id: u64) -> Error {
+    anyhow!("There is no owner with ID {}", id)
+}
+
+/// Makes sure a cat's old owner, location, and parents are still around before pointing the cat
+/// back at them.
+fn check_relations_exist(conn: &Connection, cat: &Cat) -> Result<()> {
+    if let Some(owner) = cat.owner_id {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT * FROM owners WHERE id = ?)",
+            [owner],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            bail!("The cat's owner, #{}, has since been deleted", owner);
+        }
+    }
+    if let Some(location) = cat.location_id {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT * FROM locations WHERE id = ?)",
+            [location],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            bail!("The cat's location, #{}, has since been removed", location);
+        }
+    }
+    for (relation, parent) in [("mother", cat.mother_id), ("father", cat.father_id)] {
+        if let Some(parent) = parent {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS (SELECT * FROM cats WHERE id = ?)",
+                [parent],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                bail!("The cat's {}, #{}, has since been purged", relation, parent);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Printable for Vec<Owner> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No owners found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["ID", "Name", "Email", "Phone", "Cats"]));
+        for owner in self {
+            let none = || tr("<none>").to_string();
+            table.add_row(row![
+                owner.id,
+                owner.name,
+                owner.email.clone().unwrap_or_else(none),
+                owner.phone.clone().unwrap_or_else(none),
+                owner.cats
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for owner in self {
+            println!(
+                "{} {} {} {} {}",
+                owner.id,
+                owner.name,
+                owner.email.as_deref().unwrap_or("<none>"),
+                owner.phone.as_deref().unwrap_or("<none>"),
+                owner.cats
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("owners", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Location {
+    pub id: u64,
+    pub name: String,
+    /// How many cats, not counting the trash, are in the location.
+    pub cats: u64,
+}
+
+impl Location {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            cats: row.get("cats")?,
+        })
+    }
+}
+
+/// Every column of `locations`, along with how many cats are in each one.
+const LOCATION_COLUMNS: &str = "id, name, \
+    (SELECT COUNT(*) FROM cats WHERE location_id = locations.id AND deleted_at ISNULL) AS cats";
+
+fn no_such_location(name: &str) -> Error {
+    anyhow!(
+        "There is no location named `{}`, see `cats location list` for the locations there are",
+        name
+    )
+}
+
+pub fn location(conn: &Connection, cmd: CmdLocation) -> Result<Vec<Location>> {
+    match cmd {
+        CmdLocation::Add { name } => {
+            let location = conn
+                .query_row(
+                    &format!(
+                        "INSERT OR IGNORE INTO locations (name) VALUES (?) RETURNING {}",
+                        LOCATION_COLUMNS
+                    ),
+                    [&name],
+                    Location::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!("There is already a location named `{}`", name))?;
+            Ok(vec![location])
+        }
+        CmdLocation::List => conn
+            .prepare(&format!(
+                "SELECT {} FROM locations ORDER BY name",
+                LOCATION_COLUMNS
+            ))?
+            .query_map([], Location::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+        CmdLocation::Remove { name } => atomic(conn, || {
+            let location = conn
+                .query_row(
+                    &format!("SELECT {} FROM locations WHERE name = ?", LOCATION_COLUMNS),
+                    [&name],
+                    Location::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| no_such_location(&name))?;
+            // Trashed cats count too, since they'd be left pointing at a missing location.
+            let cats: u64 = conn.query_row(
+                "SELECT COUNT(*) FROM cats WHERE location_id = ?",
+                [location.id],
+                |row| row.get(0),
+            )?;
+            if cats > 0 {
+                bail!(
+                    "There are still cats in `{}`, move them somewhere else first",
+                    location.name
+                );
+            }
+            conn.execute("DELETE FROM locations WHERE id = ?", [location.id])?;
+            Ok(vec![location])
+        }),
+    }
+}
+
+impl Printable for Vec<Location> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No locations found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["#", "Name", "Cats"]));
+        for location in self {
+            table.add_row(row![location.id, location.name, location.cats]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for location in self {
+            println!("{} {} {}", location.id, location.name, location.cats)
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("locations", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+/// Moves a cat to the location named `to`.
+pub fn move_cat(conn: &Connection, id: u64, to: &str) -> Result<Cat> {
+    atomic(conn, || {
+        let old = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        let (location_id, name) = conn
+            .query_row(
+                "SELECT id, name FROM locations WHERE name = ?",
+                [to],
+                |row| Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| no_such_location(to))?;
+        if old.location_id == Some(location_id) {
+            bail!("Cat #{} is already in `{}`", id, name);
+        }
+        let mut new = conn.query_row(
+            "UPDATE cats SET location_id = ?, updated_at = datetime('now') WHERE id = ? \
+            RETURNING *",
+            params![location_id, id],
+            Cat::from_row,
+        )?;
+        record(conn, Action::Update, Some(&old), Some(&new))?;
+        new.location = Some(name);
+        Ok(new)
+    })
+}
+
+/// Marks a cat as adopted by an owner on `date`, or today.
+pub fn adopt(conn: &Connection, id: u64, owner: u64, date: Option<Date>) -> Result<Cat> {
+    let today = Date::today();
+    let date = date.unwrap_or(today);
+    if date > today {
+        bail!("{} is in the future", date);
+    }
+    atomic(conn, || {
+        let old = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        if old.status == Status::Adopted {
+            bail!("Cat #{} has already been adopted", id);
+        }
+        if old.deceased_at.is_some() {
+            bail!("Cat #{} has died", id);
+        }
+        let name = conn
+            .query_row("SELECT name FROM owners WHERE id = ?", [owner], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?
+            .ok_or_else(|| no_such_owner(owner))?;
+        let mut new = conn.query_row(
+            "UPDATE cats SET status = 'adopted', adopted_at = ?, owner_id = ?, \
+            updated_at = datetime('now') WHERE id = ? RETURNING *",
+            params![date.to_string(), owner, id],
+            Cat::from_row,
+        )?;
+        record(conn, Action::Update, Some(&old), Some(&new))?;
+        new.owner = Some(name);
+        Ok(new)
+    })
+}
+
+/// Marks an adopted cat as returned, taking it away from the owner who adopted it.
+pub fn return_cat(conn: &Connection, id: u64) -> Result<Cat> {
+    atomic(conn, || {
+        let old = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        if old.status != Status::Adopted {
+            bail!("Cat #{} hasn't been adopted, it's {}", id, old.status);
+        }
+        let new = conn.query_row(
+            "UPDATE cats SET status = 'returned', adopted_at = NULL, owner_id = NULL, \
+            updated_at = datetime('now') WHERE id = ? RETURNING *",
+            [id],
+            Cat::from_row,
+        )?;
+        record(conn, Action::Update, Some(&old), Some(&new))?;
+        Ok(new)
+    })
+}
+
+/// Records that a cat died on `date`, or today.
+pub fn mark_deceased(conn: &Connection, id: u64, date: Option<Date>) -> Result<Cat> {
+    let today = Date::today();
+    let date = date.unwrap_or(today);
+    if date > today {
+        bail!("{} is in the future", date);
+    }
+    atomic(conn, || {
+        let old = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        if let Some(deceased_at) = &old.deceased_at {
+            bail!(
+                "Cat #{} has already been marked as having died on {}",
+                id,
+                deceased_at
+            );
+        }
+        if let Some(birthdate) = &old.birthdate {
+            if date.to_string() < *birthdate {
+                bail!("Cat #{} was only born on {}", id, birthdate);
+            }
+        }
+        let new = conn.query_row(
+            "UPDATE cats SET deceased_at = ?, updated_at = datetime('now') WHERE id = ? \
+            RETURNING *",
+            params![date.to_string(), id],
+            Cat::from_row,
+        )?;
+        record(conn, Action::Update, Some(&old), Some(&new))?;
+        Ok(new)
+    })
+}
+
+/// Gives a cat to an owner, or takes it away from its owner when `owner` is `None`.
+pub fn assign(conn: &Connection, id: u64, owner: Option<u64>) -> Result<Cat> {
+    atomic(conn, || {
+        let old = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        let name = match owner {
+            Some(owner) => Some(
+                conn.query_row("SELECT name FROM owners WHERE id = ?", [owner], |row| {
+                    row.get::<_, String>(0)
+                })
+                .optional()?
+                .ok_or_else(|| no_such_owner(owner))?,
+            ),
+            None => None,
+        };
+        let mut new = conn.query_row(
+            "UPDATE cats SET owner_id = ?, updated_at = datetime('now') WHERE id = ? RETURNING *",
+            params![owner, id],
+            Cat::from_row,
+        )?;
+        record(conn, Action::Update, Some(&old), Some(&new))?;
+        new.owner = name;
+        Ok(new)
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Litter {
+    pub id: u64,
+    pub mother_id: Option<u64>,
+    pub father_id: Option<u64>,
+    pub born_on: Option<String>,
+    /// The IDs of the kittens, which are looked up separately.
+    pub kittens: Vec<u64>,
+}
+
+impl Litter {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            mother_id: row.get("mother_id")?,
+            father_id: row.get("father_id")?,
+            born_on: row.get("born_on")?,
+            kittens: Vec::new(),
+        })
+    }
+}
+
+pub fn litter(conn: &Connection, cmd: CmdLitter) -> Result<Vec<Litter>> {
+    let mut litters = match cmd {
+        CmdLitter::Record {
+            mother,
+            father,
+            kittens,
+            born,
+        } => {
+            if let Some(born) = born {
+                if born > Date::today() {
+                    bail!("{} is in the future", born);
+                }
+            }
+            atomic(conn, || {
+                let get = |id: u64| {
+                    conn.query_row(
+                        "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                        [id],
+                        Cat::from_row,
+                    )
+                    .optional()?
+                    .ok_or_else(|| anyhow!(NoSuchCat(id)))
+                };
+                for (parent, wrong_sex, relation) in [
+                    (mother, Sex::Male, "mother"),
+                    (father, Sex::Female, "father"),
+                ] {
+                    if let Some(parent) = parent {
+                        if get(parent)?.sex == wrong_sex {
+                            bail!(
+                                "Cat #{} is {}, so it can't be the {}",
+                                parent,
+                                wrong_sex,
+                                relation
+                            );
+                        }
+                        if kittens.contains(&parent) {
+                            bail!("Cat #{} can't be its own {}", parent, relation);
+                        }
+                        for &kitten in &kittens {
+                            let descends = conn.query_row(
+                                "WITH RECURSIVE descendants (id) AS ( \
+                                    SELECT ?1 UNION SELECT kitten.id FROM descendants \
+                                    JOIN cats AS kitten \
+                                    ON descendants.id IN (kitten.mother_id, kitten.father_id)) \
+                                SELECT EXISTS (SELECT * FROM descendants WHERE id = ?2)",
+                                [kitten, parent],
+                                |row| row.get(0),
+                            )?;
+                            if descends {
+                                bail!(
+                                    "Cat #{} is descended from cat #{}, so it can't be its {}",
+                                    parent,
+                                    kitten,
+                                    relation
+                                );
+                            }
+                        }
+                    }
+                }
+                let litter = conn.query_row(
+                    "INSERT INTO litters (mother_id, father_id, born_on) VALUES (?, ?, ?) \
+                    RETURNING *",
+                    params![mother, father, born.map(|born| born.to_string())],
+                    Litter::from_row,
+                )?;
+                for &kitten in kittens.iter().unique() {
+                    let old = get(kitten)?;
+                    let new = conn.query_row(
+                        "UPDATE cats SET mother_id = ?, father_id = ?, litter_id = ?, \
+                        birthdate = ifnull(birthdate, ?), updated_at = datetime('now') \
+                        WHERE id = ? RETURNING *",
+                        params![
+                            mother,
+                            father,
+                            litter.id,
+                            born.map(|born| born.to_string()),
+                            kitten
+                        ],
+                        Cat::from_row,
+                    )?;
+                    record(conn, Action::Update, Some(&old), Some(&new))?;
+                }
+                Ok(vec![litter])
+            })?
+        }
+        CmdLitter::List { parent } => conn
+            .prepare(
+                "SELECT * FROM litters WHERE ?1 ISNULL OR ?1 IN (mother_id, father_id) \
+                ORDER BY born_on, id",
+            )?
+            .query_map([parent], Litter::from_row)?
+            .collect::<rusqlite::Result<_>>()?,
+    };
+    let mut stmt = conn.prepare("SELECT id FROM cats WHERE litter_id = ? ORDER BY id")?;
+    for litter in &mut litters {
+        litter.kittens = stmt
+            .query_map([litter.id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+    }
+    Ok(litters)
+}
+
+impl Printable for Vec<Litter> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No litters found"));
+            return;
+        }
+        let parent = |id: Option<u64>| {
+            id.map_or_else(|| tr("<unknown>").to_string(), |id| format!("#{}", id))
+        };
+        let mut table = Table::new();
+        table.set_titles(titles(&["#", "Mother", "Father", "Born", "Kittens"]));
+        for litter in self {
+            table.add_row(row![
+                litter.id,
+                parent(litter.mother_id),
+                parent(litter.father_id),
+                litter.born_on.as_deref().unwrap_or(tr("<unknown>")),
+                litter
+                    .kittens
+                    .iter()
+                    .map(|id| format!("#{}", id))
+                    .join(", ")
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for litter in self {
+            let id = |id: Option<u64>| id.map_or_else(|| "<none>".to_string(), |id| id.to_string());
+            println!(
+                "{} {} {} {} {}",
+                litter.id,
+                id(litter.mother_id),
+                id(litter.father_id),
+                litter.born_on.as_deref().unwrap_or("<none>"),
+                litter.kittens.iter().join(",")
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("litters", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        let id = |id: Option<u64>| id.map_or_else(String::new, |id| id.to_string());
+        write_records(
+            dialect,
+            &["id", "mother_id", "father_id", "born_on", "kittens"],
+            self.iter().map(|litter| {
+                vec![
+                    litter.id.to_string(),
+                    id(litter.mother_id),
+                    id(litter.father_id),
+                    litter.born_on.clone().unwrap_or_default(),
+                    litter.kittens.iter().join(" "),
+                ]
+            }),
+        );
+    }
+}
+
+/// A cat in a family tree, along with the relatives further out from the cat the tree is of.
+#[derive(Debug, Serialize)]
+pub struct Relative {
+    pub id: u64,
+    pub name: String,
+    /// How the cat is related to the one before it in the tree: mother, father, or kitten.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relation: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<Relative>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub kittens: Vec<Relative>,
+}
+
+/// Every ancestor up to `?2` generations back from the cat `?1`, next to the cat it's a parent of.
+const ANCESTORS: &str = "\
+WITH RECURSIVE ancestors (id, next, relation, depth) AS (
+    SELECT ?1, NULL, NULL, 0
+    UNION
+    SELECT parent.id, ancestors.id, iif(parent.id = cats.mother_id, 'mother', 'father'), depth + 1
+    FROM ancestors JOIN cats ON cats.id = ancestors.id
+    JOIN cats AS parent ON parent.id IN (cats.mother_id, cats.father_id)
+    WHERE depth < ?2
+)
+SELECT id, next, relation, depth, name FROM ancestors JOIN cats USING (id)
+WHERE depth > 0 AND deleted_at ISNULL ORDER BY relation DESC, id";
+
+/// Every descendant down to `?2` generations after the cat `?1`, next to the cat it's a kitten of.
+const DESCENDANTS: &str = "\
+WITH RECURSIVE descendants (id, next, depth) AS (
+    SELECT ?1, NULL, 0
+    UNION
+    SELECT kitten.id, descendants.id, depth + 1
+    FROM descendants JOIN cats AS kitten ON descendants.id IN (kitten.mother_id, kitten.father_id)
+    WHERE depth < ?2
+)
+SELECT id, next, 'kitten', depth, name FROM descendants JOIN cats USING (id)
+WHERE depth > 0 AND deleted_at ISNULL ORDER BY id";
+
+/// A relative, and the cat it's a parent or kitten of.
+#[derive(Debug)]
+struct Link {
+    id: u64,
+    next: u64,
+    relation: String,
+    depth: u32,
+    name: String,
+}
+
+/// Builds the part of a family tree that's `depth` generations out, next to the cat `next`.
+///
+/// Links are only unique by who they're next to and how far out they are, since inbred cats turn
+/// up in more than one place in the tree.
+fn branch(links: &[Link], next: u64, depth: u32, ancestors: bool) -> Vec<Relative> {
+    links
+        .iter()
+        .filter(|link| link.next == next && link.depth == depth)
+        .map(|link| {
+            let further = branch(links, link.id, depth + 1, ancestors);
+            let (parents, kittens) = if ancestors {
+                (further, Vec::new())
+            } else {
+                (Vec::new(), further)
+            };
+            Relative {
+                id: link.id,
+                name: link.name.clone(),
+                relation: Some(link.relation.clone()),
+                parents,
+                kittens,
+            }
+        })
+        .collect()
+}
+
+pub fn pedigree(conn: &Connection, id: u64, depth: u32) -> Result<Relative> {
+    let name = conn
+        .query_row(
+            "SELECT name FROM cats WHERE id = ? AND deleted_at ISNULL",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+    let links = |sql| -> Result<Vec<Link>> {
+        conn.prepare(sql)?
+            .query_map(params![id, depth], |row| {
+                Ok(Link {
+                    id: row.get(0)?,
+                    next: row.get(1)?,
+                    relation: row.get(2)?,
+                    depth: row.get(3)?,
+                    name: row.get(4)?,
+                })
+            })?
+            .map(|res| Ok(res?))
+            .collect()
+    };
+    let ancestors = links(ANCESTORS)?;
+    let descendants = links(DESCENDANTS)?;
+    Ok(Relative {
+        id,
+        name,
+        relation: None,
+        parents: branch(&ancestors, id, 1, true),
+        kittens: branch(&descendants, id, 1, false),
+    })
+}
+
+impl Relative {
+    /// Parents go before kittens, so that a tree reads from the oldest generation to the youngest.
+    fn branches(&self) -> impl Iterator<Item = &Relative> {
+        self.parents.iter().chain(&self.kittens)
+    }
+
+    fn print_branches(&self, prefix: &str) {
+        let count = self.branches().count();
+        for (i, relative) in self.branches().enumerate() {
+            let last = i + 1 == count;
+            println!(
+                "{}{}{}: {} (#{})",
+                prefix,
+                if last { "`-- " } else { "+-- " },
+                relative.relation.as_deref().unwrap_or_default(),
+                relative.name,
+                relative.id
+            );
+            relative.print_branches(&format!("{}{}", prefix, if last { "    " } else { "|   " }));
+        }
+    }
+
+    /// Every relative in the tree along with its generation, counting back from this one's for
+    /// ancestors and forward for descendants.
+    fn lines(&self, generation: i64) -> Vec<(i64, &str, &Relative)> {
+        let mut lines = Vec::new();
+        for (relatives, next) in [
+            (&self.parents, generation - 1),
+            (&self.kittens, generation + 1),
+        ] {
+            for relative in relatives {
+                let relation = relative.relation.as_deref().unwrap_or_default();
+                lines.push((next, relation, relative));
+                lines.extend(relative.lines(next));
+            }
+        }
+        lines
+    }
+}
+
+impl Printable for Relative {
+    fn print_display(&self, _style: Style) {
+        println!("{} (#{})", self.name, self.id);
+        if self.parents.is_empty() && self.kittens.is_empty() {
+            println!(
+                "{}",
+                tr("No parents or kittens have been recorded for this cat")
+            );
+        }
+        self.print_branches("");
+    }
+    fn print_plain(&self) {
+        println!("0 self {} {}", self.id, self.name);
+        for (generation, relation, relative) in self.lines(0) {
+            println!(
+                "{} {} {} {}",
+                generation, relation, relative.id, relative.name
+            );
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("cat", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(
+            dialect,
+            &["generation", "relation", "id", "name"],
+            Some((0, "self", self))
+                .into_iter()
+                .chain(self.lines(0))
+                .map(|(generation, relation, relative)| {
+                    vec![
+                        generation.to_string(),
+                        relation.to_string(),
+                        relative.id.to_string(),
+                        relative.name.clone(),
+                    ]
+                }),
+        );
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Related {
+    /// How the cat is related: mother, father, littermate, sibling, kitten, or bonded.
+    pub relation: String,
+    pub id: u64,
+    pub name: String,
+}
+
+/// Everyone related to the cat `?1`. Siblings from other litters have at least one parent in common.
+const RELATED: &str = "\
+WITH cat AS (SELECT * FROM cats WHERE id = ?1)
+SELECT 'mother', cats.id, cats.name FROM cats, cat WHERE cats.id = cat.mother_id
+UNION ALL
+SELECT 'father', cats.id, cats.name FROM cats, cat WHERE cats.id = cat.father_id
+UNION ALL
+SELECT 'littermate', cats.id, cats.name FROM cats, cat
+WHERE cats.litter_id = cat.litter_id AND cats.id != cat.id AND cats.deleted_at ISNULL
+UNION ALL
+SELECT 'sibling', cats.id, cats.name FROM cats, cat
+WHERE (cats.mother_id = cat.mother_id OR cats.father_id = cat.father_id) AND cats.id != cat.id
+AND (cat.litter_id ISNULL OR cats.litter_id IS NOT cat.litter_id) AND cats.deleted_at ISNULL
+UNION ALL
+SELECT 'kitten', cats.id, cats.name FROM cats, cat
+WHERE cat.id IN (cats.mother_id, cats.father_id) AND cats.deleted_at ISNULL
+UNION ALL
+SELECT 'bonded', cats.id, cats.name FROM bonds JOIN cats
+ON cats.id = iif(bonds.cat_id = ?1, bonds.other_id, bonds.cat_id)
+WHERE ?1 IN (bonds.cat_id, bonds.other_id) AND cats.deleted_at ISNULL";
+
+pub fn related(conn: &Connection, id: u64) -> Result<Vec<Related>> {
+    conn.query_row(
+        "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+        [id],
+        |_| Ok(()),
+    )
+    .optional()?
+    .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+    conn.prepare(RELATED)?
+        .query_map([id], |row| {
+            Ok(Related {
+                relation: row.get(0)?,
+                id: row.get(1)?,
+                name: row.get(2)?,
+            })
+        })?
+        .map(|res| Ok(res?))
+        .collect()
+}
+
+impl Printable for Vec<Related> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!(
+                "{}",
+                tr("No relatives or bonded cats have been recorded for this cat")
+            );
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Relation", "ID", "Name"]));
+        for related in self {
+            table.add_row(row![related.relation, related.id, related.name]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for related in self {
+            println!("{} {} {}", related.relation, related.id, related.name)
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("related", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+/// Bonds two cats, or unbonds them if `remove` is set, returning both.
+pub fn bond(conn: &Connection, id: u64, with: u64, remove: bool) -> Result<Vec<Cat>> {
+    if id == with {
+        bail!("A cat can't be bonded with itself");
+    }
+    // Each pair is only stored one way around.
+    let (first, second) = (id.min(with), id.max(with));
+    atomic(conn, || {
+        for id in [first, second] {
+            conn.query_row(
+                "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        }
+        if remove {
+            let removed = conn.execute(
+                "DELETE FROM bonds WHERE cat_id = ? AND other_id = ?",
+                [first, second],
+            )?;
+            if removed == 0 {
+                bail!("Cats #{} and #{} aren't bonded", id, with);
+            }
+        } else {
+            let added = conn.execute(
+                "INSERT OR IGNORE INTO bonds (cat_id, other_id) VALUES (?, ?)",
+                [first, second],
+            )?;
+            if added == 0 {
+                bail!("Cats #{} and #{} are already bonded", id, with);
+            }
+        }
+        let stmt = format!(
+            "SELECT * FROM {} WHERE cats.id IN (?, ?) ORDER BY id",
+            CATS_WITH_DETAILS
+        );
+        conn.prepare(&stmt)?
+            .query_map([id, with], Cat::from_row)?
+            .map(|res| Ok(res?))
+            .collect()
+    })
+}
+
+/// A cat along with its first photo, for `get --show-photo`.
+#[derive(Debug)]
+pub struct PicturedCat {
+    pub cat: Cat,
+    pub photo: Option<Image>,
+}
+
+#[derive(Debug)]
+pub struct Image {
+    pub id: u64,
+    pub name: String,
+    pub format: String,
+    pub data: Vec<u8>,
+}
+
+pub fn with_photos(conn: &Connection, cats: Vec<Cat>) -> Result<Vec<PicturedCat>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, format, data FROM photos WHERE cat_id = ? ORDER BY id LIMIT 1",
+    )?;
+    cats.into_iter()
+        .map(|cat| {
+            let photo = stmt
+                .query_row([cat.id], |row| {
+                    Ok(Image {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        format: row.get(2)?,
+                        data: row.get(3)?,
+                    })
+                })
+                .optional()?;
+            Ok(PicturedCat { cat, photo })
+        })
+        .collect()
+}
+
+impl Printable for Vec<PicturedCat> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            None::<Cat>.print_display(style);
+            return;
+        }
+        for PicturedCat { cat, photo } in self {
+            cat.print_display(style);
+            if let Some(photo) = photo {
+                if !terminal::show_image(&photo.format, &photo.data).unwrap_or(false) {
+                    println!(
+                        "{} #{}: {}, {} `cats photo export --photo {}`",
+                        tr("Photo"),
+                        photo.id,
+                        photo.name,
+                        tr("see"),
+                        photo.id
+                    );
+                }
+            }
+        }
+    }
+    fn print_plain(&self) {
+        for pictured in self {
+            pictured.cat.print_plain()
+        }
+    }
+    fn print_json(&self) {
+        let cats = self
+            .iter()
+            .map(|pictured| &pictured.cat)
+            .collect::<Vec<_>>();
+        serde_json::to_writer(io::stdout(), &cats).unwrap();
+    }
+    fn print_toml(&self) {
+        let cats = self
+            .iter()
+            .map(|pictured| &pictured.cat)
+            .collect::<Vec<_>>();
+        write_toml("cats", &cats);
+    }
+    fn cats(&self) -> Option<Vec<&Cat>> {
+        Some(self.iter().map(|pictured| &pictured.cat).collect())
+    }
+    fn print_template(&self, template: &Template) {
+        let cats = self
+            .iter()
+            .map(|pictured| &pictured.cat)
+            .collect::<Vec<_>>();
+        template.render(&cats);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_cats(
+            dialect,
+            self.iter()
+                .map(|pictured| (&pictured.cat, pictured.photo.as_ref())),
+        );
+    }
+}
+
+pub fn get_by_microchip(store: &dyn CatStore, microchip: Microchip) -> Result<Vec<Cat>> {
+    store.find(&CmdFind {
+        microchip: Some(vec![microchip]),
+        include_deceased: true,
+        ..CmdFind::default()
+    })
+}
+
+pub fn find(store: &dyn CatStore, cmd: CmdFind) -> Result<FoundCats> {
+    let cats = store.find(&cmd)?;
+    let matchers = |values: &Option<Vec<String>>, default| {
+        values
+            .iter()
+            .flatten()
+            .filter_map(|value| matcher(value, &cmd, default))
+            .collect::<Vec<_>>()
+    };
+    // `--text` looks through names and breeds too, so it can be why either of them matched.
+    let text = matchers(&cmd.text, MatchMode::Contains);
+    let names = matchers(&cmd.name, MatchMode::Exact);
+    let breeds = matchers(&cmd.breed, MatchMode::Exact);
+    Ok(FoundCats {
+        cats,
+        names: names.into_iter().chain(text.iter().cloned()).collect(),
+        breeds: breeds.into_iter().chain(text).collect(),
+        summary: cmd.summary,
+    })
+}
+
+pub fn count(store: &dyn CatStore, cmd: CmdFind) -> Result<u64> {
+    store.count(&cmd)
+}
+
+/// Cats that were looked for, along with what their names and breeds were matched against, so
+/// that tables can highlight why each cat is there.
+#[derive(Debug)]
+pub struct FoundCats {
+    pub cats: Vec<Cat>,
+    names: Vec<Regex>,
+    breeds: Vec<Regex>,
+    /// Whether tables end with how many cats there are and their average age, for `--summary`.
+    summary: bool,
+}
+
+pub fn search(store: &dyn CatStore, query: &str) -> Result<FoundCats> {
+    let cats = store.search(query)?;
+    // Column filters, operators, and punctuation aren't in the cats' names, so only the words are
+    // highlighted.
+    let words = query
+        .split_whitespace()
+        .filter_map(|term| term.rsplit(':').next())
+        .flat_map(|term| term.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty() && !matches!(*word, "AND" | "OR" | "NOT" | "NEAR"))
+        .map(|word| Regex::new(&format!("(?i){}", regex::escape(word))).unwrap())
+        .collect::<Vec<_>>();
+    Ok(FoundCats {
+        cats,
+        names: words.clone(),
+        breeds: words,
+        summary: false,
+    })
+}
+
+impl Printable for FoundCats {
+    fn print_display(&self, style: Style) {
+        let cats = &self.cats;
+        if cats.is_empty() {
+            return None::<Cat>.print_display(style);
+        }
+        let mut table = cat_table(cats);
+        if self.summary {
+            let average_age =
+                cats.iter().map(|cat| cat.age as f64).sum::<f64>() / cats.len() as f64;
+            let mut footer = vec![String::new(); CAT_TITLES.len()];
+            let noun = tr(if cats.len() == 1 { "cat" } else { "cats" });
+            footer[1] = format!("{} {}", cats.len(), noun);
+            footer[2] = format!("{:.1} {}", average_age, tr("avg"));
+            table.add_row(footer.iter().collect());
+        }
+        let [name, breed] =
+            ["Name", "Breed"].map(|title| CAT_TITLES.iter().position(|t| *t == title));
+        terminal::print_highlighting(table, style, |row, column, _| {
+            // The summary isn't a cat, so it has nothing to highlight.
+            let cat = match cats.get(row) {
+                Some(cat) => cat,
+                None => return false,
+            };
+            let (text, matchers) = match Some(column) {
+                column if column == name => (Some(&*cat.name), &self.names),
+                column if column == breed => (cat.breed.as_deref(), &self.breeds),
+                _ => return false,
+            };
+            text.is_some_and(|text| matchers.iter().any(|matcher| matcher.is_match(text)))
+        });
+        // A single cat still gets the rest of the detail view.
+        if let ([cat], false) = (&cats[..], self.summary) {
+            cat.print_details();
+        }
+    }
+    fn print_plain(&self) {
+        self.cats.print_plain();
+    }
+    fn print_json(&self) {
+        self.cats.print_json();
+    }
+    fn print_toml(&self) {
+        self.cats.print_toml();
+    }
+    fn print_template(&self, template: &Template) {
+        self.cats.print_template(template);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        self.cats.print_records(dialect);
+    }
+    fn cats(&self) -> Option<Vec<&Cat>> {
+        self.cats.cats()
+    }
+}
+
+pub fn saved_searches(conn: &Connection, cmd: CmdSearch) -> Result<Vec<SavedSearch>> {
+    match cmd {
+        CmdSearch::Save { save_as: name, cmd } => {
+            // Only the options that were actually set are kept, which is also what gets listed.
+            let mut options = serde_json::to_value(cmd)?;
+            if let Value::Object(options) = &mut options {
+                options.retain(|_, value| !matches!(value, Value::Null | Value::Bool(false)));
+            }
+            conn.execute(
+                "INSERT OR REPLACE INTO saved_searches (name, options) VALUES (?, ?)",
+                params![name, options.to_string()],
+            )?;
+            Ok(vec![SavedSearch { name, options }])
+        }
+        CmdSearch::List => conn
+            .prepare("SELECT name, options FROM saved_searches ORDER BY name")?
+            .query_and_then([], |row| {
+                Ok(SavedSearch {
+                    name: row.get(0)?,
+                    options: serde_json::from_str(&row.get::<_, String>(1)?)?,
+                })
+            })?
+            .collect(),
+        CmdSearch::Remove { name } => {
+            let options = conn
+                .query_row(
+                    "DELETE FROM saved_searches WHERE name = ? RETURNING options",
+                    [&name],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+                .ok_or_else(|| no_saved_search(&name))?;
+            Ok(vec![SavedSearch {
+                name,
+                options: serde_json::from_str(&options)?,
+            }])
+        }
+        CmdSearch::Run { .. } => unreachable!("saved searches are run as `find`"),
+    }
+}
+
+/// Looks up the `find` options of a saved search.
+pub fn saved_search(conn: &Connection, name: &str) -> Result<CmdFind> {
+    let options = conn
+        .query_row(
+            "SELECT options FROM saved_searches WHERE name = ?",
+            [name],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .ok_or_else(|| no_saved_search(name))?;
+    serde_json::from_str(&options)
+        .with_context(|| format!("The saved search `{}` is invalid", name))
+}
+
+fn no_saved_search(name: &str) -> Error {
+    anyhow!(
+        "There is no saved search named `{}`, add one with `cats search save`",
+        name
+    )
+}
+
+pub fn field(conn: &Connection, cmd: CmdField) -> Result<Vec<Field>> {
+    match cmd {
+        CmdField::Define { name, field_type } => {
+            // Names can't have `=` or comparisons in them, since `--set` and `--where` split on those.
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                bail!(
+                    "Invalid field name `{}`, expected letters, digits, hyphens, and underscores",
+                    name
+                );
+            }
+            let added = conn.execute(
+                "INSERT OR IGNORE INTO fields (name, type) VALUES (?, ?)",
+                params![name, field_type.as_str()],
+            )?;
+            if added == 0 {
+                bail!("There is already a field named `{}`", name);
+            }
+            Ok(vec![Field { name, field_type }])
+        }
+        CmdField::List => conn
+            .prepare("SELECT name, type FROM fields ORDER BY name")?
+            .query_and_then([], |row| {
+                Ok(Field {
+                    name: row.get(0)?,
+                    field_type: row.get::<_, String>(1)?.parse()?,
+                })
+            })?
+            .collect(),
+        CmdField::Remove { name } => atomic(conn, || {
+            let field_type = conn
+                .query_row(
+                    "DELETE FROM fields WHERE name = ? RETURNING type",
+                    [&name],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+                .ok_or_else(|| no_such_field(&name))?;
+            conn.execute("DELETE FROM attributes WHERE field = ?", [&name])?;
+            Ok(vec![Field {
+                name: name.clone(),
+                field_type: field_type.parse()?,
+            }])
+        }),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+}
+
+impl Printable for Vec<Field> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No custom fields exist"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Name", "Type"]));
+        for field in self {
+            table.add_row(row![field.name, field.field_type]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for field in self {
+            println!("{} {}", field.name, field.field_type)
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("fields", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub options: Value,
+}
+
+impl Printable for Vec<SavedSearch> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No saved searches exist"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Name", "Options"]));
+        for search in self {
+            table.add_row(row![search.name, search.options]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for search in self {
+            println!("{} {}", search.name, search.options)
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("searches", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(
+            dialect,
+            &["name", "options"],
+            self.iter()
+                .map(|search| vec![search.name.clone(), search.options.to_string()]),
+        );
+    }
+}
+
+pub fn update(store: &dyn CatStore, id: u64, cmd: CmdUpdate) -> Result<Option<Cat>> {
+    check_birthdate(cmd.birthdate)?;
+    store.update(id, &cmd)
+}
+
+/// Makes the same change to every cat matching the `--where-` options, in one transaction.
+pub fn update_all(conn: &Connection, mut cmd: CmdUpdate) -> Result<Vec<Cat>> {
+    check_birthdate(cmd.birthdate)?;
+    let find = mem::take(&mut cmd.filter).into_find();
+    let store = SqliteStore::new(conn);
+    let cats = store.find(&find)?;
+    if !cmd.yes && !cats.is_empty() && atty::is(atty::Stream::Stdin) {
+        confirm_cats(&cats, "Update")?;
+    }
+    atomic(conn, || {
+        cats.iter()
+            .filter_map(|cat| {
+                store
+                    .update(cat.id, &cmd)
+                    .with_context(|| format!("Couldn't update {}, {}", cat.id, cat.name))
+                    .transpose()
+            })
+            .collect()
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    statement: String,
+    /// Each parameter as an SQL literal, in the order of the placeholders.
+    params: Vec<String>,
+    plan: Vec<PlanStep>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanStep {
+    id: i64,
+    /// The step this is part of, or 0 for the top level.
+    parent: i64,
+    detail: String,
+}
+
+pub fn explain_find(conn: &Connection, cmd: CmdFind) -> Result<Explanation> {
+    let (statement, params) = if cmd.count {
+        count_stmt(&cmd)
+    } else {
+        find_stmt(&cmd)
+    };
+    explain(conn, statement, params)
+}
+
+pub fn explain_update(conn: &Connection, id: u64, cmd: CmdUpdate) -> Result<Explanation> {
+    let (statement, params) = update_stmt(id, &cmd);
+    explain(conn, statement, params)
+}
+
+/// Asks SQLite how it would run a statement, without running it.
+fn explain(conn: &Connection, statement: String, params: Vec<SqlValue>) -> Result<Explanation> {
+    let plan = conn
+        .prepare(&format!("EXPLAIN QUERY PLAN {}", statement))?
+        .query_map(params_from_iter(&params), |row| {
+            Ok(PlanStep {
+                id: row.get("id")?,
+                parent: row.get("parent")?,
+                detail: row.get("detail")?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(Explanation {
+        statement,
+        params: params.iter().map(|param| literal(param.into())).collect(),
+        plan,
+    })
+}
+
+impl Printable for Explanation {
+    fn print_display(&self, _style: Style) {
+        println!("{}", self.statement);
+        if !self.params.is_empty() {
+            println!();
+            println!("{}", tr("Parameters:"));
+            for (i, param) in self.params.iter().enumerate() {
+                println!("  ?{} = {}", i + 1, param);
+            }
+        }
+        println!();
+        println!("{}", tr("Query plan:"));
+        // Steps come out in order, each after the step it is part of.
+        let mut depths = Vec::<(i64, usize)>::new();
+        for step in &self.plan {
+            let depth = depths
+                .iter()
+                .find(|(id, _)| *id == step.parent)
+                .map_or(0, |(_, depth)| depth + 1);
+            depths.push((step.id, depth));
+            println!("  {}{}", "  ".repeat(depth), step.detail);
+        }
+    }
+    fn print_plain(&self) {
+        println!("{}", self.statement);
+        for (i, param) in self.params.iter().enumerate() {
+            println!("?{} {}", i + 1, param);
+        }
+        for step in &self.plan {
+            println!("{} {} {}", step.id, step.parent, step.detail);
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("explanation", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        // The statement and its parameters don't fit in rows, so only the plan is written.
+        write_rows(dialect, &self.plan);
+    }
+}
+
+pub fn history(conn: &Connection, id: Option<u64>) -> Result<Vec<HistoryEntry>> {
+    conn.prepare("SELECT * FROM cat_history WHERE ?1 ISNULL OR cat_id = ?1 ORDER BY id")?
+        .query_map([id], HistoryEntry::from_row)?
+        .map(|res| Ok(res?))
+        .collect()
+}
+
+pub fn undo(conn: &Connection, id: Option<u64>) -> Result<Vec<HistoryEntry>> {
+    atomic(conn, || {
+        let entry = match id {
+            Some(id) => conn
+                .query_row(
+                    "SELECT * FROM cat_history WHERE id = ?",
+                    [id],
+                    HistoryEntry::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!("History entry #{} does not exist", id))?,
+            None => conn
+                .query_row(
+                    "SELECT * FROM cat_history AS h WHERE undoes ISNULL \
+                    AND NOT EXISTS (SELECT * FROM cat_history WHERE undoes = h.id) \
+                    ORDER BY id DESC LIMIT 1",
+                    [],
+                    HistoryEntry::from_row,
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!("There is nothing to undo"))?,
+        };
+        let undone: bool = conn.query_row(
+            "SELECT EXISTS (SELECT * FROM cat_history WHERE undoes = ?)",
+            [entry.id],
+            |row| row.get(0),
+        )?;
+        if undone {
+            bail!("History entry #{} has already been undone", entry.id);
+        }
+        let gone = || anyhow!("Cat {} no longer exists", entry.cat_id);
+        let current = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ?",
+                [entry.cat_id],
+                Cat::from_row,
+            )
+            .optional()?;
+        let (action, old, new) = match entry.action {
+            Action::Add => {
+                let cat = conn
+                    .query_row(
+                        "DELETE FROM cats WHERE id = ? RETURNING *",
+                        [entry.cat_id],
+                        Cat::from_row,
+                    )
+                    .optional()?
+                    .ok_or_else(gone)?;
+                (Action::Purge, Some(cat), None)
+            }
+            Action::Update => {
+                let current = current.ok_or_else(gone)?;
+                let old = entry.old.as_ref().ok_or_else(gone)?;
+                check_relations_exist(conn, old)?;
+                (Action::Update, Some(current), overwrite(conn, old)?)
+            }
+            Action::Delete => {
+                let cat = conn
+                    .query_row(
+                        "UPDATE cats SET deleted_at = NULL WHERE id = ? RETURNING *",
+                        [entry.cat_id],
+                        Cat::from_row,
+                    )
+                    .optional()?
+                    .ok_or_else(gone)?;
+                (Action::Restore, None, Some(cat))
+            }
+            Action::Restore => {
+                let cat = conn
+                    .query_row(
+                        "UPDATE cats SET deleted_at = datetime('now') WHERE id = ? RETURNING *",
+                        [entry.cat_id],
+                        Cat::from_row,
+                    )
+                    .optional()?
+                    .ok_or_else(gone)?;
+                (Action::Delete, Some(cat), None)
+            }
+            Action::Purge => {
+                if current.is_some() {
+                    bail!("ID {} has been given to another cat", entry.cat_id);
+                }
+                let old = entry.old.as_ref().ok_or_else(gone)?;
+                check_relations_exist(conn, old)?;
+                (Action::Add, None, Some(insert(conn, old, true)?))
+            }
+        };
+        let id = record_entry(conn, action, old.as_ref(), new.as_ref(), Some(entry.id))?;
+        Ok(vec![conn.query_row(
+            "SELECT * FROM cat_history WHERE id = ?",
+            [id],
+            HistoryEntry::from_row,
+        )?])
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub cat_id: u64,
+    pub action: Action,
+    pub old: Option<Cat>,
+    pub new: Option<Cat>,
+    pub timestamp: String,
+    pub undoes: Option<u64>,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let snapshot = |idx: usize| -> rusqlite::Result<Option<Cat>> {
+            row.get::<_, Option<String>>(idx)?
+                .map(|json| serde_json::from_str(&json))
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e))
+                })
+        };
+        Ok(Self {
+            id: row.get(0)?,
+            cat_id: row.get(1)?,
+            action: row.get::<_, String>(2)?.parse().map_err(|e: Error| {
+                rusqlite::Error::FromSqlConversionFailure(2, Type::Text, e.into())
+            })?,
+            old: snapshot(3)?,
+            new: snapshot(4)?,
+            timestamp: row.get(5)?,
+            undoes: row.get(6)?,
+        })
+    }
+
+    /// The action, noting which entry it reverted if it was an undo, with `undoes` in the language
+    /// being printed in.
+    fn describe_action(&self, undoes: &str) -> String {
+        match self.undoes {
+            Some(id) => format!("{} ({} #{})", self.action.as_str(), undoes, id),
+            None => self.action.as_str().to_string(),
+        }
+    }
+
+    fn changes(&self, none: &str) -> String {
+        changes(&self.old, &self.new, none)
+    }
+}
+
+/// Fields that are kept track of by cats rather than describing the cat, so aren't worth showing
+/// as changes.
+const BOOKKEEPING: &[&str] = &["id", "created_at", "updated_at"];
+
+/// Describes which fields changed, e.g. `age: 3 -> 4`, or every field of a cat that was added.
+/// Fields without a value are shown as `none`.
+fn changes(old: &Option<Cat>, new: &Option<Cat>, none: &str) -> String {
+    let fields = |cat: &Option<Cat>| match serde_json::to_value(cat) {
+        Ok(Value::Object(map)) => map,
+        _ => Default::default(),
+    };
+    let (old, new) = (fields(old), fields(new));
+    let show = |value: &Value| match value {
+        Value::Null => none.to_string(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    match (old.is_empty(), new.is_empty()) {
+        (true, false) => new
+            .iter()
+            .filter(|(key, _)| !BOOKKEEPING.contains(&key.as_str()))
+            .map(|(key, value)| format!("{}: {}", key, show(value)))
+            .join(", "),
+        (false, false) => new
+            .iter()
+            .filter(|(key, value)| {
+                !BOOKKEEPING.contains(&key.as_str()) && old.get(*key) != Some(*value)
+            })
+            .map(|(key, value)| {
+                format!(
+                    "{}: {} -> {}",
+                    key,
+                    old.get(key).map_or_else(String::new, show),
+                    show(value)
+                )
+            })
+            .join(", "),
+        _ => String::new(),
+    }
+}
+
+impl Printable for Vec<HistoryEntry> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No history found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["#", "Cat", "Time", "Action", "Changes"]));
+        for entry in self {
+            table.add_row(row![
+                entry.id,
+                entry.cat_id,
+                entry.timestamp,
+                entry.describe_action(tr("undoes")),
+                entry.changes(tr("<none>"))
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for entry in self {
+            println!(
+                "{} {} {} {} {}",
+                entry.id,
+                entry.cat_id,
+                entry.timestamp,
+                entry.describe_action("undoes"),
+                entry.changes("<none>")
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("history", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(
+            dialect,
+            &["id", "cat_id", "timestamp", "action", "undoes", "changes"],
+            self.iter().map(|entry| {
+                vec![
+                    entry.id.to_string(),
+                    entry.cat_id.to_string(),
+                    entry.timestamp.clone(),
+                    entry.action.as_str().to_string(),
+                    entry
+                        .undoes
+                        .map_or_else(String::new, |undoes| undoes.to_string()),
+                    entry.changes("<none>"),
+                ]
+            }),
+        );
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Weighing {
+    pub date: String,
+    /// In grams.
+    pub weight: u32,
+}
+
+pub fn weigh(
+    conn: &Connection,
+    id: u64,
+    weight: Weight,
+    date: Option<Date>,
+) -> Result<Vec<Weighing>> {
+    let today = Date::today();
+    let date = date.unwrap_or(today);
+    if date > today {
+        bail!("{} is in the future", date);
+    }
+    atomic(conn, || {
+        let old = conn
+            .query_row(
+                "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                [id],
+                Cat::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+        conn.execute(
+            "INSERT INTO weights (cat_id, date, weight) VALUES (?, ?, ?)",
+            params![id, date.to_string(), weight.grams],
+        )?;
+        // Weighings can be recorded late, so the newest one isn't necessarily this one.
+        let latest: u32 = conn.query_row(
+            "SELECT weight FROM weights WHERE cat_id = ? ORDER BY date DESC, id DESC LIMIT 1",
+            [id],
+            |row| row.get(0),
+        )?;
+        let new = conn
+            .query_row(
+                "UPDATE cats SET weight = ?2, updated_at = datetime('now') \
+                WHERE id = ?1 AND weight IS NOT ?2 RETURNING *",
+                params![id, latest],
+                Cat::from_row,
+            )
+            .optional()?;
+        if let Some(new) = &new {
+            record(conn, Action::Update, Some(&old), Some(new))?;
+        }
+        weights(conn, id)
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Photo {
+    pub id: u64,
+    pub cat_id: u64,
+    /// The name of the file the photo was added from.
+    pub name: String,
+    pub format: String,
+    /// In bytes.
+    pub size: u64,
+    /// The SHA-256 of the image, in hex.
+    pub hash: String,
+    pub added_at: String,
+}
+
+impl Photo {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            cat_id: row.get("cat_id")?,
+            name: row.get("name")?,
+            format: row.get("format")?,
+            size: row.get("size")?,
+            hash: row.get("hash")?,
+            added_at: row.get("added_at")?,
+        })
+    }
+}
+
+/// Every column of `photos` but the image itself.
+const PHOTO_COLUMNS: &str = "id, cat_id, name, format, length(data) AS size, hash, added_at";
+
+/// Works out what kind of image `data` is from the bytes it starts with.
+fn image_format(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpeg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("webp"),
+        _ => None,
+    }
+}
+
+/// A file size in kilobytes, rounded up so that small files don't show as 0 KB.
+fn kilobytes(bytes: u64) -> String {
+    format!("{} KB", (bytes as f64 / 1000.0).ceil())
+}
+
+pub fn photo(conn: &Connection, cmd: CmdPhoto) -> Result<Vec<Photo>> {
+    match cmd {
+        CmdPhoto::Add { id, file } => {
+            let data =
+                fs::read(&file).with_context(|| format!("Couldn't read {}", file.display()))?;
+            let format = image_format(&data).ok_or_else(|| {
+                anyhow!(
+                    "{} doesn't look like a PNG, JPEG, GIF, or WebP image",
+                    file.display()
+                )
+            })?;
+            let name = file
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            let hash = format!("{:x}", Sha256::digest(&data));
+            atomic(conn, || {
+                conn.query_row(
+                    "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+                let existing: Option<u64> = conn
+                    .query_row(
+                        "SELECT id FROM photos WHERE cat_id = ? AND hash = ?",
+                        params![id, hash],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                if let Some(existing) = existing {
+                    bail!(
+                        "This photo is already attached to cat #{}, as photo #{}",
+                        id,
+                        existing
+                    );
+                }
+                let photo = conn.query_row(
+                    &format!(
+                        "INSERT INTO photos (cat_id, name, format, hash, data) VALUES (?, ?, ?, ?, ?) \
+                        RETURNING {}",
+                        PHOTO_COLUMNS
+                    ),
+                    params![id, name, format, hash, data],
+                    Photo::from_row,
+                )?;
+                Ok(vec![photo])
+            })
+        }
+        CmdPhoto::List { id } => conn
+            .prepare(&format!(
+                "SELECT {} FROM photos WHERE ?1 ISNULL OR cat_id = ?1 ORDER BY cat_id, id",
+                PHOTO_COLUMNS
+            ))?
+            .query_map([id], Photo::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+        CmdPhoto::Remove { photo } => Ok(vec![conn
+            .query_row(
+                &format!(
+                    "DELETE FROM photos WHERE id = ? RETURNING {}",
+                    PHOTO_COLUMNS
+                ),
+                [photo],
+                Photo::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| no_such_photo(photo))?]),
+        CmdPhoto::Export { photo, file, force } => {
+            if file.exists() && !force {
+                bail!(
+                    "{} already exists, pass --force to replace it",
+                    file.display()
+                );
+            }
+            let (data, photo) = conn
+                .query_row(
+                    &format!("SELECT data, {} FROM photos WHERE id = ?", PHOTO_COLUMNS),
+                    [photo],
+                    |row| Ok((row.get::<_, Vec<u8>>("data")?, Photo::from_row(row)?)),
+                )
+                .optional()?
+                .ok_or_else(|| no_such_photo(photo))?;
+            fs::write(&file, data).with_context(|| format!("Couldn't write {}", file.display()))?;
+            Ok(vec![photo])
+        }
+    }
+}
+
+fn no_such_photo(photo: u64) -> Error {
+    anyhow!(
+        "There is no photo #{}, see `cats photo list` for the photos there are",
+        photo
+    )
+}
+
+impl Printable for Vec<Photo> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No photos found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&[
+            "#", "Cat", "Name", "Format", "Size", "SHA-256", "Added",
+        ]));
+        for photo in self {
+            table.add_row(row![
+                photo.id,
+                photo.cat_id,
+                photo.name,
+                photo.format,
+                kilobytes(photo.size),
+                // The start of the hash is plenty to tell photos apart by eye.
+                &photo.hash[..12],
+                photo.added_at
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for photo in self {
+            println!(
+                "{} {} {} {} {} {}",
+                photo.id, photo.cat_id, photo.name, photo.format, photo.size, photo.hash
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("photos", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Attachment {
+    pub id: u64,
+    pub cat_id: u64,
+    /// The name of the file the attachment was added from.
+    pub name: String,
+    pub mime_type: String,
+    pub description: Option<String>,
+    /// In bytes, or `None` for linked files.
+    pub size: Option<u64>,
+    /// The SHA-256 of the file as it was attached, in hex.
+    pub hash: String,
+    /// Where the file is, for attachments that are only linked to.
+    pub path: Option<String>,
+    pub added_at: String,
+}
+
+impl Attachment {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            cat_id: row.get("cat_id")?,
+            name: row.get("name")?,
+            mime_type: row.get("mime_type")?,
+            description: row.get("description")?,
+            size: row.get("size")?,
+            hash: row.get("hash")?,
+            path: row.get("path")?,
+            added_at: row.get("added_at")?,
+        })
+    }
+}
+
+/// Every column of `attachments` but the file itself.
+const ATTACHMENT_COLUMNS: &str =
+    "id, cat_id, name, mime_type, description, length(data) AS size, hash, path, added_at";
+
+/// Works out the MIME type of a file from the bytes it starts with, or else its extension.
+fn mime_type(file: &Path, data: &[u8]) -> String {
+    if let Some(format) = image_format(data) {
+        return format!("image/{}", format);
+    }
+    if data.starts_with(b"%PDF") {
+        return "application/pdf".to_string();
+    }
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("txt") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("html") | Some("htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("doc") => "application/msword",
+        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Some("odt") => "application/vnd.oasis.opendocument.text",
+        Some("rtf") => "application/rtf",
+        _ => "application/octet-stream",
+    }
+    .to_string()
+}
+
+pub fn attachment(conn: &Connection, cmd: CmdAttachment) -> Result<Vec<Attachment>> {
+    match cmd {
+        CmdAttachment::Add {
+            id,
+            file,
+            description,
+            mime_type: given_type,
+            link,
+        } => {
+            let data =
+                fs::read(&file).with_context(|| format!("Couldn't read {}", file.display()))?;
+            let mime_type = given_type.unwrap_or_else(|| mime_type(&file, &data));
+            let name = file
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            let hash = format!("{:x}", Sha256::digest(&data));
+            // Linked files are found again from wherever cats is run.
+            let (path, data) = if link {
+                let path = fs::canonicalize(&file)
+                    .with_context(|| format!("Couldn't find {}", file.display()))?;
+                (Some(path.to_string_lossy().into_owned()), None)
+            } else {
+                (None, Some(data))
+            };
+            atomic(conn, || {
+                conn.query_row(
+                    "SELECT 1 FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .ok_or_else(|| anyhow!(NoSuchCat(id)))?;
+                let existing: Option<u64> = conn
+                    .query_row(
+                        "SELECT id FROM attachments WHERE cat_id = ? AND hash = ?",
+                        params![id, hash],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                if let Some(existing) = existing {
+                    bail!(
+                        "This file is already attached to cat #{}, as attachment #{}",
+                        id,
+                        existing
+                    );
+                }
+                let attachment = conn.query_row(
+                    &format!(
+                        "INSERT INTO attachments (cat_id, name, mime_type, description, hash, \
+                        path, data) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING {}",
+                        ATTACHMENT_COLUMNS
+                    ),
+                    params![id, name, mime_type, description, hash, path, data],
+                    Attachment::from_row,
+                )?;
+                Ok(vec![attachment])
+            })
+        }
+        CmdAttachment::List { id } => conn
+            .prepare(&format!(
+                "SELECT {} FROM attachments WHERE ?1 ISNULL OR cat_id = ?1 ORDER BY cat_id, id",
+                ATTACHMENT_COLUMNS
+            ))?
+            .query_map([id], Attachment::from_row)?
+            .map(|res| Ok(res?))
+            .collect(),
+        CmdAttachment::Remove { attachment } => Ok(vec![conn
+            .query_row(
+                &format!(
+                    "DELETE FROM attachments WHERE id = ? RETURNING {}",
+                    ATTACHMENT_COLUMNS
+                ),
+                [attachment],
+                Attachment::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| no_such_attachment(attachment))?]),
+        CmdAttachment::Export {
+            attachment,
+            file,
+            force,
+        } => {
+            if file.exists() && !force {
+                bail!(
+                    "{} already exists, pass --force to replace it",
+                    file.display()
+                );
+            }
+            let (data, attachment) = conn
+                .query_row(
+                    &format!(
+                        "SELECT data, {} FROM attachments WHERE id = ?",
+                        ATTACHMENT_COLUMNS
+                    ),
+                    [attachment],
+                    |row| {
+                        Ok((
+                            row.get::<_, Option<Vec<u8>>>("data")?,
+                            Attachment::from_row(row)?,
+                        ))
+                    },
+                )
+                .optional()?
+                .ok_or_else(|| no_such_attachment(attachment))?;
+            match (data, &attachment.path) {
+                (Some(data), _) => fs::write(&file, data)
+                    .with_context(|| format!("Couldn't write {}", file.display()))?,
+                (None, Some(path)) => {
+                    fs::copy(path, &file)
+                        .with_context(|| format!("Couldn't copy {} to {}", path, file.display()))?;
+                }
+                (None, None) => unreachable!("the schema requires a path or the data"),
+            }
+            Ok(vec![attachment])
+        }
+    }
+}
+
+fn no_such_attachment(attachment: u64) -> Error {
+    anyhow!(
+        "There is no attachment #{}, see `cats attachment list` for the attachments there are",
+        attachment
+    )
+}
+
+impl Printable for Vec<Attachment> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No attachments found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&[
+            "#",
+            "Cat",
+            "Name",
+            "Type",
+            "Size",
+            "Description",
+            "Added",
+        ]));
+        for attachment in self {
+            table.add_row(row![
+                attachment.id,
+                attachment.cat_id,
+                attachment.name,
+                attachment.mime_type,
+                match (attachment.size, &attachment.path) {
+                    (Some(size), _) => kilobytes(size),
+                    (None, Some(path)) => format!("{}: {}", tr("linked"), path),
+                    (None, None) => tr("<unknown>").to_string(),
+                },
+                attachment.description.as_deref().unwrap_or(tr("<none>")),
+                attachment.added_at
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for attachment in self {
+            println!(
+                "{} {} {} {} {}",
+                attachment.id,
+                attachment.cat_id,
+                attachment.name,
+                attachment.mime_type,
+                attachment.hash
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("attachments", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+pub fn weights(conn: &Connection, id: u64) -> Result<Vec<Weighing>> {
+    conn.prepare("SELECT date, weight FROM weights WHERE cat_id = ? ORDER BY date, id")?
+        .query_map([id], |row| {
+            Ok(Weighing {
+                date: row.get(0)?,
+                weight: row.get(1)?,
+            })
+        })?
+        .map(|res| Ok(res?))
+        .collect()
+}
+
+impl Printable for Vec<Weighing> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No weights have been recorded for this cat"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Date", "Weight", "Change"]));
+        let mut previous = None;
+        for weighing in self {
+            let change = previous.map_or_else(String::new, |previous: u32| {
+                let grams = i64::from(weighing.weight) - i64::from(previous);
+                format!("{:+}kg", grams as f64 / 1000.0)
+            });
+            let weight = Weight {
+                grams: weighing.weight,
+            };
+            table.add_row(row![weighing.date, weight, change]);
+            previous = Some(weighing.weight);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for weighing in self {
+            let weight = Weight {
+                grams: weighing.weight,
+            };
+            println!("{} {}", weighing.date, weight)
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("weights", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub total: u64,
+    pub average_age: Option<f64>,
+    pub median_age: Option<f64>,
+    pub min_age: Option<u32>,
+    pub max_age: Option<u32>,
+    pub breeds: u64,
+    pub no_breed: u64,
+}
+
+pub fn stats(conn: &Connection) -> Result<Stats> {
+    // Every age is as of today, for cats with a birthdate.
+    let living = format!(
+        "(SELECT name, breed, {} AS age FROM cats WHERE deleted_at ISNULL AND deceased_at ISNULL)",
+        AGE
+    );
+    let (total, average_age, min_age, max_age, breeds, no_breed) = conn.query_row(
+        &format!(
+            "SELECT COUNT(*), AVG(age), MIN(age), MAX(age), COUNT(DISTINCT breed), \
+            COUNT(*) - COUNT(breed) FROM {}",
+            living
+        ),
+        [],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        },
+    )?;
+    // The middle age, or the average of the middle two if there are an even number of cats.
+    let median_age = conn.query_row(
+        &format!(
+            "SELECT AVG(age) FROM (SELECT age FROM {} ORDER BY age \
+            LIMIT 2 - ?1 % 2 OFFSET (?1 - 1) / 2)",
+            living
+        ),
+        [total],
+        |row| row.get(0),
+    )?;
+    Ok(Stats {
+        total,
+        average_age,
+        median_age,
+        min_age,
+        max_age,
+        breeds,
+        no_breed,
+    })
+}
+
+impl Printable for Stats {
+    fn print_display(&self, style: Style) {
+        let average =
+            |age: Option<f64>| age.map_or_else(|| "-".to_string(), |age| format!("{:.1}", age));
+        let age = |age: Option<u32>| age.map_or_else(|| "-".to_string(), |age| age.to_string());
+        let mut table = table!([
+            self.total,
+            average(self.average_age),
+            average(self.median_age),
+            age(self.min_age),
+            age(self.max_age),
+            self.breeds,
+            self.no_breed
+        ]);
+        table.set_titles(titles(&[
+            "Cats",
+            "Average age",
+            "Median age",
+            "Youngest",
+            "Oldest",
+            "Breeds",
+            "No breed",
+        ]));
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        let show = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        println!(
+            "{} {} {} {} {} {} {}",
+            self.total,
+            show(self.average_age.map(|age| age.to_string())),
+            show(self.median_age.map(|age| age.to_string())),
+            show(self.min_age.map(|age| age.to_string())),
+            show(self.max_age.map(|age| age.to_string())),
+            self.breeds,
+            self.no_breed
+        )
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("stats", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, Some(self));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupStats {
+    /// The breed, age, or initial the cats in this group share.
+    pub group: Option<String>,
+    pub total: u64,
+    pub average_age: f64,
+    pub min_age: u32,
+    pub max_age: u32,
+}
+
+pub fn group_stats(conn: &Connection, by: GroupBy) -> Result<Vec<GroupStats>> {
+    let (group, order) = match by {
+        GroupBy::Breed => ("breed", "grp"),
+        GroupBy::Age => ("CAST(age AS TEXT)", "MIN(age)"),
+        GroupBy::NameInitial => ("upper(substr(name, 1, 1))", "grp"),
+    };
+    let stmt = format!(
+        "SELECT {} AS grp, COUNT(*), AVG(age), MIN(age), MAX(age) \
+        FROM (SELECT name, breed, {} AS age FROM cats \
+        WHERE deleted_at ISNULL AND deceased_at ISNULL) \
+        GROUP BY grp ORDER BY {}",
+        group, AGE, order
+    );
+    Ok(conn
+        .prepare(&stmt)?
+        .query_map([], |row| {
+            Ok(GroupStats {
+                group: row.get(0)?,
+                total: row.get(1)?,
+                average_age: row.get(2)?,
+                min_age: row.get(3)?,
+                max_age: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?)
+}
+
+impl Printable for Vec<GroupStats> {
+    fn print_display(&self, style: Style) {
+        let mut table = Table::new();
+        table.set_titles(titles(&[
+            "Group",
+            "Cats",
+            "Average age",
+            "Youngest",
+            "Oldest",
+        ]));
+        for group in self {
+            table.add_row(row![
+                group.group.as_deref().unwrap_or(tr("<none>")),
+                group.total,
+                format!("{:.1}", group.average_age),
+                group.min_age,
+                group.max_age
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for group in self {
+            println!(
+                "{} {} {} {} {}",
+                group.group.as_deref().unwrap_or("<none>"),
+                group.total,
+                group.average_age,
+                group.min_age,
+                group.max_age
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("groups", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+/// A bar in one of the charts from `stats --chart`.
+#[derive(Debug, Serialize)]
+pub struct ChartBar {
+    /// Which chart the bar is in, `age` or `breed`.
+    pub chart: &'static str,
+    pub group: Option<String>,
+    pub cats: u64,
+}
+
+#[derive(Debug)]
+pub struct StatsChart(pub Vec<ChartBar>);
+
+pub fn stats_chart(conn: &Connection) -> Result<StatsChart> {
+    let mut bars = Vec::new();
+    let mut next_age = None;
+    for group in group_stats(conn, GroupBy::Age)? {
+        // Ages that no cat is still get a bar, so that the histogram's spacing is true to them.
+        for age in next_age.unwrap_or(group.min_age)..group.min_age {
+            bars.push(ChartBar {
+                chart: "age",
+                group: Some(age.to_string()),
+                cats: 0,
+            });
+        }
+        next_age = Some(group.min_age + 1);
+        bars.push(ChartBar {
+            chart: "age",
+            group: group.group,
+            cats: group.total,
+        });
+    }
+    let mut breeds = group_stats(conn, GroupBy::Breed)?;
+    // The most common breeds go first, and ties stay alphabetical.
+    breeds.sort_by_key(|group| Reverse(group.total));
+    bars.extend(breeds.into_iter().map(|group| ChartBar {
+        chart: "breed",
+        group: group.group,
+        cats: group.total,
+    }));
+    Ok(StatsChart(bars))
+}
+
+impl Printable for StatsChart {
+    fn print_display(&self, style: Style) {
+        for (i, (chart, title)) in [("age", "Age"), ("breed", "Breed")].iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            println!("{}", tr(title));
+            let bars = self
+                .0
+                .iter()
+                .filter(|bar| bar.chart == *chart)
+                .map(|bar| (bar.group.as_deref().unwrap_or(tr("<none>")), bar.cats))
+                .collect::<Vec<_>>();
+            terminal::print_bars(&bars, style);
+        }
+    }
+    fn print_plain(&self) {
+        for bar in &self.0 {
+            println!(
+                "{} {} {}",
+                bar.chart,
+                bar.group.as_deref().unwrap_or("<none>"),
+                bar.cats
+            );
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), &self.0).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("bars", &self.0);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(&self.0);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, &self.0);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Breed {
+    pub breed: String,
+    pub cats: u64,
+    /// Other breeds that are probably this one spelled differently.
+    pub similar: Vec<String>,
+}
+
+pub fn breeds(conn: &Connection) -> Result<Vec<Breed>> {
+    let mut breeds = conn
+        .prepare(
+            "SELECT breed, COUNT(*) FROM cats WHERE deleted_at ISNULL AND breed NOTNULL \
+            GROUP BY breed ORDER BY COUNT(*) DESC, breed",
+        )?
+        .query_map([], |row| {
+            Ok(Breed {
+                breed: row.get(0)?,
+                cats: row.get(1)?,
+                similar: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    // Spacing, punctuation, and case are ignored, and longer breeds can have more typos.
+    let normalized = breeds
+        .iter()
+        .map(|breed| {
+            breed
+                .breed
+                .to_lowercase()
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    for i in 0..breeds.len() {
+        for j in 0..breeds.len() {
+            let (a, b) = (&normalized[i], &normalized[j]);
+            let typos = a.chars().count().min(b.chars().count()) / 3;
+            if i != j && levenshtein(a, b) <= typos {
+                let similar = breeds[j].breed.clone();
+                breeds[i].similar.push(similar);
+            }
+        }
+    }
+    Ok(breeds)
+}
+
+impl Printable for Vec<Breed> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No cats have a breed"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Breed", "Cats", "Similar to"]));
+        for breed in self {
+            table.add_row(row![breed.breed, breed.cats, breed.similar.join(", ")]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for breed in self {
+            println!("{} {} {}", breed.breed, breed.cats, breed.similar.join(","))
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("breeds", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(
+            dialect,
+            &["breed", "cats", "similar"],
+            self.iter().map(|breed| {
+                vec![
+                    breed.breed.clone(),
+                    breed.cats.to_string(),
+                    breed.similar.join(", "),
+                ]
+            }),
+        );
+    }
+}
+
+pub fn export(conn: &Connection, cmd: CmdExport) -> Result<()> {
+    let format = cmd
+        .format
+        .or_else(|| cmd.file.as_deref().and_then(FileFormat::from_path))
+        .unwrap_or(FileFormat::Json);
+    let mut out: Box<dyn Write> = match &cmd.file {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    let cats = || -> Result<Vec<Cat>> {
+        Ok(conn
+            .prepare("SELECT * FROM cats WHERE deleted_at ISNULL ORDER BY id")?
+            .query_map([], Cat::from_row)?
+            .collect::<rusqlite::Result<_>>()?)
+    };
+    match format {
+        FileFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &cats()?)?;
+            writeln!(out)?;
+        }
+        FileFormat::Ndjson => {
+            for cat in &cats()? {
+                serde_json::to_writer(&mut out, cat)?;
+                writeln!(out)?;
+            }
+        }
+        FileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut out);
+            for cat in &cats()? {
+                writer.serialize(cat)?;
+            }
+            writer.flush()?;
+        }
+        FileFormat::Sql => dump(conn, &mut out)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes every table and index as SQL, in the same shape as the sqlite3 shell's `.dump`.
+fn dump(conn: &Connection, out: &mut dyn Write) -> Result<()> {
+    writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    // Tables come first, so that indexes are created after the rows are inserted.
+    let schema = conn
+        .prepare(
+            "SELECT type, name, sql FROM sqlite_master \
+            WHERE sql NOTNULL AND name NOT LIKE 'sqlite_%' ORDER BY type != 'table', rowid",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    // Virtual tables keep their data in shadow tables named after them, which they create themselves.
+    let virtual_tables = schema
+        .iter()
+        .filter(|(_, _, sql)| sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(_, name, _)| format!("{}_", name))
+        .collect::<Vec<_>>();
+    for (kind, name, sql) in &schema {
+        if virtual_tables.iter().any(|prefix| name.starts_with(prefix)) {
+            continue;
+        }
+        writeln!(out, "{};", sql)?;
+        if kind != "table" || sql.starts_with("CREATE VIRTUAL TABLE") {
+            continue;
+        }
+        let name = format!("\"{}\"", name.replace('"', "\"\""));
+        let mut stmt = conn.prepare(&format!("SELECT * FROM {} ORDER BY rowid", name))?;
+        let columns = stmt.column_count();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = (0..columns)
+                .map(|i| Ok(literal(row.get_ref(i)?)))
+                .collect::<Result<Vec<_>>>()?;
+            writeln!(out, "INSERT INTO {} VALUES({});", name, values.join(","))?;
+        }
+    }
+    // The rows went in before the triggers that index them, so the search index starts out empty.
+    writeln!(out, "INSERT INTO cats_fts (cats_fts) VALUES ('rebuild');")?;
+    writeln!(out, "PRAGMA user_version={};", migrations::version(conn)?)?;
+    writeln!(out, "COMMIT;")?;
+    Ok(())
+}
+
+/// Writes a value the way it would appear in SQL.
+fn literal(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => format!("{:e}", f),
+        ValueRef::Text(s) => quote(&String::from_utf8_lossy(s)),
+        ValueRef::Blob(b) => format!(
+            "X'{}'",
+            b.iter().map(|byte| format!("{:02X}", byte)).join("")
+        ),
+    }
+}
+
+pub fn import(conn: &Connection, cmd: CmdImport) -> Result<ImportSummary> {
+    let format = cmd
+        .format
+        .or_else(|| FileFormat::from_path(&cmd.file))
+        .unwrap_or(FileFormat::Json);
+    let input: Box<dyn Read> = if cmd.file == Path::new("-") {
+        Box::new(io::stdin())
+    } else {
+        Box::new(BufReader::new(File::open(&cmd.file)?))
+    };
+    let cats: Vec<Cat> = match format {
+        FileFormat::Json => serde_json::from_reader(input)?,
+        FileFormat::Ndjson => BufReader::new(input)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<_>>()?,
+        FileFormat::Csv => csv::Reader::from_reader(input)
+            .deserialize()
+            .collect::<csv::Result<_>>()?,
+        FileFormat::Sql => bail!("SQL dumps can't be imported, load them with the sqlite3 shell"),
+    };
+    import_cats(conn, cats, cmd.key, cmd.on_conflict)
+}
+
+fn import_cats(
+    conn: &Connection,
+    cats: Vec<Cat>,
+    key: ImportKey,
+    on_conflict: OnConflict,
+) -> Result<ImportSummary> {
+    atomic(conn, || {
+        let mut summary = ImportSummary::default();
+        for cat in &cats {
+            let existing = match key {
+                ImportKey::Id => conn
+                    .query_row("SELECT * FROM cats WHERE id = ?", [cat.id], Cat::from_row)
+                    .optional()?,
+                ImportKey::NameBreed => conn
+                    .query_row(
+                        "SELECT * FROM cats WHERE name = ? AND breed IS ? AND deleted_at ISNULL \
+                        ORDER BY id LIMIT 1",
+                        params![cat.name, cat.breed],
+                        Cat::from_row,
+                    )
+                    .optional()?,
+            };
+            let existing = match existing {
+                Some(existing) => existing,
+                None => {
+                    // Owners, parents, and locations belong to a registry, so cats from elsewhere
+                    // arrive without them.
+                    let cat = Cat {
+                        owner_id: None,
+                        location_id: None,
+                        mother_id: None,
+                        father_id: None,
+                        litter_id: None,
+                        ..cat.clone()
+                    };
+                    let added = insert(conn, &cat, key == ImportKey::Id)?;
+                    record(conn, Action::Add, None, Some(&added))?;
+                    summary.added += 1;
+                    continue;
+                }
+            };
+            let updated = match on_conflict {
+                OnConflict::Skip => None,
+                OnConflict::Overwrite => overwrite(
+                    conn,
+                    &Cat {
+                        id: existing.id,
+                        owner_id: existing.owner_id,
+                        location_id: existing.location_id,
+                        mother_id: existing.mother_id,
+                        father_id: existing.father_id,
+                        litter_id: existing.litter_id,
+                        ..cat.clone()
+                    },
+                )?,
+                OnConflict::Merge => conn
+                    .prepare_cached(
+                        "UPDATE cats SET breed = ?, updated_at = datetime('now') \
+                        WHERE id = ? AND breed ISNULL AND ? NOTNULL RETURNING *",
+                    )?
+                    .query_row(params![cat.breed, existing.id, cat.breed], Cat::from_row)
+                    .optional()?,
+            };
+            if let Some(updated) = updated {
+                record(conn, Action::Update, Some(&existing), Some(&updated))?;
+                summary.updated += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        Ok(summary)
+    })
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub added: u64,
+    pub updated: u64,
+    pub skipped: u64,
+}
+
+impl Printable for ImportSummary {
+    fn print_display(&self, style: Style) {
+        let mut table = table!([self.added, self.updated, self.skipped]);
+        table.set_titles(titles(&["Added", "Updated", "Skipped"]));
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        println!("{} {} {}", self.added, self.updated, self.skipped)
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("import", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, Some(self));
+    }
+}
+
+/// The oldest a cat can plausibly be. The oldest cat on record lived to 38.
+const MAX_AGE: i64 = 50;
+
+/// Rows that can be read as a `Cat` at all. Anything else was written by something other than cats.
+const WELL_TYPED: &str = "typeof(name) = 'text' AND typeof(age) = 'integer' \
+    AND age BETWEEN 0 AND 4294967295 AND (breed ISNULL OR typeof(breed) = 'text') \
+    AND (created_at ISNULL OR typeof(created_at) = 'text') \
+    AND (updated_at ISNULL OR typeof(updated_at) = 'text') \
+    AND (birthdate ISNULL OR typeof(birthdate) = 'text') \
+    AND (weight ISNULL OR typeof(weight) = 'integer' AND weight BETWEEN 0 AND 4294967295) \
+    AND sex IN ('male', 'female', 'unknown') AND fixed IN (0, 1) \
+    AND (color ISNULL OR typeof(color) = 'text') AND (pattern ISNULL OR typeof(pattern) = 'text') \
+    AND (microchip ISNULL OR typeof(microchip) = 'text') \
+    AND (notes ISNULL OR typeof(notes) = 'text') \
+    AND (owner_id ISNULL OR typeof(owner_id) = 'integer') \
+    AND (mother_id ISNULL OR typeof(mother_id) = 'integer') \
+    AND (father_id ISNULL OR typeof(father_id) = 'integer') \
+    AND (litter_id ISNULL OR typeof(litter_id) = 'integer') \
+    AND status IN ('intake', 'available', 'fostered', 'adopted', 'returned') \
+    AND (adopted_at ISNULL OR typeof(adopted_at) = 'text') \
+    AND (deceased_at ISNULL OR typeof(deceased_at) = 'text') \
+    AND (location_id ISNULL OR typeof(location_id) = 'integer') \
+    AND (good_with_dogs ISNULL OR good_with_dogs IN (0, 1)) \
+    AND (good_with_kids ISNULL OR good_with_kids IN (0, 1)) \
+    AND (energy ISNULL OR energy IN (1, 2, 3, 4, 5)) \
+    AND (litter_trained ISNULL OR litter_trained IN (0, 1)) \
+    AND (diet ISNULL OR typeof(diet) = 'text') \
+    AND (license_number ISNULL OR typeof(license_number) = 'text') \
+    AND (license_expires ISNULL OR typeof(license_expires) = 'text')";
+
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    pub description: String,
+    pub fix: String,
+    pub fixed: bool,
+}
+
+impl Problem {
+    fn new(description: String, fix: impl Into<String>) -> Self {
+        Self {
+            description,
+            fix: fix.into(),
+            fixed: false,
+        }
+    }
+}
+
+pub fn doctor(conn: &Connection, fix: bool) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let integrity = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if integrity != ["ok"] {
+        // Nothing else can be trusted in a corrupted database.
+        return Ok(integrity
+            .into_iter()
+            .map(|message| {
+                Problem::new(
+                    format!("The database is corrupted: {}", message),
+                    "Restore the registry from a backup with `restore --file`",
+                )
+            })
+            .collect());
+    }
+    let version = migrations::version(conn)?;
+    if version != migrations::LATEST {
+        problems.push(Problem::new(
+            format!(
+                "The registry is at schema version {}, but this version of cats expects {}",
+                version,
+                migrations::LATEST
+            ),
+            "Run any command without --read-only, or use a matching version of cats",
+        ));
+    }
+    let mut expected = Connection::open_in_memory()?;
+    migrations::migrate(&mut expected)?;
+    for table in ["cats", "cat_history"] {
+        if columns(conn, table)? != columns(&expected, table)? {
+            problems.push(Problem::new(
+                format!("The `{}` table doesn't match the expected schema", table),
+                "Export the cats and import them into a new registry",
+            ));
+        }
+    }
+    let ill_typed = conn
+        .prepare(&format!("SELECT id FROM cats WHERE NOT ({})", WELL_TYPED))?
+        .query_map([], |row| row.get::<_, u64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for id in ill_typed {
+        problems.push(Problem::new(
+            format!(
+                "Cat #{} has values cats can't read, like a negative age",
+                id
+            ),
+            "Correct it with the sqlite3 shell, since cats can't read it either",
+        ));
+    }
+    let implausible = conn
+        .prepare(&format!(
+            "SELECT id, {} FROM cats WHERE {} AND {0} > ?",
+            AGE, WELL_TYPED
+        ))?
+        .query_map([MAX_AGE], |row| {
+            Ok((row.get::<_, u64>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, age) in implausible {
+        problems.push(Problem::new(
+            format!("Cat #{} is {} years old", id, age),
+            format!("Correct its age with `update --id {} --age`", id),
+        ));
+    }
+    let unnamed = conn
+        .prepare(&format!(
+            "SELECT id FROM cats WHERE {} AND trim(name) = ''",
+            WELL_TYPED
+        ))?
+        .query_map([], |row| row.get::<_, u64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for id in unnamed {
+        problems.push(Problem::new(
+            format!("Cat #{} has no name", id),
+            format!("Name it with `update --id {} --name`", id),
+        ));
+    }
+    atomic(conn, || {
+        let mut fixes = Vec::new();
+        // Stray whitespace can only have come from a typo, and an empty breed means no breed.
+        let untidy = conn
+            .prepare(&format!(
+                "SELECT * FROM cats WHERE {} AND trim(name) != '' \
+                AND (name != trim(name) OR breed != trim(breed) OR trim(breed) = '')",
+                WELL_TYPED
+            ))?
+            .query_map([], Cat::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for old in untidy {
+            let mut problem = Problem::new(
+                format!("Cat #{} has stray whitespace or an empty breed", old.id),
+                "Trim the name and breed, removing the breed if it is empty",
+            );
+            if fix {
+                let new = Cat {
+                    name: old.name.trim().to_string(),
+                    breed: old
+                        .breed
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|breed| !breed.is_empty())
+                        .map(String::from),
+                    ..old.clone()
+                };
+                overwrite(conn, &new)?;
+                record(conn, Action::Update, Some(&old), Some(&new))?;
+                problem.fixed = true;
+            }
+            fixes.push(problem);
+        }
+        let index_intact = conn
+            .execute(
+                "INSERT INTO cats_fts (cats_fts, rank) VALUES ('integrity-check', 1)",
+                [],
+            )
+            .is_ok();
+        if !index_intact {
+            let mut problem = Problem::new(
+                "The search index is out of date".to_string(),
+                "Rebuild the search index",
+            );
+            if fix {
+                conn.execute("INSERT INTO cats_fts (cats_fts) VALUES ('rebuild')", [])?;
+                problem.fixed = true;
+            }
+            fixes.push(problem);
+        }
+        let dangling = conn
+            .prepare("SELECT rowid FROM pragma_foreign_key_check('cat_history')")?
+            .query_map([], |row| row.get::<_, u64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for id in dangling {
+            let mut problem = Problem::new(
+                format!("History entry #{} undoes an entry that doesn't exist", id),
+                "Forget which entry it undid",
+            );
+            if fix {
+                conn.execute("UPDATE cat_history SET undoes = NULL WHERE id = ?", [id])?;
+                problem.fixed = true;
+            }
+            fixes.push(problem);
+        }
+        problems.extend(fixes);
+        Ok(())
+    })?;
+    Ok(problems)
+}
+
+/// The name, type, and constraints of every column in `table`.
+fn columns(conn: &Connection, table: &str) -> Result<Vec<(String, String, bool, bool)>> {
+    Ok(conn
+        .prepare("SELECT name, type, \"notnull\", pk FROM pragma_table_info(?) ORDER BY cid")?
+        .query_map([table], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+impl Printable for Vec<Problem> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No problems found"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Problem", "Fix", "Fixed"]));
+        for problem in self {
+            table.add_row(row![
+                problem.description,
+                problem.fix,
+                tr(if problem.fixed { "yes" } else { "no" })
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for problem in self {
+            if problem.fixed {
+                println!("{}: fixed", problem.description)
+            } else {
+                println!("{}: {}", problem.description, problem.fix)
+            }
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("problems", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+/// How much of the file can be free pages, or how much the number of cats can change, before
+/// `maintenance --auto` vacuums or analyzes.
+const MAINTENANCE_THRESHOLD: f64 = 0.25;
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceSummary {
+    pub vacuumed: bool,
+    pub analyzed: bool,
+    pub reindexed: bool,
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+pub fn maintenance(conn: &Connection, cmd: CmdMaintenance) -> Result<MaintenanceSummary> {
+    let size_before = db_size(conn)?;
+    let all = !(cmd.vacuum || cmd.analyze || cmd.reindex || cmd.auto);
+    let vacuum = if cmd.auto {
+        let free: u64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        let pages: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        pages > 0 && free as f64 / pages as f64 >= MAINTENANCE_THRESHOLD
+    } else {
+        all || cmd.vacuum
+    };
+    let reindexed = all || cmd.reindex;
+    if reindexed {
+        conn.execute_batch("REINDEX")?;
+    }
+    let analyzed = if cmd.auto {
+        stats_stale(conn)?
+    } else {
+        all || cmd.analyze
+    };
+    if analyzed {
+        conn.execute_batch("ANALYZE")?;
+    }
+    if vacuum {
+        conn.execute_batch("VACUUM")?;
+    }
+    Ok(MaintenanceSummary {
+        vacuumed: vacuum,
+        analyzed,
+        reindexed,
+        size_before,
+        size_after: db_size(conn)?,
+    })
+}
+
+/// Whether the number of cats has changed by more than a quarter since the registry was analyzed.
+fn stats_stale(conn: &Connection) -> Result<bool> {
+    let has_stats: bool = conn.query_row(
+        "SELECT EXISTS (SELECT * FROM sqlite_master WHERE name = 'sqlite_stat1')",
+        [],
+        |row| row.get(0),
+    )?;
+    let count: u64 = conn.query_row("SELECT count(*) FROM cats", [], |row| row.get(0))?;
+    if !has_stats {
+        return Ok(count > 0);
+    }
+    // The first number in each entry is the number of rows in the table.
+    let analyzed = conn
+        .query_row(
+            "SELECT stat FROM sqlite_stat1 WHERE tbl = 'cats' LIMIT 1",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .and_then(|stat| stat.split(' ').next()?.parse::<u64>().ok())
+        .unwrap_or(0);
+    Ok(count.abs_diff(analyzed) as f64 > analyzed.max(count) as f64 * MAINTENANCE_THRESHOLD)
+}
+
+fn db_size(conn: &Connection) -> Result<u64> {
+    Ok(conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count, pragma_page_size",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+impl Printable for MaintenanceSummary {
+    fn print_display(&self, style: Style) {
+        let yes_no = |b| tr(if b { "yes" } else { "no" });
+        let mut table = table!([
+            yes_no(self.vacuumed),
+            yes_no(self.analyzed),
+            yes_no(self.reindexed),
+            self.size_before,
+            self.size_after
+        ]);
+        table.set_titles(titles(&[
+            "Vacuumed",
+            "Analyzed",
+            "Reindexed",
+            "Size before",
+            "Size after",
+        ]));
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        println!(
+            "{} {} {} {} {}",
+            self.vacuumed, self.analyzed, self.reindexed, self.size_before, self.size_after
+        )
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("maintenance", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, Some(self));
+    }
+}
+
+pub fn backup(conn: &Connection, file: &Path, force: bool) -> Result<()> {
+    if file.exists() {
+        if !force {
+            bail!(
+                "{} already exists, pass --force to replace it",
+                file.display()
+            );
+        }
+        fs::remove_file(file)?;
+    }
+    let path = file
+        .to_str()
+        .ok_or_else(|| anyhow!("{} is not a valid UTF-8 path", file.display()))?;
+    conn.execute("VACUUM INTO ?", [path])?;
+    Ok(())
+}
+
+pub fn restore_backup(
+    conn: &mut Connection,
+    file: &Path,
+    force: bool,
+    merge: bool,
+) -> Result<ImportSummary> {
+    verify_backup(file)?;
+    if merge {
+        let cats = live_cats(&load_backup(file)?)?;
+        return import_cats(conn, cats, ImportKey::Id, OnConflict::Skip);
+    }
+    let existing: u64 = conn.query_row("SELECT COUNT(*) FROM cats", [], |row| row.get(0))?;
+    if existing > 0 && !force {
+        bail!(
+            "The registry already contains {} cats, pass --force to replace them or --merge to keep them",
+            existing
+        );
+    }
+    conn.restore(DatabaseName::Main, file, None::<fn(Progress)>)?;
+    migrations::migrate(conn)?;
+    let added = conn.query_row(
+        "SELECT COUNT(*) FROM cats WHERE deleted_at ISNULL",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(ImportSummary {
+        added,
+        ..Default::default()
+    })
+}
+
+/// Makes sure `file` is an intact registry that this version of cats can read.
+fn verify_backup(file: &Path) -> Result<()> {
+    let backup = Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {}", file.display()))?;
+    let integrity: String = backup
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .with_context(|| format!("{} is not a valid backup", file.display()))?;
+    if integrity != "ok" {
+        bail!("{} is corrupted: {}", file.display(), integrity);
+    }
+    let version = migrations::version(&backup)?;
+    if version > migrations::LATEST {
+        bail!(
+            "{} uses schema version {}, but this version of cats only supports up to {}",
+            file.display(),
+            version,
+            migrations::LATEST
+        );
+    }
+    let has_cats: bool = backup.query_row(
+        "SELECT EXISTS (SELECT * FROM sqlite_master WHERE type = 'table' AND name = 'cats')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_cats {
+        bail!("{} is not a cats registry", file.display());
+    }
+    Ok(())
+}
+
+/// Copies a registry into memory and brings it up to date, so that the file itself is left alone.
+fn load_backup(file: &Path) -> Result<Connection> {
+    let mut copy = Connection::open_in_memory()?;
+    copy.restore(DatabaseName::Main, file, None::<fn(Progress)>)?;
+    migrations::migrate(&mut copy)?;
+    Ok(copy)
+}
+
+fn live_cats(conn: &Connection) -> Result<Vec<Cat>> {
+    Ok(conn
+        .prepare("SELECT * FROM cats WHERE deleted_at ISNULL ORDER BY id")?
+        .query_map([], Cat::from_row)?
+        .collect::<rusqlite::Result<_>>()?)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MergeSummary {
+    pub added: u64,
+    pub renumbered: u64,
+    pub skipped: u64,
+}
+
+pub fn merge(
+    conn: &Connection,
+    file: &Path,
+    strategy: Option<MergeStrategy>,
+) -> Result<MergeSummary> {
+    verify_backup(file)?;
+    let cats = live_cats(&load_backup(file)?)?;
+    let strategy = strategy.unwrap_or(if atty::is(atty::Stream::Stdin) {
+        MergeStrategy::Ask
+    } else {
+        MergeStrategy::Skip
+    });
+    // Decide about every duplicate up front, so that nobody is asked twice if the merge is retried.
+    let mut keep = Vec::with_capacity(cats.len());
+    for cat in &cats {
+        let duplicate = conn
+            .query_row(
+                &format!(
+                    "SELECT * FROM cats WHERE name = ? AND {} = ? AND breed IS ? \
+                    AND deleted_at ISNULL ORDER BY id LIMIT 1",
+                    AGE
+                ),
+                params![cat.name, cat.age, cat.breed],
+                Cat::from_row,
+            )
+            .optional()?;
+        keep.push(match (duplicate, strategy) {
+            (None, _) | (Some(_), MergeStrategy::KeepBoth) => true,
+            (Some(_), MergeStrategy::Skip) => false,
+            (Some(duplicate), MergeStrategy::Ask) => confirm(&format!(
+                "{} ({}, {}) looks like cat #{} already in the registry. Add it anyway?",
+                cat.name,
+                cat.age,
+                cat.breed.as_deref().unwrap_or("no breed"),
+                duplicate.id
+            ))?,
+        });
+    }
+    atomic(conn, || {
+        let mut summary = MergeSummary::default();
+        for (cat, &keep) in cats.iter().zip(&keep) {
+            if !keep {
+                summary.skipped += 1;
+                continue;
+            }
+            let taken: bool = conn.query_row(
+                "SELECT EXISTS (SELECT * FROM cats WHERE id = ?)",
+                [cat.id],
+                |row| row.get(0),
+            )?;
+            let cat = Cat {
+                owner_id: None,
+                location_id: None,
+                mother_id: None,
+                father_id: None,
+                litter_id: None,
+                ..cat.clone()
+            };
+            let added = insert(conn, &cat, !taken)?;
+            record(conn, Action::Add, None, Some(&added))?;
+            summary.added += 1;
+            if taken {
+                summary.renumbered += 1;
+            }
+        }
+        Ok(summary)
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Difference {
+    pub id: u64,
+    pub here: Option<Cat>,
+    pub other: Option<Cat>,
+}
+
+impl Difference {
+    fn describe(&self) -> &'static str {
+        match (&self.here, &self.other) {
+            (Some(_), None) => "only here",
+            (None, Some(_)) => "only in other",
+            _ => "changed",
+        }
+    }
+
+    /// The cat's fields if it is only in one registry, or how it differs from here to the other.
+    fn details(&self, none: &str) -> String {
+        match (&self.here, &self.other) {
+            (Some(_), None) => changes(&None, &self.here, none),
+            _ => changes(&self.here, &self.other, none),
+        }
+    }
+}
+
+/// Compares the cats in the registry with the ones in another, matching them up by ID.
+pub fn diff(conn: &Connection, file: &Path) -> Result<Vec<Difference>> {
+    verify_backup(file)?;
+    let here = live_cats(conn)?;
+    let other = live_cats(&load_backup(file)?)?;
+    Ok(here
+        .into_iter()
+        .merge_join_by(other, |a, b| a.id.cmp(&b.id))
+        .filter_map(|pair| {
+            let (here, other) = match pair {
+                EitherOrBoth::Left(here) => (Some(here), None),
+                EitherOrBoth::Right(other) => (None, Some(other)),
+                EitherOrBoth::Both(here, other) => {
+                    if here.same_details(&other) {
+                        return None;
+                    }
+                    (Some(here), Some(other))
+                }
+            };
+            let id = here.as_ref().or(other.as_ref())?.id;
+            Some(Difference { id, here, other })
+        })
+        .collect())
+}
+
+impl Printable for Vec<Difference> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("The registries contain the same cats"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["ID", "Difference", "Details"]));
+        for difference in self {
+            table.add_row(row![
+                difference.id,
+                tr(difference.describe()),
+                difference.details(tr("<none>"))
+            ]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for difference in self {
+            println!(
+                "{} {} {}",
+                difference.id,
+                difference.describe(),
+                difference.details("<none>")
+            )
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("differences", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(
+            dialect,
+            &["id", "difference", "details"],
+            self.iter().map(|difference| {
+                vec![
+                    difference.id.to_string(),
+                    difference.describe().to_string(),
+                    difference.details("<none>"),
+                ]
+            }),
+        );
+    }
+}
+
+/// Asks a yes or no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+impl Printable for MergeSummary {
+    fn print_display(&self, style: Style) {
+        let mut table = table!([self.added, self.renumbered, self.skipped]);
+        table.set_titles(titles(&["Added", "Renumbered", "Skipped"]));
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        println!("{} {} {}", self.added, self.renumbered, self.skipped)
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("merge", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, Some(self));
+    }
+}
+
+/// Unlocks an encrypted registry. This must happen before anything else is done with it.
+pub fn unlock(conn: &Connection, key: &str) -> Result<()> {
+    check_encryption()?;
+    conn.execute_batch(&format!("PRAGMA key = {}", quote(key)))?;
+    Ok(())
+}
+
+pub fn rekey(conn: &Connection, new_key: &str) -> Result<()> {
+    check_encryption()?;
+    conn.execute_batch(&format!("PRAGMA rekey = {}", quote(new_key)))?;
+    Ok(())
+}
+
+fn check_encryption() -> Result<()> {
+    if !cfg!(feature = "sqlcipher") {
+        bail!(
+            "This build of cats does not support encryption, rebuild it with the sqlcipher feature"
+        );
+    }
+    Ok(())
+}
+
+/// Quotes a string as an SQL literal, for the few places that can't take parameters.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+pub fn profile(config: &mut Config, cmd: CmdProfile) -> Result<Vec<Profile>> {
+    match cmd {
+        CmdProfile::List => Ok(config
+            .profiles
+            .iter()
+            .map(|(name, path)| Profile {
+                name: name.clone(),
+                path: path.clone(),
+            })
+            .collect()),
+        CmdProfile::Add { name, path } => {
+            let path = match path {
+                Some(path) => path,
+                None => paths::profile_db(&name)?,
+            };
+            config.profiles.insert(name.clone(), path.clone());
+            config.save()?;
+            Ok(vec![Profile { name, path }])
+        }
+        CmdProfile::Remove { name } => {
+            let path = config.profile(&name)?;
+            config.profiles.remove(&name);
+            config.save()?;
+            Ok(vec![Profile { name, path }])
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Printable for Vec<Profile> {
+    fn print_display(&self, style: Style) {
+        if self.is_empty() {
+            println!("{}", tr("No profiles exist"));
+            return;
+        }
+        let mut table = Table::new();
+        table.set_titles(titles(&["Name", "Registry"]));
+        for profile in self {
+            table.add_row(row![profile.name, profile.path.display()]);
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for profile in self {
+            println!("{} {}", profile.name, profile.path.display())
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("profiles", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_rows(dialect, self);
+    }
+}
+
+impl Printable for u64 {
+    fn print_display(&self, _style: Style) {
+        println!("{}", self)
+    }
+    fn print_plain(&self) {
+        println!("{}", self)
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("count", self);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(dialect, &["count"], Some(vec![self.to_string()]));
+    }
+}
+
+/// Writes rows to stdout as records, with a header row made of the names of their fields.
+///
+/// This only works for rows with nothing nested in them. Nothing at all is written for no rows,
+/// since there is nothing to take the header from.
+fn write_rows<T: serde::Serialize>(dialect: Dialect, rows: impl IntoIterator<Item = T>) {
+    // Only CSV can be written with serde, so it is read back in to be written in the dialect.
+    let mut buffer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        buffer.serialize(row).unwrap();
+    }
+    let buffer = buffer.into_inner().unwrap();
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(&buffer[..]);
+    let mut writer = RecordWriter::new(dialect);
+    for record in reader.records() {
+        writer.write(&record.unwrap());
+    }
+}
+
+/// The start of an HTML page of results, which goes on to the table's rows.
+const HTML_START: &str = "\
+<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Cats</title>
+<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+th { background: #eee; }
+img { max-width: 160px; }
+</style>
+</head>
+<body>
+<table>";
+
+/// Writes a header row and then the records to stdout.
+fn write_records(
+    dialect: Dialect,
+    header: &[&str],
+    records: impl IntoIterator<Item = Vec<String>>,
+) {
+    let mut writer = RecordWriter::new(dialect);
+    writer.write(header);
+    for record in records {
+        writer.write(&record);
+    }
+}
+
+/// Prints a result as a TOML document.
+///
+/// TOML documents are tables, so anything else goes in one under `key`. TOML has no null either,
+/// so anything unknown is left out.
+fn write_toml<T: serde::Serialize>(key: &str, value: &T) {
+    fn strip_nulls(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.retain(|_, value| !value.is_null());
+                map.values_mut().for_each(strip_nulls);
+            }
+            Value::Array(values) => {
+                values.retain(|value| !value.is_null());
+                values.iter_mut().for_each(strip_nulls);
+            }
+            _ => {}
+        }
+    }
+    let mut value = match serde_json::to_value(value).unwrap() {
+        table @ Value::Object(_) => table,
+        value => Value::Object(Some((key.to_string(), value)).into_iter().collect()),
+    };
+    strip_nulls(&mut value);
+    print!("{}", toml::Value::try_from(value).unwrap());
+}
+
+struct RecordWriter {
+    dialect: Dialect,
+    writer: csv::Writer<io::Stdout>,
+    /// Whether the header row has been written yet, or for `Labeled`, the first record.
+    started: bool,
+    /// The header, which `Labeled` writes as the label of each field instead of on its own.
+    header: Vec<String>,
+    /// Whether the first field of each record is an image, as a data URI. Only HTML shows these.
+    images: bool,
+}
+
+impl RecordWriter {
+    fn new(dialect: Dialect) -> Self {
+        let mut builder = csv::WriterBuilder::new();
+        match dialect {
+            // RFC 4180 ends lines with CRLF, which is also what spreadsheets expect.
+            Dialect::Csv => builder.terminator(csv::Terminator::CRLF),
+            // Fields are escaped instead of quoted, so that they can be split on tabs.
+            Dialect::Tsv => builder.delimiter(b'\t').quote_style(csv::QuoteStyle::Never),
+            Dialect::Print0 => builder
+                .delimiter(b'\t')
+                .quote_style(csv::QuoteStyle::Never)
+                .terminator(csv::Terminator::Any(b'\0')),
+            // Each row is written with empty fields at either end, so that it also starts and
+            // ends with a pipe.
+            Dialect::Markdown => builder.delimiter(b'|').quote_style(csv::QuoteStyle::Never),
+            // HTML and labeled fields are written by hand.
+            Dialect::Html | Dialect::Labeled => &mut builder,
+        };
+        if dialect == Dialect::Html {
+            println!("{}", HTML_START);
+        }
+        Self {
+            dialect,
+            writer: builder.from_writer(io::stdout()),
+            started: false,
+            header: Vec::new(),
+            images: false,
+        }
+    }
+
+    fn write<I>(&mut self, record: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let dialect = self.dialect;
+        let fields = record
+            .into_iter()
+            .map(|field| dialect.escape(field.as_ref()).into_owned());
+        if dialect == Dialect::Html {
+            let cells = fields
+                .enumerate()
+                .map(|(i, field)| match (self.started, i) {
+                    (false, _) => format!("<th>{}</th>", field),
+                    (true, 0) if self.images && !field.is_empty() => {
+                        format!("<td><img src=\"{}\" alt=\"\"></td>", field)
+                    }
+                    (true, _) => format!("<td>{}</td>", field),
+                });
+            let row = format!("<tr>{}</tr>", cells.format(""));
+            if self.started {
+                println!("{}", row);
+            } else {
+                println!("<thead>\n{}\n</thead>\n<tbody>", row);
+            }
+            self.started = true;
+            return;
+        }
+        if dialect == Dialect::Labeled {
+            if self.header.is_empty() {
+                self.header = fields.collect();
+                return;
+            }
+            if self.started {
+                println!();
+            }
+            // Empty fields are left out, so that there's less to listen to.
+            for (label, field) in self.header.iter().zip(fields) {
+                if !field.is_empty() {
+                    println!("{}: {}", label, field);
+                }
+            }
+            self.started = true;
+            return;
+        }
+        if dialect != Dialect::Markdown {
+            self.writer.write_record(fields).unwrap();
+            return;
+        }
+        let fields = fields
+            .map(|field| format!(" {} ", field))
+            .collect::<Vec<_>>();
+        let edge = || Some(String::new());
+        self.writer
+            .write_record(
+                edge()
+                    .into_iter()
+                    .chain(fields.iter().cloned())
+                    .chain(edge()),
+            )
+            .unwrap();
+        // The header is marked as one by the row under it.
+        if !self.started {
+            let separators = fields.iter().map(|_| " --- ".to_string());
+            self.writer
+                .write_record(edge().into_iter().chain(separators).chain(edge()))
+                .unwrap();
+        }
+        self.started = true;
+    }
+}
+
+impl Drop for RecordWriter {
+    fn drop(&mut self) {
+        self.writer.flush().unwrap();
+        if self.dialect == Dialect::Html {
+            if self.started {
+                println!("</tbody>");
+            }
+            println!("</table>\n</body>\n</html>");
+        }
+    }
+}
+
+impl Printable for () {
+    fn print_display(&self, _style: Style) {}
+    fn print_plain(&self) {}
+    fn print_json(&self) {}
+    fn print_toml(&self) {}
+    fn print_template(&self, _template: &Template) {}
+    fn print_records(&self, _dialect: Dialect) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cat {
+    pub id: u64,
+    pub name: String,
+    /// The cat's age as of today.
+    pub age: u32,
+    pub breed: Option<String>,
+    /// The day the cat was born, as `YYYY-MM-DD`, if it is known.
+    #[serde(default)]
+    pub birthdate: Option<String>,
+    /// The cat's weight in grams, if it is known.
+    #[serde(default)]
+    pub weight: Option<u32>,
+    #[serde(default)]
+    pub sex: Sex,
+    /// Whether the cat has been spayed or neutered.
+    #[serde(default)]
+    pub fixed: bool,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub microchip: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// The ID of the person the cat belongs to, if anyone.
+    #[serde(default)]
+    pub owner_id: Option<u64>,
+    /// Where the cat is in the adoption process.
+    #[serde(default)]
+    pub status: Status,
+    /// The day the cat was adopted, if it currently is.
+    #[serde(default)]
+    pub adopted_at: Option<String>,
+    /// The day the cat died, if it has.
+    #[serde(default)]
+    pub deceased_at: Option<String>,
+    /// The ID of the room, kennel, or foster home the cat is in, if it's known.
+    #[serde(default)]
+    pub location_id: Option<u64>,
+    /// Whether the cat gets along with dogs, if anyone has found out.
+    #[serde(default)]
+    pub good_with_dogs: Option<bool>,
+    /// Whether the cat gets along with children, if anyone has found out.
+    #[serde(default)]
+    pub good_with_kids: Option<bool>,
+    /// How energetic the cat is, from 1 to 5.
+    #[serde(default)]
+    pub energy: Option<u8>,
+    #[serde(default)]
+    pub litter_trained: Option<bool>,
+    /// What the cat can't or shouldn't eat.
+    #[serde(default)]
+    pub diet: Option<String>,
+    #[serde(default)]
+    pub license_number: Option<String>,
+    /// The day the cat's license runs out, if it has one.
+    #[serde(default)]
+    pub license_expires: Option<String>,
+    /// The IDs of the cat's parents, if they are in the registry.
+    #[serde(default)]
+    pub mother_id: Option<u64>,
+    #[serde(default)]
+    pub father_id: Option<u64>,
+    /// The ID of the litter the cat was born in, if it was recorded.
+    #[serde(default)]
+    pub litter_id: Option<u64>,
+    /// The name of the cat's owner, for the commands that look it up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// The IDs of the cats this one should be adopted with, for the commands that look them up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bonded_with: Vec<u64>,
+    /// The name of the cat's location, for the commands that look it up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// The cat's custom fields, which only some commands look up.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, Value>,
+    /// When the cat was added, in UTC. Cats added before this was kept track of may not have one.
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// When the cat's name, age, or breed last changed, in UTC.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Cat {
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let birthdate: Option<String> = row.get("birthdate")?;
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            age: Self::current_age(row.get(2)?, birthdate.as_deref()),
+            breed: row.get(3)?,
+            birthdate,
+            weight: row.get("weight")?,
+            sex: row.get::<_, String>("sex")?.parse().map_err(|e: Error| {
+                rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into())
+            })?,
+            fixed: row.get("fixed")?,
+            color: row.get("color")?,
+            pattern: row.get("pattern")?,
+            microchip: row.get("microchip")?,
+            notes: row.get("notes")?,
+            owner_id: row.get("owner_id")?,
+            mother_id: row.get("mother_id")?,
+            father_id: row.get("father_id")?,
+            litter_id: row.get("litter_id")?,
+            status: row
+                .get::<_, String>("status")?
+                .parse()
+                .map_err(|e: Error| {
+                    rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into())
+                })?,
+            adopted_at: row.get("adopted_at")?,
+            deceased_at: row.get("deceased_at")?,
+            location_id: row.get("location_id")?,
+            good_with_dogs: row.get("good_with_dogs")?,
+            good_with_kids: row.get("good_with_kids")?,
+            energy: row.get("energy")?,
+            litter_trained: row.get("litter_trained")?,
+            diet: row.get("diet")?,
+            license_number: row.get("license_number")?,
+            license_expires: row.get("license_expires")?,
+            owner: match row.as_ref().column_index("owner_name") {
+                Ok(i) => row.get(i)?,
+                Err(_) => None,
+            },
+            bonded_with: match row.as_ref().column_index("bonded_with") {
+                Ok(i) => match row.get::<_, Option<String>>(i)? {
+                    Some(ids) => serde_json::from_str(&ids).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(i, Type::Text, e.into())
+                    })?,
+                    None => Vec::new(),
+                },
+                Err(_) => Vec::new(),
+            },
+            location: match row.as_ref().column_index("location_name") {
+                Ok(i) => row.get(i)?,
+                Err(_) => None,
+            },
+            fields: BTreeMap::new(),
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    /// Whether two cats are the same, not counting when they were added or changed.
+    pub fn same_details(&self, other: &Cat) -> bool {
+        let details = |cat: &Cat| Cat {
+            created_at: None,
+            updated_at: None,
+            ..cat.clone()
+        };
+        details(self) == details(other)
+    }
+
+    pub fn weight(&self) -> Option<Weight> {
+        self.weight.map(|grams| Weight { grams })
+    }
+
+    /// The age of a cat today, counted from its birthdate if it has one.
+    ///
+    /// `age` is the stored age, which is used for cats without a birthdate. This must agree with
+    /// `store::AGE`, which does the same in SQL.
+    pub fn current_age(age: u32, birthdate: Option<&str>) -> u32 {
+        match birthdate.and_then(|birthdate| birthdate.parse::<Date>().ok()) {
+            Some(birthdate) => birthdate.years_until(Date::today()),
+            None => age,
+        }
+    }
+}
+
+/// A table of cats, with a row for each.
+fn cat_table<'a>(cats: impl IntoIterator<Item = &'a Cat>) -> Table {
+    let mut table = Table::new();
+    table.set_titles(titles(CAT_TITLES));
+    for cat in cats {
+        table.add_row(cat.cells().iter().collect());
+    }
+    table
+}
+
+/// A table's titles, in the language output is printed in.
+fn titles(titles: &[&'static str]) -> prettytable::Row {
+    titles.iter().map(|title| tr(title)).collect()
+}
+
+/// The columns of the table cats are shown in.
+const CAT_TITLES: &[&str] = &[
+    "ID",
+    "Name",
+    "Age",
+    "Born",
+    "Weight",
+    "Sex",
+    "Fixed",
+    "Breed",
+    "Status",
+    "Owner",
+    "Location",
+    "Coat",
+    "Microchip",
+    "Added",
+    "Updated",
+];
+
+impl Cat {
+    /// The cat's row in a table, in the same order as `CAT_TITLES`.
+    fn cells(&self) -> Vec<String> {
+        let unknown = || tr("<unknown>").to_string();
+        let none = || tr("<none>").to_string();
+        vec![
+            self.id.to_string(),
+            // Bonded cats are pointed out, so that nobody adopts one without the other.
+            if self.bonded_with.is_empty() {
+                self.name.clone()
+            } else {
+                let bonded_with = self.bonded_with.iter().map(|id| format!("#{}", id));
+                format!(
+                    "{} ({} {})",
+                    self.name,
+                    tr("bonded with"),
+                    bonded_with.format(", ")
+                )
+            },
+            self.age.to_string(),
+            self.birthdate.clone().unwrap_or_else(unknown),
+            self.weight()
+                .map_or_else(unknown, |weight| weight.to_string()),
+            self.sex.to_string(),
+            tr(if self.fixed { "yes" } else { "no" }).to_string(),
+            self.breed.clone().unwrap_or_else(none),
+            // A cat's adoption status stops mattering once it has died.
+            match &self.deceased_at {
+                Some(_) => "deceased".to_string(),
+                None => self.status.to_string(),
+            },
+            match (&self.owner, self.owner_id) {
+                (Some(owner), _) => owner.clone(),
+                (None, Some(owner_id)) => format!("#{}", owner_id),
+                (None, None) => none(),
+            },
+            match (&self.location, self.location_id) {
+                (Some(location), _) => location.clone(),
+                (None, Some(location_id)) => format!("#{}", location_id),
+                (None, None) => unknown(),
+            },
+            // e.g. `black tabby`
+            match (&self.color, &self.pattern) {
+                (None, None) => unknown(),
+                (color, pattern) => color.iter().chain(pattern).join(" "),
+            },
+            self.microchip.clone().unwrap_or_else(none),
+            self.created_at.clone().unwrap_or_else(unknown),
+            self.updated_at.clone().unwrap_or_else(unknown),
+        ]
+    }
+}
+
+/// The columns cats have as records, which are followed by any custom fields they have.
+///
+/// Unlike in tables, everything is in a form spreadsheets can work with, like weights in grams.
+pub const CAT_RECORD_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "age",
+    "birthdate",
+    "weight",
+    "sex",
+    "fixed",
+    "breed",
+    "color",
+    "pattern",
+    "microchip",
+    "status",
+    "adopted_at",
+    "deceased_at",
+    "owner_id",
+    "owner",
+    "location_id",
+    "location",
+    "mother_id",
+    "father_id",
+    "litter_id",
+    "bonded_with",
+    "good_with_dogs",
+    "good_with_kids",
+    "energy",
+    "litter_trained",
+    "diet",
+    "license_number",
+    "license_expires",
+    "notes",
+    "created_at",
+    "updated_at",
+];
+
+/// Writes cats to stdout as records, with a column for every custom field any of them have.
+///
+/// HTML also gets a column for the cats' photos, if any of them have one.
+fn write_cats<'a>(dialect: Dialect, cats: impl IntoIterator<Item = (&'a Cat, Option<&'a Image>)>) {
+    let (cats, photos): (Vec<_>, Vec<_>) = cats.into_iter().unzip();
+    let fields = cats
+        .iter()
+        .flat_map(|cat| cat.fields.keys())
+        .unique()
+        .sorted()
+        .collect::<Vec<_>>();
+    let mut writer = RecordWriter::new(dialect);
+    writer.images = dialect == Dialect::Html && photos.iter().any(Option::is_some);
+    let header = writer
+        .images
+        .then_some("photo")
+        .into_iter()
+        .chain(CAT_RECORD_COLUMNS.iter().copied())
+        .chain(fields.iter().map(|field| field.as_str()));
+    writer.write(header);
+    for (cat, photo) in cats.iter().zip(&photos) {
+        let mut record = Vec::new();
+        if writer.images {
+            record.push(photo.map_or_else(String::new, |photo| {
+                format!(
+                    "data:image/{};base64,{}",
+                    photo.format,
+                    base64::encode(&photo.data)
+                )
+            }));
+        }
+        record.extend(cat.record());
+        record.extend(
+            fields
+                .iter()
+                .map(|field| field_text(cat.fields.get(*field))),
+        );
+        writer.write(&record);
+    }
+}
+
+/// A custom field's value as a record field.
+fn field_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    }
+}
+
+impl Cat {
+    /// The cat's record, in the same order as `CAT_RECORD_COLUMNS`.
+    fn record(&self) -> Vec<String> {
+        let text = |text: &Option<String>| text.clone().unwrap_or_default();
+        let value = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            self.id.to_string(),
+            self.name.clone(),
+            self.age.to_string(),
+            text(&self.birthdate),
+            value(self.weight.map(|weight| weight.to_string())),
+            self.sex.as_str().to_string(),
+            self.fixed.to_string(),
+            text(&self.breed),
+            text(&self.color),
+            text(&self.pattern),
+            text(&self.microchip),
+            self.status.as_str().to_string(),
+            text(&self.adopted_at),
+            text(&self.deceased_at),
+            value(self.owner_id.map(|id| id.to_string())),
+            text(&self.owner),
+            value(self.location_id.map(|id| id.to_string())),
+            text(&self.location),
+            value(self.mother_id.map(|id| id.to_string())),
+            value(self.father_id.map(|id| id.to_string())),
+            value(self.litter_id.map(|id| id.to_string())),
+            self.bonded_with.iter().join(" "),
+            value(self.good_with_dogs.map(|known| known.to_string())),
+            value(self.good_with_kids.map(|known| known.to_string())),
+            value(self.energy.map(|energy| energy.to_string())),
+            value(self.litter_trained.map(|known| known.to_string())),
+            text(&self.diet),
+            text(&self.license_number),
+            text(&self.license_expires),
+            text(&self.notes),
+            text(&self.created_at),
+            text(&self.updated_at),
+        ]
+    }
+}
+
+/// Cats with only the columns picked with `--fields`, in the order they were picked.
+pub struct SelectedCats<'a> {
+    pub cats: Vec<&'a Cat>,
+    pub columns: &'a [Column],
+}
+
+impl SelectedCats<'_> {
+    fn header(&self) -> Vec<&str> {
+        self.columns.iter().map(Column::name).collect()
+    }
+
+    fn rows(&self) -> impl Iterator<Item = Vec<String>> + '_ {
+        self.cats.iter().map(move |cat| {
+            let record = cat.record();
+            self.columns
+                .iter()
+                .map(|column| match column {
+                    Column::Record(index) => record[*index].clone(),
+                    Column::Field(field) => field_text(cat.fields.get(field)),
+                })
+                .collect()
+        })
+    }
+}
+
+impl Printable for SelectedCats<'_> {
+    fn print_display(&self, style: Style) {
+        if self.cats.is_empty() {
+            return None::<Cat>.print_display(style);
+        }
+        let mut table = Table::new();
+        table.set_titles(self.header().into_iter().collect());
+        for row in self.rows() {
+            table.add_row(row.iter().collect());
+        }
+        terminal::print_table(table, style);
+    }
+    fn print_plain(&self) {
+        for row in self.rows() {
+            let fields = row.iter().map(|field| match &**field {
+                "" => "<none>",
+                field => field,
+            });
+            println!("{}", fields.format(" "));
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), &self.cats).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("cats", &self.cats);
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(&self.cats);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_records(dialect, &self.header(), self.rows());
+    }
+}
+
+impl Cat {
+    /// Prints everything about the cat that doesn't fit in its table, under it.
+    fn print_details(&self) {
+        if let Some(deceased_at) = &self.deceased_at {
+            println!("{}: {}", tr("Died"), deceased_at);
+        }
+        let yes_no = |known: bool| tr(if known { "yes" } else { "no" });
+        if let Some(good_with_dogs) = self.good_with_dogs {
+            println!("{}: {}", tr("Good with dogs"), yes_no(good_with_dogs));
+        }
+        if let Some(good_with_kids) = self.good_with_kids {
+            println!("{}: {}", tr("Good with kids"), yes_no(good_with_kids));
+        }
+        if let Some(energy) = self.energy {
+            println!("{}: {}/5", tr("Energy"), energy);
+        }
+        if let Some(litter_trained) = self.litter_trained {
+            println!("{}: {}", tr("Litter trained"), yes_no(litter_trained));
+        }
+        if let Some(diet) = &self.diet {
+            println!("{}: {}", tr("Diet"), diet);
+        }
+        let (license, expires) = (tr("License"), tr("expires"));
+        match (&self.license_number, &self.license_expires) {
+            (Some(number), Some(date)) => println!("{}: {}, {} {}", license, number, expires, date),
+            (Some(number), None) => println!("{}: {}", license, number),
+            (None, Some(date)) => println!("{}: {} {}", license, expires, date),
+            (None, None) => {}
+        }
+        if let Some(mother_id) = self.mother_id {
+            println!("{}: #{}", tr("Mother"), mother_id);
+        }
+        if let Some(father_id) = self.father_id {
+            println!("{}: #{}", tr("Father"), father_id);
+        }
+        for (field, value) in &self.fields {
+            match value {
+                Value::String(text) => println!("{}: {}", field, text),
+                value => println!("{}: {}", field, value),
+            }
+        }
+        // Notes run too long for the table, so they go underneath it.
+        if let Some(notes) = &self.notes {
+            println!("{}:\n{}", tr("Notes"), notes);
+        }
+    }
+}
+
+impl Printable for Cat {
+    fn print_display(&self, style: Style) {
+        terminal::print_table(cat_table(Some(self)), style);
+        self.print_details();
+    }
+    fn print_plain(&self) {
+        println!(
+            "{} {} {} {} {}",
+            self.id,
+            self.name,
+            self.age,
+            self.breed.as_deref().unwrap_or("<none>"),
+            self.weight()
+                .map_or_else(|| "<none>".to_string(), |weight| weight.to_string())
+        )
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("cat", self);
+    }
+    fn cats(&self) -> Option<Vec<&Cat>> {
+        Some(vec![self])
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_cats(dialect, Some((self, None)));
+    }
+}
+
+impl Printable for Vec<Cat> {
+    fn print_display(&self, style: Style) {
+        match &self[..] {
+            [] => return None::<Cat>.print_display(style),
+            // A single cat gets the detail view, notes and all.
+            [cat] => return cat.print_display(style),
+            _ => {}
+        }
+        terminal::print_table(cat_table(self), style);
+    }
+    fn print_plain(&self) {
+        for cat in self {
+            cat.print_plain()
+        }
+    }
+    fn print_json(&self) {
+        serde_json::to_writer(io::stdout(), self).unwrap();
+    }
+    fn print_toml(&self) {
+        write_toml("cats", self);
+    }
+    fn cats(&self) -> Option<Vec<&Cat>> {
+        Some(self.iter().collect())
+    }
+    fn print_template(&self, template: &Template) {
+        template.render(self);
+    }
+    fn print_records(&self, dialect: Dialect) {
+        write_cats(dialect, self.iter().map(|cat| (cat, None)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use structopt::StructOpt;
+
+    /// A store with no cats in it, which remembers what the command layer asked of it.
+    #[derive(Default)]
+    struct FakeStore {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl FakeStore {
+        fn call(&self, call: String) {
+            self.calls.borrow_mut().push(call);
+        }
+    }
+
+    impl CatStore for FakeStore {
+        fn add(&self, cmd: &CmdAdd) -> Result<Cat> {
+            self.call(format!("add {:?}", cmd.name));
+            bail!("The fake store can't add cats")
+        }
+        fn get(&self, ids: &[u64]) -> Result<Vec<Cat>> {
+            self.call(format!("get {:?}", ids));
+            Ok(Vec::new())
+        }
+        fn find(&self, _cmd: &CmdFind) -> Result<Vec<Cat>> {
+            self.call("find".to_string());
+            Ok(Vec::new())
+        }
+        fn count(&self, _cmd: &CmdFind) -> Result<u64> {
+            self.call("count".to_string());
+            Ok(0)
+        }
+        fn search(&self, query: &str) -> Result<Vec<Cat>> {
+            self.call(format!("search {}", query));
+            Ok(Vec::new())
+        }
+        fn update(&self, id: u64, _cmd: &CmdUpdate) -> Result<Option<Cat>> {
+            self.call(format!("update {}", id));
+            Ok(None)
+        }
+        fn delete(&self, id: u64, purge: bool) -> Result<Option<Cat>> {
+            self.call(format!("delete {} {}", id, purge));
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn update_rejects_future_birthdates_before_the_store() {
+        let store = FakeStore::default();
+        let cmd = CmdUpdate::from_iter(&["update", "--id", "1", "--birthdate", "2999-01-01"]);
+        let error = update(&store, 1, cmd).unwrap_err();
+        assert_eq!(error.to_string(), "2999-01-01 is in the future");
+        assert!(store.calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn delete_with_yes_goes_straight_to_the_store() {
+        let store = FakeStore::default();
+        assert!(delete(&store, 7, true, true).unwrap().is_none());
+        assert_eq!(*store.calls.borrow(), ["delete 7 true"]);
+    }
+
+    fn registry() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        conn
+    }
+
+    fn cat(id: u64, name: &str, breed: Option<&str>) -> Cat {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "age": 3,
+            "breed": breed,
+        }))
+        .unwrap()
+    }
+
+    fn breeds(conn: &Connection) -> Vec<(u64, String, Option<String>)> {
+        live_cats(conn)
+            .unwrap()
+            .into_iter()
+            .map(|cat| (cat.id, cat.name, cat.breed))
+            .collect()
+    }
+
+    #[test]
+    fn import_conflicts() {
+        let conn = registry();
+        let existing = vec![cat(1, "Tom", None), cat(2, "Kit", Some("Siamese"))];
+        let summary = import_cats(&conn, existing, ImportKey::Id, OnConflict::Skip).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.skipped), (2, 0, 0));
+
+        let incoming = || vec![cat(1, "Tom", Some("Tabby")), cat(2, "Kitty", Some("Manx"))];
+        let summary = import_cats(&conn, incoming(), ImportKey::Id, OnConflict::Skip).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.skipped), (0, 0, 2));
+
+        // Merging only fills in what's missing.
+        let summary = import_cats(&conn, incoming(), ImportKey::Id, OnConflict::Merge).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.skipped), (0, 1, 1));
+        assert_eq!(
+            breeds(&conn),
+            [
+                (1, "Tom".to_string(), Some("Tabby".to_string())),
+                (2, "Kit".to_string(), Some("Siamese".to_string())),
+            ]
+        );
+
+        let summary = import_cats(&conn, incoming(), ImportKey::Id, OnConflict::Overwrite).unwrap();
+        assert_eq!((summary.added, summary.updated, summary.skipped), (0, 2, 0));
+        assert_eq!(
+            breeds(&conn)[1],
+            (2, "Kitty".to_string(), Some("Manx".to_string()))
+        );
+    }
+
+    #[test]
+    fn import_by_name_and_breed_gives_new_ids() {
+        let conn = registry();
+        import_cats(
+            &conn,
+            vec![cat(1, "Tom", Some("Tabby"))],
+            ImportKey::Id,
+            OnConflict::Skip,
+        )
+        .unwrap();
+        let incoming = vec![cat(1, "Tom", Some("Tabby")), cat(1, "Tom", None)];
+        let summary = import_cats(&conn, incoming, ImportKey::NameBreed, OnConflict::Skip).unwrap();
+        assert_eq!((summary.added, summary.skipped), (1, 1));
+        assert_eq!(breeds(&conn)[1], (2, "Tom".to_string(), None));
+    }
+
+    #[test]
+    fn merge_skips_or_renumbers_duplicates() {
+        let file = std::env::temp_dir().join(format!("cats-test-{}-merge.db", std::process::id()));
+        let mut other = Connection::open(&file).unwrap();
+        migrations::migrate(&mut other).unwrap();
+        let cats = vec![cat(1, "Tom", Some("Tabby")), cat(2, "Kit", None)];
+        import_cats(&other, cats, ImportKey::Id, OnConflict::Skip).unwrap();
+        drop(other);
+        let registry = || {
+            let conn = registry();
+            import_cats(
+                &conn,
+                vec![cat(1, "Tom", Some("Tabby"))],
+                ImportKey::Id,
+                OnConflict::Skip,
+            )
+            .unwrap();
+            conn
+        };
+
+        let conn = registry();
+        let summary = merge(&conn, &file, Some(MergeStrategy::Skip));
+        let kept = registry();
+        let both = merge(&kept, &file, Some(MergeStrategy::KeepBoth));
+        fs::remove_file(&file).unwrap();
+
+        let summary = summary.unwrap();
+        assert_eq!(
+            (summary.added, summary.renumbered, summary.skipped),
+            (1, 0, 1)
+        );
+        assert_eq!(breeds(&conn)[1], (2, "Kit".to_string(), None));
+        let both = both.unwrap();
+        // The renumbered Tom takes the next ID, so Kit's is taken by the time it's added.
+        assert_eq!((both.added, both.renumbered, both.skipped), (2, 2, 0));
+        assert_eq!(
+            breeds(&kept),
+            [
+                (1, "Tom".to_string(), Some("Tabby".to_string())),
+                (2, "Tom".to_string(), Some("Tabby".to_string())),
+                (3, "Kit".to_string(), None),
+            ]
+        );
+    }
 }
@@ -0,0 +1,126 @@
+// Every setting in here is optional, and anything passed on the command line takes precedence.
+
+use crate::args::{Cmd, OutputFormat, SortField, TableStyle};
+use crate::paths;
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where this config was loaded from, and where it will be saved to.
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// The path to the registry database.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db: Option<PathBuf>,
+    /// The output format to use when `--format` isn't passed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+    /// The table style to use when `--table-style` isn't passed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_style: Option<TableStyle>,
+    /// Whether to use write-ahead logging, which lets readers and writers work at the same time.
+    ///
+    /// This is off unless asked for, since registries on network shares don't support it.
+    pub wal: bool,
+    /// The registry database for each profile name.
+    pub profiles: BTreeMap<String, PathBuf>,
+    /// Defaults for `cats add`.
+    pub add: AddConfig,
+    /// Defaults for `cats find`.
+    pub find: FindConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AddConfig {
+    /// The breed to use when none is specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breed: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FindConfig {
+    /// Whether to match names and breeds that contain the given text.
+    pub fuzzy: bool,
+    /// Whether to show cats that match any of the name, age, and breed, instead of all of them.
+    pub any: bool,
+    /// The field to sort results by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SortField>,
+    /// Whether to sort from highest to lowest.
+    pub desc: bool,
+}
+
+impl Config {
+    /// Loads the config file at `path`, or from the default location if there isn't one.
+    ///
+    /// A missing file at the default location is not an error.
+    pub fn load(path: Option<PathBuf>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path, true),
+            None => (paths::default_config()?, false),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound && !explicit => {
+                return Ok(Self {
+                    path,
+                    ..Self::default()
+                })
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let config = toml::from_str(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(Self { path, ..config })
+    }
+
+    /// Writes the config back to where it was loaded from.
+    ///
+    /// Comments and formatting in the file are not preserved.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// Looks up the registry database for a profile.
+    pub fn profile(&self, name: &str) -> Result<PathBuf> {
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            anyhow!(
+                "There is no profile named `{}`, add one with `cats profile add`",
+                name
+            )
+        })
+    }
+
+    /// Fills in anything the command line left unspecified.
+    pub fn apply(&self, cmd: &mut Cmd) {
+        match cmd {
+            Cmd::Add { cmd } => {
+                cmd.breed = cmd.breed.take().or_else(|| self.add.breed.clone());
+            }
+            Cmd::Find { cmd } => {
+                cmd.fuzzy |= self.find.fuzzy
+                    && !cmd.regex
+                    && !cmd.glob
+                    && cmd.match_mode.is_none()
+                    && cmd.fuzzy_distance.is_none();
+                cmd.any |= self.find.any && !cmd.all;
+                if cmd.sort.is_none() {
+                    cmd.sort = self.find.sort;
+                    cmd.desc |= self.find.desc;
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+// A registry kept in a single JSON file, for people with a handful of cats who want to read,
+// grep, and version their registry by hand.
+// The file is loaded into an in-memory SQLite database, so every command works the same as it
+// does against a real one, and is written back out after any command that changes it.
+
+use crate::migrations::{self, LATEST};
+use crate::store;
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use serde_json::{json, Map, Value as Json};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+/// The tables that make up the registry, in the order they are written to the file.
+///
+/// Owners, litters, and locations come before cats, which is the order they usually refer to each other in.
+const TABLES: &[&str] = &[
+    "owners",
+    "litters",
+    "locations",
+    "cats",
+    "cat_history",
+    "saved_searches",
+    "weights",
+    "fields",
+    "attributes",
+    "photos",
+    "attachments",
+    "medical_events",
+    "medications",
+    "bonds",
+    "events",
+    "feeding_schedule",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Registry {
+    version: usize,
+    #[serde(default)]
+    owners: Vec<Map<String, Json>>,
+    #[serde(default)]
+    litters: Vec<Map<String, Json>>,
+    #[serde(default)]
+    locations: Vec<Map<String, Json>>,
+    cats: Vec<Map<String, Json>>,
+    cat_history: Vec<Map<String, Json>>,
+    #[serde(default)]
+    saved_searches: Vec<Map<String, Json>>,
+    #[serde(default)]
+    weights: Vec<Map<String, Json>>,
+    #[serde(default)]
+    fields: Vec<Map<String, Json>>,
+    #[serde(default)]
+    attributes: Vec<Map<String, Json>>,
+    #[serde(default)]
+    photos: Vec<Map<String, Json>>,
+    #[serde(default)]
+    attachments: Vec<Map<String, Json>>,
+    #[serde(default)]
+    medical_events: Vec<Map<String, Json>>,
+    #[serde(default)]
+    medications: Vec<Map<String, Json>>,
+    #[serde(default)]
+    bonds: Vec<Map<String, Json>>,
+    #[serde(default)]
+    events: Vec<Map<String, Json>>,
+    #[serde(default)]
+    feeding_schedule: Vec<Map<String, Json>>,
+}
+
+/// Loads the registry at `path` into an in-memory database. A missing file is an empty registry.
+pub fn load(path: &Path) -> Result<Connection> {
+    let mut conn = Connection::open_in_memory()?;
+    store::add_functions(&conn)?;
+    migrations::migrate(&mut conn)?;
+    let registry: Registry = match File::open(path) {
+        Ok(file) => serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Couldn't read the registry {}", path.display()))?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(conn),
+        Err(e) => return Err(e.into()),
+    };
+    if registry.version > LATEST {
+        bail!(
+            "The registry uses schema version {}, but this version of cats only supports up to {}",
+            registry.version,
+            LATEST
+        );
+    }
+    let tx = conn.transaction()?;
+    // A mother can have been added after her kittens, so references are checked once it's all in.
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+    // Rows from older versions simply lack the newer columns, which get their defaults.
+    for (table, rows) in TABLES.iter().zip([
+        &registry.owners,
+        &registry.litters,
+        &registry.locations,
+        &registry.cats,
+        &registry.cat_history,
+        &registry.saved_searches,
+        &registry.weights,
+        &registry.fields,
+        &registry.attributes,
+        &registry.photos,
+        &registry.attachments,
+        &registry.medical_events,
+        &registry.medications,
+        &registry.bonds,
+        &registry.events,
+        &registry.feeding_schedule,
+    ]) {
+        for row in rows {
+            if let Some(column) = row.keys().find(|column| {
+                !column
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }) {
+                bail!("Invalid column `{}` in `{}`", column, table);
+            }
+            let stmt = format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                table,
+                row.keys().join(", "),
+                row.keys().map(|_| "?").join(", ")
+            );
+            let params = row.values().map(to_sql).collect::<Result<Vec<_>>>()?;
+            tx.prepare_cached(&stmt)?
+                .execute(params_from_iter(params))
+                .with_context(|| format!("Invalid row in `{}`", table))?;
+        }
+    }
+    tx.commit()?;
+    Ok(conn)
+}
+
+/// Writes the registry back to `path`, replacing it only once the new version is complete.
+pub fn save(conn: &Connection, path: &Path) -> Result<()> {
+    let mut tables = TABLES
+        .iter()
+        .map(|table| dump(conn, table))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter();
+    let registry = Registry {
+        version: migrations::version(conn)?,
+        owners: tables.next().unwrap_or_default(),
+        litters: tables.next().unwrap_or_default(),
+        locations: tables.next().unwrap_or_default(),
+        cats: tables.next().unwrap_or_default(),
+        cat_history: tables.next().unwrap_or_default(),
+        saved_searches: tables.next().unwrap_or_default(),
+        weights: tables.next().unwrap_or_default(),
+        fields: tables.next().unwrap_or_default(),
+        attributes: tables.next().unwrap_or_default(),
+        photos: tables.next().unwrap_or_default(),
+        attachments: tables.next().unwrap_or_default(),
+        medical_events: tables.next().unwrap_or_default(),
+        medications: tables.next().unwrap_or_default(),
+        bonds: tables.next().unwrap_or_default(),
+        events: tables.next().unwrap_or_default(),
+        feeding_schedule: tables.next().unwrap_or_default(),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer_pretty(&mut writer, &registry)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn dump(conn: &Connection, table: &str) -> Result<Vec<Map<String, Json>>> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {} ORDER BY rowid", table))?;
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let rows = stmt.query_map([], |row| {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| Ok((column.clone(), from_sql(row.get(i)?))))
+            .collect()
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn to_sql(value: &Json) -> Result<Value> {
+    Ok(match value {
+        Json::Null => Value::Null,
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        Json::String(s) => Value::Text(s.clone()),
+        Json::Object(object) => match object.get("hex") {
+            Some(Json::String(hex)) if object.len() == 1 => Value::Blob(from_hex(hex)?),
+            _ => bail!(
+                "Expected a blob like {{\"hex\": \"...\"}}, found `{}`",
+                value
+            ),
+        },
+        _ => bail!("Expected a string, number, or null, found `{}`", value),
+    })
+}
+
+fn from_sql(value: Value) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Integer(i) => i.into(),
+        Value::Real(f) => f.into(),
+        Value::Text(s) => s.into(),
+        // JSON has no bytes, so blobs like photos are written out in hex.
+        Value::Blob(bytes) => {
+            json!({ "hex": bytes.iter().map(|byte| format!("{:02x}", byte)).join("") })
+        }
+    }
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 == 1 {
+        bail!("Invalid hex `{}`", hex);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .with_context(|| format!("Invalid hex `{}`", hex))
+        })
+        .collect()
+}
@@ -0,0 +1,299 @@
+// Translations of what cats prints, for `--lang`.
+// Text is looked up by its English, the way gettext does it, so that English is written out where
+// it's used and anything without a translation yet is still printed in English.
+
+use crate::args::Lang;
+use std::sync::OnceLock;
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Sets the language to print in. Only the first call has any effect.
+pub fn set(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// Translates text into the language being printed in.
+pub fn tr(text: &'static str) -> &'static str {
+    let translations = match LANG.get().copied().unwrap_or_default() {
+        Lang::English => return text,
+        Lang::Spanish => SPANISH,
+        Lang::German => GERMAN,
+    };
+    translations
+        .iter()
+        .find(|(english, _)| *english == text)
+        .map_or(text, |(_, translated)| translated)
+}
+
+const SPANISH: &[(&str, &str)] = &[
+    ("<none>", "<ninguno>"),
+    ("<unknown>", "<desconocido>"),
+    ("Action", "Acción"),
+    ("Added", "Añadido"),
+    ("Age", "Edad"),
+    ("Amount", "Cantidad"),
+    ("Analyzed", "Analizado"),
+    ("Average age", "Edad media"),
+    ("Born", "Nacido"),
+    ("Breed", "Raza"),
+    ("Breeds", "Razas"),
+    ("Cat", "Gato"),
+    ("Cats", "Gatos"),
+    ("Change", "Cambio"),
+    ("Changes", "Cambios"),
+    ("Coat", "Pelaje"),
+    ("Date", "Fecha"),
+    ("Description", "Descripción"),
+    ("Details", "Detalles"),
+    ("Died", "Murió"),
+    ("Diet", "Dieta"),
+    ("Difference", "Diferencia"),
+    ("Dose", "Dosis"),
+    ("Drug", "Medicamento"),
+    ("Due", "Próxima"),
+    ("Email", "Correo"),
+    ("End", "Fin"),
+    ("Energy", "Energía"),
+    ("Expires", "Caduca"),
+    ("Father", "Padre"),
+    ("Fix", "Solución"),
+    ("Fixed", "Esterilizado"),
+    ("Food", "Comida"),
+    ("Format", "Formato"),
+    ("Frequency", "Frecuencia"),
+    ("Good with dogs", "Se lleva bien con perros"),
+    ("Good with kids", "Se lleva bien con niños"),
+    ("Group", "Grupo"),
+    ("Kind", "Tipo"),
+    ("Kittens", "Gatitos"),
+    ("License", "Licencia"),
+    ("Litter trained", "Usa el arenero"),
+    ("Location", "Ubicación"),
+    ("Location unknown", "Ubicación desconocida"),
+    ("Meals", "Comidas"),
+    ("Median age", "Edad mediana"),
+    ("Microchip", "Microchip"),
+    ("Mother", "Madre"),
+    ("Name", "Nombre"),
+    ("No attachments found", "No se encontraron documentos"),
+    ("No breed", "Sin raza"),
+    ("No cats have a breed", "Ningún gato tiene raza"),
+    ("No cats need feeding", "Ningún gato necesita comer"),
+    ("No custom fields exist", "No hay campos personalizados"),
+    ("No events found", "No se encontraron eventos"),
+    ("No feedings found", "No se encontraron comidas"),
+    ("No history found", "No se encontró historial"),
+    ("No litters found", "No se encontraron camadas"),
+    ("No locations found", "No se encontraron ubicaciones"),
+    (
+        "No medical events found",
+        "No se encontraron eventos médicos",
+    ),
+    ("No medications found", "No se encontraron medicamentos"),
+    ("No owners found", "No se encontraron dueños"),
+    (
+        "No parents or kittens have been recorded for this cat",
+        "No se han registrado padres ni gatitos de este gato",
+    ),
+    ("No photos found", "No se encontraron fotos"),
+    ("No problems found", "No se encontraron problemas"),
+    ("No profiles exist", "No hay perfiles"),
+    (
+        "No relatives or bonded cats have been recorded for this cat",
+        "No se han registrado parientes ni gatos vinculados a este gato",
+    ),
+    ("No saved searches exist", "No hay búsquedas guardadas"),
+    ("No such cat exists", "Ese gato no existe"),
+    (
+        "No weights have been recorded for this cat",
+        "No se han registrado pesos de este gato",
+    ),
+    ("Notes", "Notas"),
+    ("Nothing is expiring", "Nada está por caducar"),
+    ("Number", "Número"),
+    ("Oldest", "Mayor"),
+    ("Options", "Opciones"),
+    ("Owner", "Dueño"),
+    ("Parameters:", "Parámetros:"),
+    ("Phone", "Teléfono"),
+    ("Photo", "Foto"),
+    ("Problem", "Problema"),
+    ("Query plan:", "Plan de la consulta:"),
+    ("Reason", "Motivo"),
+    ("Registry", "Registro"),
+    ("Reindexed", "Reindexado"),
+    ("Relation", "Parentesco"),
+    ("Renumbered", "Renumerados"),
+    ("Sex", "Sexo"),
+    ("Similar to", "Parecida a"),
+    ("Size", "Tamaño"),
+    ("Size after", "Tamaño después"),
+    ("Size before", "Tamaño antes"),
+    ("Skipped", "Omitidos"),
+    ("Start", "Inicio"),
+    ("Status", "Estado"),
+    (
+        "The registries contain the same cats",
+        "Los registros contienen los mismos gatos",
+    ),
+    ("Time", "Hora"),
+    ("Type", "Tipo"),
+    ("Updated", "Actualizado"),
+    ("Vacuumed", "Compactado"),
+    ("Weight", "Peso"),
+    ("What", "Qué"),
+    ("Youngest", "Menor"),
+    ("avg", "media"),
+    ("bonded with", "vinculado con"),
+    ("cat", "gato"),
+    ("cats", "gatos"),
+    ("changed", "cambiado"),
+    ("day", "día"),
+    ("expired", "caducado"),
+    ("expires", "caduca"),
+    ("linked", "enlazado"),
+    ("no", "no"),
+    ("only here", "solo aquí"),
+    ("only in other", "solo en el otro"),
+    ("see", "véase"),
+    ("undoes", "deshace"),
+    ("yes", "sí"),
+];
+
+const GERMAN: &[(&str, &str)] = &[
+    ("<none>", "<keine>"),
+    ("<unknown>", "<unbekannt>"),
+    ("Action", "Aktion"),
+    ("Added", "Hinzugefügt"),
+    ("Age", "Alter"),
+    ("Amount", "Menge"),
+    ("Analyzed", "Analysiert"),
+    ("Average age", "Durchschnittsalter"),
+    ("Born", "Geboren"),
+    ("Breed", "Rasse"),
+    ("Breeds", "Rassen"),
+    ("Cat", "Katze"),
+    ("Cats", "Katzen"),
+    ("Change", "Änderung"),
+    ("Changes", "Änderungen"),
+    ("Coat", "Fell"),
+    ("Date", "Datum"),
+    ("Description", "Beschreibung"),
+    ("Details", "Details"),
+    ("Died", "Gestorben"),
+    ("Diet", "Futter"),
+    ("Difference", "Unterschied"),
+    ("Dose", "Dosis"),
+    ("Drug", "Medikament"),
+    ("Due", "Fällig"),
+    ("Email", "E-Mail"),
+    ("End", "Ende"),
+    ("Energy", "Energie"),
+    ("Expires", "Läuft ab"),
+    ("Father", "Vater"),
+    ("Fix", "Behebung"),
+    ("Fixed", "Kastriert"),
+    ("Food", "Futter"),
+    ("Format", "Format"),
+    ("Frequency", "Häufigkeit"),
+    ("Good with dogs", "Verträgt sich mit Hunden"),
+    ("Good with kids", "Verträgt sich mit Kindern"),
+    ("Group", "Gruppe"),
+    ("Kind", "Art"),
+    ("Kittens", "Kätzchen"),
+    ("License", "Lizenz"),
+    ("Litter trained", "Stubenrein"),
+    ("Location", "Standort"),
+    ("Location unknown", "Standort unbekannt"),
+    ("Meals", "Mahlzeiten"),
+    ("Median age", "Medianalter"),
+    ("Microchip", "Mikrochip"),
+    ("Mother", "Mutter"),
+    ("Name", "Name"),
+    ("No attachments found", "Keine Dokumente gefunden"),
+    ("No breed", "Ohne Rasse"),
+    ("No cats have a breed", "Keine Katze hat eine Rasse"),
+    ("No cats need feeding", "Keine Katze muss gefüttert werden"),
+    ("No custom fields exist", "Es gibt keine eigenen Felder"),
+    ("No events found", "Keine Ereignisse gefunden"),
+    ("No feedings found", "Keine Fütterungen gefunden"),
+    ("No history found", "Kein Verlauf gefunden"),
+    ("No litters found", "Keine Würfe gefunden"),
+    ("No locations found", "Keine Standorte gefunden"),
+    (
+        "No medical events found",
+        "Keine medizinischen Ereignisse gefunden",
+    ),
+    ("No medications found", "Keine Medikamente gefunden"),
+    ("No owners found", "Keine Besitzer gefunden"),
+    (
+        "No parents or kittens have been recorded for this cat",
+        "Für diese Katze sind keine Eltern oder Kätzchen eingetragen",
+    ),
+    ("No photos found", "Keine Fotos gefunden"),
+    ("No problems found", "Keine Probleme gefunden"),
+    ("No profiles exist", "Es gibt keine Profile"),
+    (
+        "No relatives or bonded cats have been recorded for this cat",
+        "Für diese Katze sind keine Verwandten oder verbundenen Katzen eingetragen",
+    ),
+    (
+        "No saved searches exist",
+        "Es gibt keine gespeicherten Suchen",
+    ),
+    ("No such cat exists", "Diese Katze gibt es nicht"),
+    (
+        "No weights have been recorded for this cat",
+        "Für diese Katze ist kein Gewicht eingetragen",
+    ),
+    ("Notes", "Notizen"),
+    ("Nothing is expiring", "Nichts läuft ab"),
+    ("Number", "Nummer"),
+    ("Oldest", "Älteste"),
+    ("Options", "Optionen"),
+    ("Owner", "Besitzer"),
+    ("Parameters:", "Parameter:"),
+    ("Phone", "Telefon"),
+    ("Photo", "Foto"),
+    ("Problem", "Problem"),
+    ("Query plan:", "Abfrageplan:"),
+    ("Reason", "Grund"),
+    ("Registry", "Register"),
+    ("Reindexed", "Neu indiziert"),
+    ("Relation", "Beziehung"),
+    ("Renumbered", "Neu nummeriert"),
+    ("Sex", "Geschlecht"),
+    ("Similar to", "Ähnlich wie"),
+    ("Size", "Größe"),
+    ("Size after", "Größe danach"),
+    ("Size before", "Größe davor"),
+    ("Skipped", "Übersprungen"),
+    ("Start", "Beginn"),
+    ("Status", "Status"),
+    (
+        "The registries contain the same cats",
+        "Die Register enthalten dieselben Katzen",
+    ),
+    ("Time", "Zeit"),
+    ("Type", "Typ"),
+    ("Updated", "Aktualisiert"),
+    ("Vacuumed", "Komprimiert"),
+    ("Weight", "Gewicht"),
+    ("What", "Was"),
+    ("Youngest", "Jüngste"),
+    ("avg", "Ø"),
+    ("bonded with", "verbunden mit"),
+    ("cat", "Katze"),
+    ("cats", "Katzen"),
+    ("changed", "geändert"),
+    ("day", "Tag"),
+    ("expired", "abgelaufen"),
+    ("expires", "läuft ab"),
+    ("linked", "verknüpft"),
+    ("no", "nein"),
+    ("only here", "nur hier"),
+    ("only in other", "nur im anderen"),
+    ("see", "siehe"),
+    ("undoes", "macht rückgängig"),
+    ("yes", "ja"),
+];
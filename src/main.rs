@@ -11,67 +11,980 @@ extern crate rusqlite;
 #[macro_use]
 extern crate prettytable;
 
-use crate::args::{Args, Cmd};
-use anyhow::Result;
-use rusqlite::Connection;
+use crate::args::{
+    Args, Backend, Cmd, CmdAttachment, CmdFeeding, CmdImport, CmdSearch, ImportKey, Lang,
+    OnConflict, OutputFormat,
+};
+use crate::cmds::{Cat, NoSuchCat, SelectedCats};
+use crate::config::Config;
+use crate::store::{CatStore, SqliteStore};
+use crate::template::Template;
+use crate::terminal::Style;
+use anyhow::{bail, Context, Result};
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use serde_json::json;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::Path;
 use std::process;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
 mod args;
 mod cmds;
+mod config;
+mod flatfile;
+mod i18n;
 mod migrations;
+mod paths;
+mod query;
+#[cfg(feature = "remote")]
+mod remote;
+mod store;
+mod template;
+mod terminal;
+mod timing;
 
 fn main() {
-    match main_() {
+    let args = Args::from_args();
+    // Only `--json` and `--format json` count, not the config file's format, since errors can
+    // happen before it has been read.
+    let json = args.json || args.format == Some(OutputFormat::Json);
+    match main_(args) {
         Ok(_) => (),
+        Err(e) if e.is::<NothingFound>() => process::exit(2),
+        Err(e) if json => {
+            eprintln!("{}", error_json(&e));
+            process::exit(-1);
+        }
         Err(e) => {
-            eprintln!("{}", e);
+            eprintln!("{:#}", e);
             process::exit(-1);
         }
     }
 }
 
-fn main_() -> Result<()> {
+/// An error as a JSON object, for programs to tell errors apart by their code instead of by what
+/// they say, which can change.
+///
+/// The code is worked out from the first error in the chain that has one:
+///
+/// - `no_such_cat`, along with the cat's `id`
+/// - `busy`, when another process has the registry locked for too long
+/// - `constraint`, when a change would break the registry's rules, like using a taken name
+/// - `database`, for any other problem with the registry
+/// - `io`, for problems reading or writing files
+/// - `invalid_data`, for JSON, TOML, or CSV that couldn't be read
+/// - `remote`, when a remote registry couldn't be reached
+/// - `error`, for everything else
+fn error_json(e: &anyhow::Error) -> serde_json::Value {
+    let mut details = serde_json::Map::new();
+    let code = e
+        .chain()
+        .find_map(|cause| {
+            if let Some(NoSuchCat(id)) = cause.downcast_ref() {
+                details.insert("id".to_string(), (*id).into());
+                Some("no_such_cat")
+            } else if let Some(e) = cause.downcast_ref::<rusqlite::Error>() {
+                Some(match e {
+                    rusqlite::Error::SqliteFailure(e, _) => match e.code {
+                        ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => "busy",
+                        ErrorCode::ConstraintViolation => "constraint",
+                        _ => "database",
+                    },
+                    _ => "database",
+                })
+            } else if cause.is::<io::Error>() {
+                Some("io")
+            } else if cause.is::<serde_json::Error>()
+                || cause.is::<toml::de::Error>()
+                || cause.is::<csv::Error>()
+            {
+                Some("invalid_data")
+            } else {
+                remote_code(cause)
+            }
+        })
+        .unwrap_or("error");
+    details.insert("message".to_string(), e.to_string().into());
+    let causes = e.chain().skip(1).map(|cause| cause.to_string());
+    details.insert("causes".to_string(), causes.collect::<Vec<_>>().into());
+    json!({ "error": details, "code": code })
+}
+
+#[cfg(feature = "remote")]
+fn remote_code(cause: &(dyn Error + 'static)) -> Option<&'static str> {
+    cause.is::<ureq::Error>().then_some("remote")
+}
+
+#[cfg(not(feature = "remote"))]
+fn remote_code(_cause: &(dyn Error + 'static)) -> Option<&'static str> {
+    None
+}
+
+fn main_(args: Args) -> Result<()> {
+    use Cmd::*;
+    let Args {
+        mut cmd,
+        format,
+        json,
+        print0,
+        color,
+        table_style,
+        lang,
+        no_truncate,
+        no_pager,
+        timing,
+        output,
+        template,
+        template_file,
+        fields,
+        db,
+        backend,
+        seed,
+        key,
+        token,
+        read_only,
+        dry_run,
+        profile,
+        config,
+    } = args;
+    // This comes first, so that everything that checks whether stdout is a terminal sees the file.
+    let output = match output {
+        Some(path) => Some(
+            terminal::redirect(&path)
+                .with_context(|| format!("Couldn't write to {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let finish = |output: Option<terminal::Output>| match output {
+        Some(output) => output.finish().context("Couldn't save the output"),
+        None => Ok(()),
+    };
+    if read_only && cmd.writes() {
+        bail!("This command changes the registry, which isn't allowed with --read-only");
+    }
+    if dry_run
+        && !matches!(
+            cmd,
+            Add { .. } | Update { .. } | Delete { .. } | Import { .. } | Batch { .. }
+        )
+    {
+        bail!("--dry-run only works with add, update, delete, import, and batch");
+    }
+    let mut config = Config::load(config)?;
+    config.apply(&mut cmd);
+    if print0 && !matches!(format, None | Some(OutputFormat::Tsv)) {
+        bail!("--print0 only works with --format tsv");
+    }
+    if fields.is_some() && matches!(format, Some(OutputFormat::Json | OutputFormat::Toml)) {
+        bail!("--fields doesn't work with JSON or TOML, which always have every field");
+    }
+    let format = if print0 {
+        OutputFormat::Tsv
+    } else {
+        format
+            .or_else(|| json.then_some(OutputFormat::Json))
+            .or(config.format)
+            .unwrap_or_else(OutputFormat::detect)
+    };
+    let template = match (template, template_file) {
+        (Some(template), _) => Some(Template::inline(&template)?),
+        (None, Some(file)) => Some(Template::parse(
+            &fs::read_to_string(&file)
+                .with_context(|| format!("Couldn't read the template {}", file.display()))?,
+        )?),
+        (None, None) => None,
+    };
+    i18n::set(lang.unwrap_or_else(Lang::detect));
+    let style = Style {
+        color: color.enabled(),
+        table: table_style.or(config.table_style).unwrap_or_default(),
+        width: if no_truncate { None } else { terminal::width() },
+    };
+    let print = |result: &dyn Printable, quiet: bool| {
+        if let (true, Some(cats)) = (quiet, result.cats()) {
+            for cat in cats {
+                println!("{}", cat.id);
+            }
+            return;
+        }
+        let paged = format == OutputFormat::Table && template.is_none() && !no_pager && !timing;
+        let _pager = if paged { terminal::page() } else { None };
+        match (&template, &fields, result.cats()) {
+            (Some(template), _, _) => result.print_template(template),
+            (None, Some(columns), Some(cats)) => {
+                SelectedCats { cats, columns }.print(format, print0, style)
+            }
+            (None, _, _) => result.print(format, print0, style),
+        }
+    };
+    // Profiles live in the config file, so there is no need to open a registry for them.
+    if let Profile { cmd } = cmd {
+        print(&cmds::profile(&mut config, cmd)?, false);
+        return finish(output);
+    }
+    let db = if let Some(profile) = profile {
+        config.profile(&profile)?
+    } else if let Some(db) = db.or_else(|| config.db.clone()) {
+        db
+    } else if backend == Backend::Json {
+        paths::default_db()?.with_extension("json")
+    } else {
+        paths::default_db()?
+    };
+    let writes = cmd.writes() || seed.is_some();
+    let started = Instant::now();
+    let mut registry = if is_remote(&db) {
+        if key.is_some() {
+            bail!("Remote registries can't be encrypted with --key");
+        }
+        if backend == Backend::Json {
+            bail!("Remote registries always use the sqlite backend");
+        }
+        open_remote(&db, token, read_only)?
+    } else if backend == Backend::Json {
+        if key.is_some() {
+            bail!("JSON registries can't be encrypted with --key");
+        }
+        Registry::Local(flatfile::load(&db)?)
+    } else {
+        Registry::Local(open_local(&db, key, read_only, config.wal)?)
+    };
+    let opened = Instant::now();
+    if let Some(seed) = seed {
+        cmds::import(
+            registry.local()?,
+            CmdImport {
+                file: seed,
+                format: None,
+                on_conflict: OnConflict::Skip,
+                key: ImportKey::Id,
+            },
+        )?;
+    }
+    // Everything the command does happens inside this, which is rolled back once it's done, or by
+    // the connection closing if it fails. It takes the write lock up front, since once another
+    // process has written, retrying a busy write inside a deferred transaction can never succeed.
+    if dry_run {
+        registry.local()?.execute_batch("BEGIN IMMEDIATE")?;
+    }
+    // Undoes a dry run, or otherwise saves what the command did, once it has succeeded.
+    let commit = |registry: &Registry| -> Result<()> {
+        if dry_run {
+            registry.local()?.execute_batch("ROLLBACK")?;
+        } else if backend == Backend::Json && writes {
+            flatfile::save(registry.local()?, &db)?;
+        }
+        Ok(())
+    };
+    let mut ran = opened;
+    let empty = if let Batch { file } = cmd {
+        let empty = batch(&file, &mut registry, &config, dry_run, &print)?;
+        commit(&registry)?;
+        ran = Instant::now();
+        empty
+    } else {
+        run(
+            cmd,
+            &mut registry,
+            &config,
+            dry_run,
+            &mut |registry, result, quiet| {
+                commit(registry)?;
+                ran = Instant::now();
+                print(result, quiet);
+                Ok(())
+            },
+        )?
+    };
+    if dry_run {
+        eprintln!("This was a dry run, so nothing was changed");
+    }
+    if timing {
+        timing::report(opened - started, ran - opened, ran.elapsed());
+    }
+    finish(output)?;
+    if empty {
+        return Err(NothingFound.into());
+    }
+    Ok(())
+}
+
+/// Runs a command, then hands what it produced to `output` along with whether only the cats' IDs
+/// should be printed. Returns whether `--fail-if-empty` was passed and nothing matched.
+fn run(
+    mut cmd: Cmd,
+    registry: &mut Registry,
+    config: &Config,
+    dry_run: bool,
+    output: &mut dyn FnMut(&Registry, &dyn Printable, bool) -> Result<()>,
+) -> Result<bool> {
     use Cmd::*;
-    const PATH: &str = "cat_registry.db";
-    let conn = Connection::open(PATH)?;
-    migrations::migration1(&conn)?;
-    let Args { cmd, json } = Args::from_args();
-    let (a, f, g, u, d);
+    // Running a saved search is the same as running `find` with its options.
+    if let Search {
+        cmd: Some(CmdSearch::Run { name }),
+        ..
+    } = &cmd
+    {
+        cmd = Find {
+            cmd: cmds::saved_search(registry.local()?, name)?,
+        };
+        config.apply(&mut cmd);
+    }
+    let sqlite_store;
+    #[cfg(feature = "remote")]
+    let remote_store;
+    let store: &dyn CatStore = match &*registry {
+        Registry::Local(conn) => {
+            sqlite_store = SqliteStore::new(conn);
+            &sqlite_store
+        }
+        #[cfg(feature = "remote")]
+        Registry::Remote(remote) => {
+            remote_store = remote::RemoteStore::new(remote);
+            &remote_store
+        }
+    };
+    let (
+        a,
+        ab,
+        ub,
+        dl,
+        f,
+        c,
+        x,
+        sr,
+        ss,
+        g,
+        u,
+        d,
+        r,
+        rb,
+        h,
+        n,
+        e,
+        b,
+        i,
+        k,
+        dr,
+        m,
+        mg,
+        df,
+        s,
+        sg,
+        ch,
+        br,
+        wg,
+        fl,
+        p,
+        gp,
+        o,
+        md,
+        rx,
+        lt,
+        pd,
+        rl,
+        bd,
+        ev,
+        lc,
+        fd,
+        fr,
+        ex,
+        at,
+    );
+    // Set when `--fail-if-empty` was passed and nothing matched.
+    let mut empty = false;
+    // Set when `--quiet` was passed, so only the cats' IDs are printed.
+    let mut quiet = false;
     let result: &dyn Printable = match cmd {
+        Add { cmd } if cmd.from.is_some() || !cmd.cat.is_empty() => {
+            quiet = cmd.quiet;
+            ab = cmds::add_all(registry.local()?, cmd)?;
+            &ab
+        }
         Add { cmd } => {
-            a = cmds::add(&conn, cmd)?;
+            quiet = cmd.quiet;
+            a = cmds::add(store, cmd)?;
             &a
         }
-        Delete { id } => {
-            d = cmds::delete(&conn, id)?;
-            &d
+        Delete {
+            id,
+            filter,
+            purge,
+            yes,
+        } => {
+            // Nothing needs confirming when it'll all be undone.
+            let yes = yes || dry_run;
+            match id[..] {
+                [] => {
+                    dl = cmds::delete_all(registry.local()?, filter, purge, yes)?;
+                    &dl
+                }
+                [id] => {
+                    d = cmds::delete(store, id, purge, yes)?;
+                    &d
+                }
+                _ => {
+                    dl = cmds::delete_many(registry.local()?, &id, purge, yes)?;
+                    &dl
+                }
+            }
+        }
+        Restore {
+            id,
+            file,
+            force,
+            merge,
+        } => match (id, file) {
+            (Some(id), _) => {
+                r = cmds::restore(registry.local()?, id)?;
+                &r
+            }
+            (None, Some(file)) => {
+                rb = cmds::restore_backup(registry.local_mut()?, &file, force, merge)?;
+                &rb
+            }
+            (None, None) => unreachable!("clap requires one of --id and --file"),
+        },
+        Find { cmd } if cmd.explain => {
+            x = cmds::explain_find(registry.local()?, cmd)?;
+            &x
+        }
+        Find { cmd } if cmd.count => {
+            empty = cmd.fail_if_empty;
+            c = cmds::count(store, cmd)?;
+            empty &= c == 0;
+            &c
         }
         Find { cmd } => {
-            f = cmds::find(&conn, cmd)?;
-            &f
+            empty = cmd.fail_if_empty;
+            quiet = cmd.quiet;
+            let show_photo = cmd.show_photo;
+            f = cmds::find(store, cmd)?;
+            empty &= f.cats.is_empty();
+            if show_photo {
+                gp = cmds::with_photos(registry.local()?, f.cats)?;
+                &gp
+            } else {
+                &f
+            }
+        }
+        Search {
+            query: Some(query), ..
+        } => {
+            sr = cmds::search(store, &query)?;
+            &sr
+        }
+        Search { cmd: Some(cmd), .. } => {
+            ss = cmds::saved_searches(registry.local()?, cmd)?;
+            &ss
+        }
+        Search {
+            query: None,
+            cmd: None,
+        } => unreachable!("clap requires an argument or a subcommand"),
+        Get {
+            id,
+            microchip,
+            fail_if_empty,
+            show_photo,
+        } => {
+            g = match microchip {
+                Some(microchip) => cmds::get_by_microchip(store, microchip)?,
+                None => cmds::get(store, &id)?,
+            };
+            empty = fail_if_empty && g.is_empty();
+            if show_photo {
+                gp = cmds::with_photos(registry.local()?, g)?;
+                &gp
+            } else {
+                &g
+            }
+        }
+        Update { mut cmd } => {
+            quiet = cmd.quiet;
+            cmd.yes |= dry_run;
+            match cmd.id {
+                Some(id) if cmd.explain => {
+                    x = cmds::explain_update(registry.local()?, id, cmd)?;
+                    &x
+                }
+                Some(id) => {
+                    u = cmds::update(store, id, cmd)?;
+                    &u
+                }
+                None => {
+                    ub = cmds::update_all(registry.local()?, cmd)?;
+                    &ub
+                }
+            }
+        }
+        History { id } => {
+            h = cmds::history(registry.local()?, id)?;
+            &h
+        }
+        Undo { id } => {
+            n = cmds::undo(registry.local()?, id)?;
+            &n
+        }
+        Weigh { id, weight, date } => {
+            wg = cmds::weigh(registry.local()?, id, weight, date)?;
+            &wg
+        }
+        Weights { id } => {
+            wg = cmds::weights(registry.local()?, id)?;
+            &wg
+        }
+        Pedigree { id, depth } => {
+            pd = cmds::pedigree(registry.local()?, id, depth)?;
+            &pd
+        }
+        Event { cmd } => {
+            ev = cmds::event(registry.local()?, cmd)?;
+            &ev
+        }
+        Medical { cmd } => {
+            md = cmds::medical(registry.local()?, cmd)?;
+            &md
+        }
+        Feeding {
+            cmd: CmdFeeding::Today,
+        } => {
+            fr = cmds::feeding_today(registry.local()?)?;
+            &fr
+        }
+        Feeding { cmd } => {
+            fd = cmds::feeding(registry.local()?, cmd)?;
+            &fd
+        }
+        Expiring { within } => {
+            ex = cmds::expiring(registry.local()?, within)?;
+            &ex
+        }
+        Meds { cmd } => {
+            rx = cmds::meds(registry.local()?, cmd)?;
+            &rx
+        }
+        Owner { cmd } => {
+            o = cmds::owner(registry.local()?, cmd)?;
+            &o
+        }
+        Adopt { id, owner, date } => {
+            a = cmds::adopt(registry.local()?, id, owner, date)?;
+            &a
+        }
+        Location { cmd } => {
+            lc = cmds::location(registry.local()?, cmd)?;
+            &lc
+        }
+        Move { id, to } => {
+            a = cmds::move_cat(registry.local()?, id, &to)?;
+            &a
+        }
+        Return { id } => {
+            a = cmds::return_cat(registry.local()?, id)?;
+            &a
+        }
+        MarkDeceased { id, date } => {
+            a = cmds::mark_deceased(registry.local()?, id, date)?;
+            &a
+        }
+        Assign {
+            id,
+            owner,
+            unassign,
+        } => {
+            let owner = match (owner, unassign) {
+                (Some(owner), false) => Some(owner),
+                (None, true) => None,
+                _ => unreachable!("clap requires exactly one of --owner and --unassign"),
+            };
+            a = cmds::assign(registry.local()?, id, owner)?;
+            &a
         }
-        Get { id } => {
-            g = cmds::get(&conn, &id)?;
-            &g
+        Litter { cmd } => {
+            lt = cmds::litter(registry.local()?, cmd)?;
+            &lt
         }
-        Update { cmd } => {
-            u = cmds::update(&conn, cmd)?;
-            &u
+        Related { id } => {
+            rl = cmds::related(registry.local()?, id)?;
+            &rl
         }
+        Bond { id, with, remove } => {
+            bd = cmds::bond(registry.local()?, id, with, remove)?;
+            &bd
+        }
+        Photo { cmd } => {
+            p = cmds::photo(registry.local()?, cmd)?;
+            &p
+        }
+        Attachment { cmd } => {
+            at = cmds::attachment(registry.local()?, cmd)?;
+            &at
+        }
+        Attach {
+            id,
+            file,
+            description,
+            mime_type,
+            link,
+        } => {
+            let cmd = CmdAttachment::Add {
+                id,
+                file,
+                description,
+                mime_type,
+                link,
+            };
+            at = cmds::attachment(registry.local()?, cmd)?;
+            &at
+        }
+        Attachments { id } => {
+            at = cmds::attachment(registry.local()?, CmdAttachment::List { id })?;
+            &at
+        }
+        Field { cmd } => {
+            fl = cmds::field(registry.local()?, cmd)?;
+            &fl
+        }
+        Stats { by: Some(by), .. } => {
+            sg = cmds::group_stats(registry.local()?, by)?;
+            &sg
+        }
+        Stats { chart: true, .. } => {
+            ch = cmds::stats_chart(registry.local()?)?;
+            &ch
+        }
+        Stats { by: None, .. } => {
+            s = cmds::stats(registry.local()?)?;
+            &s
+        }
+        Breeds => {
+            br = cmds::breeds(registry.local()?)?;
+            &br
+        }
+        Export { cmd } => {
+            e = cmds::export(registry.local()?, cmd)?;
+            &e
+        }
+        Backup { file, force } => {
+            b = cmds::backup(registry.local()?, &file, force)?;
+            &b
+        }
+        Import { cmd } => {
+            i = cmds::import(registry.local()?, cmd)?;
+            &i
+        }
+        Rekey { new_key } => {
+            k = cmds::rekey(registry.local()?, &new_key)?;
+            &k
+        }
+        Merge { file, strategy } => {
+            mg = cmds::merge(registry.local()?, &file, strategy)?;
+            &mg
+        }
+        Diff { file } => {
+            df = cmds::diff(registry.local()?, &file)?;
+            &df
+        }
+        Doctor { fix } => {
+            dr = cmds::doctor(registry.local()?, fix)?;
+            &dr
+        }
+        Maintenance { cmd } => {
+            m = cmds::maintenance(registry.local()?, cmd)?;
+            &m
+        }
+        Profile { .. } => unreachable!("profiles are handled before opening the registry"),
+        Batch { .. } => unreachable!("scripts are run by `batch`, which can't run other scripts"),
     };
-    if json {
-        result.print_json();
-    } else if atty::is(atty::Stream::Stdout) {
-        result.print_display();
+    output(registry, result, quiet)?;
+    Ok(empty)
+}
+
+/// Runs each command in a script in one transaction, printing their results once it's committed.
+///
+/// If any of them fail, the transaction is left for the connection to roll back when it closes.
+fn batch(
+    file: &Path,
+    registry: &mut Registry,
+    config: &Config,
+    dry_run: bool,
+    print: &dyn Fn(&dyn Printable, bool),
+) -> Result<bool> {
+    let script = if file == Path::new("-") {
+        io::read_to_string(io::stdin())?
     } else {
-        result.print_plain();
+        fs::read_to_string(file)
+            .with_context(|| format!("Couldn't read the script {}", file.display()))?
+    };
+    // Every line is read before any of them run, so that a mistake anywhere stops the script
+    // before it has done anything.
+    let mut cmds = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let words =
+            args::split_words(line).with_context(|| format!("Line {} is invalid", i + 1))?;
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+        let mut cmd = Cmd::from_iter_safe(iter::once("batch".to_string()).chain(words))
+            .with_context(|| format!("Line {} is invalid", i + 1))?;
+        if !cmd.batchable() {
+            bail!(
+                "Line {} can't be run in a script, which all runs in one transaction",
+                i + 1
+            );
+        }
+        config.apply(&mut cmd);
+        cmds.push((i + 1, cmd));
     }
-    Ok(())
+    // Results are only printed once they've been committed, so nothing is printed for a script
+    // that didn't happen.
+    let held = terminal::hold().context("Couldn't hold back the output")?;
+    // A dry run is already inside a transaction, which will be rolled back.
+    if !dry_run {
+        registry.local()?.execute_batch("BEGIN IMMEDIATE")?;
+    }
+    let mut empty = false;
+    for (line, cmd) in cmds {
+        empty |= run(cmd, registry, config, dry_run, &mut |_, result, quiet| {
+            print(result, quiet);
+            Ok(())
+        })
+        .with_context(|| format!("Line {} failed, so none of the script was run", line))?;
+    }
+    if !dry_run {
+        registry.local()?.execute_batch("COMMIT")?;
+    }
+    held.release()?;
+    Ok(empty)
+}
+
+/// Exits with code 2 instead of the usual error code, so scripts can tell an empty result from a
+/// failure. Code 1 is already taken by invalid arguments.
+#[derive(Debug)]
+struct NothingFound;
+
+impl Display for NothingFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Nothing was found")
+    }
+}
+
+impl Error for NothingFound {}
+
+#[cfg(feature = "remote")]
+const REMOTE_UNSUPPORTED: &str = "Remote registries only support add, get, find, search, update \
+    --id, and delete with a single --id, so this needs a local registry";
+
+enum Registry {
+    Local(Connection),
+    #[cfg(feature = "remote")]
+    Remote(remote::Remote),
+}
+
+impl Registry {
+    /// The SQLite connection, for commands that don't go through `CatStore`.
+    fn local(&self) -> Result<&Connection> {
+        match self {
+            Self::Local(conn) => Ok(conn),
+            #[cfg(feature = "remote")]
+            Self::Remote(_) => bail!(REMOTE_UNSUPPORTED),
+        }
+    }
+
+    fn local_mut(&mut self) -> Result<&mut Connection> {
+        match self {
+            Self::Local(conn) => Ok(conn),
+            #[cfg(feature = "remote")]
+            Self::Remote(_) => bail!(REMOTE_UNSUPPORTED),
+        }
+    }
+}
+
+fn is_remote(db: &Path) -> bool {
+    let db = db.to_string_lossy();
+    ["libsql://", "https://", "http://"]
+        .iter()
+        .any(|scheme| db.starts_with(scheme))
+}
+
+fn open_local(db: &Path, key: Option<String>, read_only: bool, wal: bool) -> Result<Connection> {
+    let mut conn = if read_only {
+        Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?
+    } else {
+        // SQLite treats `:memory:` as a database that only lasts as long as the connection.
+        if db != Path::new(":memory:") {
+            if let Some(parent) = db.parent() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        Connection::open(db)?
+    };
+    if let Some(key) = key {
+        cmds::unlock(&conn, &key)?;
+    }
+    conn.busy_timeout(Duration::from_secs(5))?;
+    store::add_functions(&conn)?;
+    if !read_only {
+        // WAL sticks to the file, so registries left in it by an older cats are switched back.
+        // This reports the resulting mode, which is `memory` for in-memory registries.
+        let mode = if wal { "WAL" } else { "DELETE" };
+        conn.query_row(&format!("PRAGMA journal_mode = {}", mode), [], |_| Ok(()))?;
+    }
+    if read_only {
+        migrations::check(&conn)?;
+    } else {
+        migrations::migrate(&mut conn)?;
+    }
+    Ok(conn)
+}
+
+#[cfg(feature = "remote")]
+fn open_remote(db: &Path, token: Option<String>, read_only: bool) -> Result<Registry> {
+    let remote = remote::Remote::connect(&db.to_string_lossy(), token)?;
+    if read_only {
+        remote.check()?;
+    } else {
+        remote.migrate()?;
+    }
+    Ok(Registry::Remote(remote))
+}
+
+#[cfg(not(feature = "remote"))]
+fn open_remote(_db: &Path, _token: Option<String>, _read_only: bool) -> Result<Registry> {
+    bail!("Remote registries require cats to be built with the remote feature")
 }
 
 trait Printable {
-    fn print_display(&self);
+    fn print_display(&self, style: Style);
     fn print_plain(&self);
     fn print_json(&self);
+    fn print_records(&self, dialect: Dialect);
+    fn print_toml(&self);
+    fn print_template(&self, template: &Template);
+
+    /// The cats in the result, if it is made of cats, for `--fields`.
+    fn cats(&self) -> Option<Vec<&Cat>> {
+        None
+    }
+
+    /// Prints the result in the given format, ending TSV records with NULs if `print0` is set and
+    /// drawing tables in the given style.
+    fn print(&self, format: OutputFormat, print0: bool, style: Style) {
+        match format {
+            OutputFormat::Table => self.print_display(style),
+            OutputFormat::Plain => self.print_plain(),
+            OutputFormat::PlainLabeled => self.print_records(Dialect::Labeled),
+            OutputFormat::Json => self.print_json(),
+            OutputFormat::Csv => self.print_records(Dialect::Csv),
+            OutputFormat::Tsv if print0 => self.print_records(Dialect::Print0),
+            OutputFormat::Tsv => self.print_records(Dialect::Tsv),
+            OutputFormat::Toml => self.print_toml(),
+            OutputFormat::Markdown => self.print_records(Dialect::Markdown),
+            OutputFormat::Html => self.print_records(Dialect::Html),
+        }
+    }
+}
+
+/// The ways of writing results as records, one per result, for pasting into other programs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Dialect {
+    /// RFC 4180 CSV.
+    Csv,
+    /// Tab-separated fields and one record per line, with tabs, line breaks, and backslashes
+    /// escaped like `\t`.
+    Tsv,
+    /// Tab-separated fields and records ending in a NUL, for `xargs -0`. Line breaks are left as
+    /// they are, but tabs and backslashes are still escaped.
+    Print0,
+    /// A GitHub-flavored markdown table.
+    Markdown,
+    /// A standalone HTML page with a table.
+    Html,
+    /// A `key: value` line for each field that isn't empty, labeled by the header, with a blank
+    /// line between records. Lines after the first of a field are indented under it.
+    Labeled,
+}
+
+impl Dialect {
+    fn escape(self, field: &str) -> Cow<'_, str> {
+        let replacement = |c: char| match (self, c) {
+            (Self::Tsv, '\n') => Some("\\n"),
+            (Self::Tsv, '\r') => Some("\\r"),
+            (Self::Tsv | Self::Print0, '\t') => Some("\\t"),
+            (Self::Tsv | Self::Print0, '\\') => Some("\\\\"),
+            // A markdown table's rows can't span lines, but HTML line breaks work inside them.
+            // Markdown passes HTML through, so a name like `<b>` would otherwise be a tag.
+            (Self::Markdown, '|') => Some("\\|"),
+            (Self::Markdown, '\n') => Some("<br>"),
+            (Self::Markdown, '\r') => Some(""),
+            (Self::Markdown | Self::Html, '&') => Some("&amp;"),
+            (Self::Markdown | Self::Html, '<') => Some("&lt;"),
+            (Self::Markdown | Self::Html, '>') => Some("&gt;"),
+            (Self::Html, '"') => Some("&quot;"),
+            (Self::Html, '\'') => Some("&#39;"),
+            (Self::Html, '\n') => Some("<br>"),
+            (Self::Labeled, '\n') => Some("\n  "),
+            (Self::Labeled, '\r') => Some(""),
+            _ => None,
+        };
+        if !field.contains(|c| replacement(c).is_some()) {
+            return Cow::Borrowed(field);
+        }
+        let mut escaped = String::with_capacity(field.len() + 2);
+        for c in field.chars() {
+            match replacement(c) {
+                Some(replacement) => escaped.push_str(replacement),
+                None => escaped.push(c),
+            }
+        }
+        Cow::Owned(escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_runs_change_nothing() {
+        let dir = std::env::temp_dir().join(format!("cats-test-{}-dry-run", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (db, config, script) = (
+            dir.join("cats.db"),
+            dir.join("cats.toml"),
+            dir.join("script"),
+        );
+        fs::write(&config, "").unwrap();
+        fs::write(&script, "add --name Kit --age 1\ndelete --id 1 --yes\n").unwrap();
+        let cats = |args: &[&str]| {
+            let common = [
+                "cats",
+                "--db",
+                db.to_str().unwrap(),
+                "--config",
+                config.to_str().unwrap(),
+            ];
+            main_(Args::from_iter(common.iter().chain(args))).unwrap();
+        };
+        cats(&["add", "--name", "Tom", "--age", "3"]);
+        cats(&["--dry-run", "update", "--id", "1", "--age", "5"]);
+        cats(&["--dry-run", "batch", "--file", script.to_str().unwrap()]);
+        let cats: Vec<(String, u32)> = Connection::open(&db)
+            .unwrap()
+            .prepare("SELECT name, age FROM cats WHERE deleted_at ISNULL")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(cats, [("Tom".to_string(), 3)]);
+    }
 }
@@ -1,15 +1,396 @@
-use rusqlite::Connection;
-use anyhow::Result;
+// Each migration moves the schema up by one version, which is tracked in `PRAGMA user_version`.
+// Released migrations must never be edited; add a new one to the end of the list instead.
 
-pub fn migration1(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "\
+use anyhow::{bail, Result};
+use rusqlite::{Connection, TransactionBehavior};
+
+const MIGRATIONS: &[&str] = &[
+    // 1: The original schema. Registries from before versioning already have this table.
+    "\
 CREATE TABLE IF NOT EXISTS cats (
     id INTEGER NOT NULL PRIMARY KEY ASC,
     name TEXT NOT NULL,
     age INTEGER NOT NULL,
     breed TEXT)",
-        [],
-    )?;
+    // 2: Soft deletion. Cats with a `deleted_at` timestamp are in the trash.
+    "ALTER TABLE cats ADD COLUMN deleted_at TEXT",
+    // 3: The audit log. `old` and `new` are JSON snapshots of the cat around each change.
+    "\
+CREATE TABLE cat_history (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    old TEXT,
+    new TEXT,
+    timestamp TEXT NOT NULL DEFAULT (datetime('now')));
+CREATE INDEX cat_history_cat_id ON cat_history (cat_id)",
+    // 4: Undo. Entries made by `cats undo` point at the entry they reverted.
+    "ALTER TABLE cat_history ADD COLUMN undoes INTEGER REFERENCES cat_history (id)",
+    // 5: Full-text search. The index reads its text from `cats`, and triggers keep it up to date.
+    "\
+CREATE VIRTUAL TABLE cats_fts USING fts5 (
+    name,
+    breed,
+    content = 'cats',
+    content_rowid = 'id',
+    prefix = '2 3');
+INSERT INTO cats_fts (cats_fts) VALUES ('rebuild');
+CREATE TRIGGER cats_fts_insert AFTER INSERT ON cats BEGIN
+    INSERT INTO cats_fts (rowid, name, breed) VALUES (new.id, new.name, new.breed);
+END;
+CREATE TRIGGER cats_fts_delete AFTER DELETE ON cats BEGIN
+    INSERT INTO cats_fts (cats_fts, rowid, name, breed) VALUES ('delete', old.id, old.name, old.breed);
+END;
+CREATE TRIGGER cats_fts_update AFTER UPDATE OF name, breed ON cats BEGIN
+    INSERT INTO cats_fts (cats_fts, rowid, name, breed) VALUES ('delete', old.id, old.name, old.breed);
+    INSERT INTO cats_fts (rowid, name, breed) VALUES (new.id, new.name, new.breed);
+END",
+    // 6: Saved searches. `options` is a JSON object of the `find` options that were set.
+    "\
+CREATE TABLE saved_searches (
+    name TEXT NOT NULL PRIMARY KEY,
+    options TEXT NOT NULL)",
+    // 7: Timestamps. Cats from before this are dated by their history, where they have one.
+    "\
+ALTER TABLE cats ADD COLUMN created_at TEXT;
+ALTER TABLE cats ADD COLUMN updated_at TEXT;
+UPDATE cats SET
+    created_at = (SELECT MIN(timestamp) FROM cat_history WHERE cat_id = cats.id AND action = 'add'),
+    updated_at = (SELECT MAX(timestamp) FROM cat_history
+        WHERE cat_id = cats.id AND action IN ('add', 'update'))",
+    // 8: Birthdates, as `YYYY-MM-DD`. Cats with one still have an age, as of when it was set.
+    "ALTER TABLE cats ADD COLUMN birthdate TEXT",
+    // 9: Weights, in grams.
+    "ALTER TABLE cats ADD COLUMN weight INTEGER",
+    // 10: Weight history. A cat's `weight` is from its most recent weighing.
+    "\
+CREATE TABLE weights (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    weight INTEGER NOT NULL);
+CREATE INDEX weights_cat_id ON weights (cat_id, date);
+CREATE TRIGGER weights_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM weights WHERE cat_id = old.id;
+END;
+INSERT INTO weights (cat_id, date, weight)
+    SELECT id, date(ifnull(updated_at, 'now')), weight FROM cats WHERE weight NOTNULL",
+    // 11: Sex and neuter status. Cats from before this aren't assumed to be fixed.
+    "\
+ALTER TABLE cats ADD COLUMN sex TEXT NOT NULL DEFAULT 'unknown';
+ALTER TABLE cats ADD COLUMN fixed INTEGER NOT NULL DEFAULT 0",
+    // 12: Coat color and pattern, as free text.
+    "\
+ALTER TABLE cats ADD COLUMN color TEXT;
+ALTER TABLE cats ADD COLUMN pattern TEXT",
+    // 13: Microchips. Each number belongs to one cat, even one in the trash.
+    "\
+ALTER TABLE cats ADD COLUMN microchip TEXT;
+CREATE UNIQUE INDEX cats_microchip ON cats (microchip)",
+    // 14: Notes, as free text. The full-text index is remade with them, since FTS5 tables can't
+    // gain columns.
+    "\
+ALTER TABLE cats ADD COLUMN notes TEXT;
+DROP TRIGGER cats_fts_insert;
+DROP TRIGGER cats_fts_delete;
+DROP TRIGGER cats_fts_update;
+DROP TABLE cats_fts;
+CREATE VIRTUAL TABLE cats_fts USING fts5 (
+    name,
+    breed,
+    notes,
+    content = 'cats',
+    content_rowid = 'id',
+    prefix = '2 3');
+INSERT INTO cats_fts (cats_fts) VALUES ('rebuild');
+CREATE TRIGGER cats_fts_insert AFTER INSERT ON cats BEGIN
+    INSERT INTO cats_fts (rowid, name, breed, notes) VALUES (new.id, new.name, new.breed, new.notes);
+END;
+CREATE TRIGGER cats_fts_delete AFTER DELETE ON cats BEGIN
+    INSERT INTO cats_fts (cats_fts, rowid, name, breed, notes)
+        VALUES ('delete', old.id, old.name, old.breed, old.notes);
+END;
+CREATE TRIGGER cats_fts_update AFTER UPDATE OF name, breed, notes ON cats BEGIN
+    INSERT INTO cats_fts (cats_fts, rowid, name, breed, notes)
+        VALUES ('delete', old.id, old.name, old.breed, old.notes);
+    INSERT INTO cats_fts (rowid, name, breed, notes) VALUES (new.id, new.name, new.breed, new.notes);
+END",
+    // 15: Custom fields. Values are stored as whichever SQLite type suits the field's type.
+    "\
+CREATE TABLE fields (
+    name TEXT NOT NULL PRIMARY KEY,
+    type TEXT NOT NULL);
+CREATE TABLE attributes (
+    cat_id INTEGER NOT NULL,
+    field TEXT NOT NULL,
+    value NOT NULL,
+    PRIMARY KEY (cat_id, field));
+CREATE INDEX attributes_field ON attributes (field, value);
+CREATE TRIGGER attributes_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM attributes WHERE cat_id = old.id;
+END",
+    // 16: Photos. The images themselves are stored, so the registry doesn't depend on other files.
+    "\
+CREATE TABLE photos (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    format TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    data BLOB NOT NULL,
+    added_at TEXT NOT NULL DEFAULT (datetime('now')));
+CREATE INDEX photos_cat_id ON photos (cat_id, hash);
+CREATE TRIGGER photos_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM photos WHERE cat_id = old.id;
+END",
+    // 17: Owners. Each cat belongs to at most one.
+    "\
+CREATE TABLE owners (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    name TEXT NOT NULL,
+    email TEXT,
+    phone TEXT);
+ALTER TABLE cats ADD COLUMN owner_id INTEGER REFERENCES owners (id);
+CREATE INDEX cats_owner_id ON cats (owner_id)",
+    // 18: Medical records, such as vaccinations, with when each is next due.
+    "\
+CREATE TABLE medical_events (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    type TEXT NOT NULL,
+    name TEXT NOT NULL,
+    date TEXT NOT NULL,
+    due TEXT);
+CREATE INDEX medical_events_cat_id ON medical_events (cat_id, date);
+CREATE TRIGGER medical_events_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM medical_events WHERE cat_id = old.id;
+END",
+    // 19: Prescriptions, given some number of times a day every so many days between two dates.
+    "\
+CREATE TABLE medications (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    drug TEXT NOT NULL,
+    dose TEXT NOT NULL,
+    times_per_day INTEGER NOT NULL,
+    every_days INTEGER NOT NULL,
+    starts_on TEXT NOT NULL,
+    ends_on TEXT);
+CREATE INDEX medications_cat_id ON medications (cat_id);
+CREATE TRIGGER medications_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM medications WHERE cat_id = old.id;
+END",
+    // 20: Parentage. Purging a parent leaves its kittens and litters with an unknown parent.
+    "\
+CREATE TABLE litters (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    mother_id INTEGER REFERENCES cats (id),
+    father_id INTEGER REFERENCES cats (id),
+    born_on TEXT);
+ALTER TABLE cats ADD COLUMN mother_id INTEGER REFERENCES cats (id);
+ALTER TABLE cats ADD COLUMN father_id INTEGER REFERENCES cats (id);
+ALTER TABLE cats ADD COLUMN litter_id INTEGER REFERENCES litters (id);
+CREATE INDEX cats_mother_id ON cats (mother_id);
+CREATE INDEX cats_father_id ON cats (father_id);
+CREATE INDEX cats_litter_id ON cats (litter_id);
+CREATE TRIGGER parents_purge AFTER DELETE ON cats BEGIN
+    UPDATE cats SET mother_id = NULL WHERE mother_id = old.id;
+    UPDATE cats SET father_id = NULL WHERE father_id = old.id;
+    UPDATE litters SET mother_id = NULL WHERE mother_id = old.id;
+    UPDATE litters SET father_id = NULL WHERE father_id = old.id;
+END",
+    // 21: Cats that should be adopted together. Each pair is stored once, lowest ID first.
+    "\
+CREATE TABLE bonds (
+    cat_id INTEGER NOT NULL REFERENCES cats (id),
+    other_id INTEGER NOT NULL REFERENCES cats (id),
+    PRIMARY KEY (cat_id, other_id),
+    CHECK (cat_id < other_id));
+CREATE INDEX bonds_other_id ON bonds (other_id);
+CREATE TRIGGER bonds_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM bonds WHERE old.id IN (cat_id, other_id);
+END",
+    // 22: Adoption status. Cats that already have an owner have presumably been adopted by them.
+    "\
+ALTER TABLE cats ADD COLUMN status TEXT NOT NULL DEFAULT 'intake';
+ALTER TABLE cats ADD COLUMN adopted_at TEXT;
+UPDATE cats SET status = 'adopted' WHERE owner_id NOTNULL;
+CREATE INDEX cats_status ON cats (status)",
+    // 23: The day a cat died.
+    "ALTER TABLE cats ADD COLUMN deceased_at TEXT",
+    // 24: Intakes and outcomes, the way shelters report them.
+    "\
+CREATE TABLE events (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    type TEXT NOT NULL,
+    date TEXT NOT NULL,
+    reason TEXT);
+CREATE INDEX events_cat_id ON events (cat_id, date);
+CREATE TRIGGER events_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM events WHERE cat_id = old.id;
+END",
+    // 25: Where each cat physically is, such as a room, a kennel, or a foster home.
+    "\
+CREATE TABLE locations (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE);
+ALTER TABLE cats ADD COLUMN location_id INTEGER REFERENCES locations (id);
+CREATE INDEX cats_location_id ON cats (location_id)",
+    // 26: Temperament, for matching cats to households. Each is unknown until someone finds out.
+    "\
+ALTER TABLE cats ADD COLUMN good_with_dogs INTEGER;
+ALTER TABLE cats ADD COLUMN good_with_kids INTEGER;
+ALTER TABLE cats ADD COLUMN energy INTEGER CHECK (energy BETWEEN 1 AND 5);
+ALTER TABLE cats ADD COLUMN litter_trained INTEGER",
+    // 27: Dietary restrictions, and what each cat is fed.
+    "\
+ALTER TABLE cats ADD COLUMN diet TEXT;
+CREATE TABLE feeding_schedule (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    food TEXT NOT NULL COLLATE NOCASE,
+    amount TEXT NOT NULL,
+    times_per_day INTEGER NOT NULL CHECK (times_per_day > 0),
+    UNIQUE (cat_id, food));
+CREATE TRIGGER feeding_schedule_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM feeding_schedule WHERE cat_id = old.id;
+END",
+    // 28: The cat's license from wherever it's registered, and when it runs out.
+    "\
+ALTER TABLE cats ADD COLUMN license_number TEXT;
+ALTER TABLE cats ADD COLUMN license_expires TEXT;
+CREATE INDEX cats_license_expires ON cats (license_expires) WHERE license_expires NOTNULL",
+    // 29: Documents like vet records and adoption contracts. Unlike photos, big ones can be left
+    // where they are and only linked to.
+    "\
+CREATE TABLE attachments (
+    id INTEGER NOT NULL PRIMARY KEY ASC,
+    cat_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    description TEXT,
+    hash TEXT NOT NULL,
+    path TEXT,
+    data BLOB,
+    added_at TEXT NOT NULL DEFAULT (datetime('now')),
+    CHECK ((path ISNULL) <> (data ISNULL)));
+CREATE INDEX attachments_cat_id ON attachments (cat_id, hash);
+CREATE TRIGGER attachments_purge AFTER DELETE ON cats BEGIN
+    DELETE FROM attachments WHERE cat_id = old.id;
+END",
+];
+
+/// The schema version this build of cats expects.
+pub const LATEST: usize = MIGRATIONS.len();
+
+pub fn version(conn: &Connection) -> Result<usize> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// Makes sure the registry is up to date, without changing it.
+pub fn check(conn: &Connection) -> Result<()> {
+    ensure_latest(version(conn)?)
+}
+
+pub fn ensure_latest(current: usize) -> Result<()> {
+    if pending(current)?.is_empty() {
+        Ok(())
+    } else {
+        bail!("The registry needs to be upgraded, run any command without --read-only first")
+    }
+}
+
+/// The migrations that still need to be applied to a registry at schema version `current`.
+pub fn pending(current: usize) -> Result<&'static [&'static str]> {
+    if current > LATEST {
+        bail!(
+            "The registry uses schema version {}, but this version of cats only supports up to {}",
+            current,
+            LATEST
+        );
+    }
+    Ok(&MIGRATIONS[current..])
+}
+
+/// Applies every pending migration, in order, in a single transaction.
+pub fn migrate(conn: &mut Connection) -> Result<()> {
+    if version(conn)? == LATEST {
+        return Ok(());
+    }
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    // Re-read now that we hold the write lock, in case another process migrated in the meantime.
+    for migration in pending(version(&tx)?)? {
+        tx.execute_batch(migration)?;
+    }
+    tx.execute_batch(&format!("PRAGMA user_version = {}", LATEST))?;
+    tx.commit()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_new_and_unversioned_registries() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), LATEST);
+        check(&conn).unwrap();
+
+        // Registries from before versioning have the original table, and keep their cats.
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute_batch("INSERT INTO cats (name, age) VALUES ('Tom', 3)")
+            .unwrap();
+        assert!(check(&conn).is_err());
+        migrate(&mut conn).unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), LATEST);
+        let name: String = conn
+            .query_row("SELECT name FROM cats", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "Tom");
+    }
+
+    #[test]
+    fn newer_registries_are_refused() {
+        assert!(pending(LATEST).unwrap().is_empty());
+        assert_eq!(pending(LATEST - 1).unwrap().len(), 1);
+        assert!(pending(LATEST + 1).is_err());
+    }
+
+    #[test]
+    fn search_index_follows_cats() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        let found = |conn: &Connection, query: &str| -> Vec<i64> {
+            let mut statement = conn
+                .prepare("SELECT rowid FROM cats_fts WHERE cats_fts MATCH ? ORDER BY rowid")
+                .unwrap();
+            let ids = statement
+                .query_map([query], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            ids
+        };
+        conn.execute_batch(
+            "INSERT INTO cats (name, age, breed) VALUES ('Tom', 3, 'Tabby'), ('Kit', 1, 'Siamese')",
+        )
+        .unwrap();
+        assert_eq!(found(&conn, "tabby"), [1]);
+        conn.execute_batch("UPDATE cats SET breed = 'Tabby' WHERE id = 2")
+            .unwrap();
+        assert_eq!(found(&conn, "tabby"), [1, 2]);
+        assert!(found(&conn, "siamese").is_empty());
+        conn.execute_batch("DELETE FROM cats WHERE id = 1").unwrap();
+        assert_eq!(found(&conn, "tabby"), [2]);
+        assert!(found(&conn, "tom").is_empty());
+        conn.execute_batch("UPDATE cats SET notes = 'Loves tuna' WHERE id = 2")
+            .unwrap();
+        assert_eq!(found(&conn, "notes:tuna"), [2]);
+    }
+}
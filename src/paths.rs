@@ -0,0 +1,19 @@
+use anyhow::{anyhow, Result};
+use directories::ProjectDirs;
+use std::path::PathBuf;
+
+fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("", "", "cats").ok_or_else(|| anyhow!("Could not locate the home directory"))
+}
+
+pub fn default_db() -> Result<PathBuf> {
+    Ok(project_dirs()?.data_dir().join("cat_registry.db"))
+}
+
+pub fn profile_db(name: &str) -> Result<PathBuf> {
+    Ok(project_dirs()?.data_dir().join(format!("{}.db", name)))
+}
+
+pub fn default_config() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("config.toml"))
+}
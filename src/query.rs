@@ -0,0 +1,351 @@
+// The little language behind `find --query`, e.g. `age > 3 AND (breed:tabby OR breed:siamese)`.
+// Queries are parsed along with the rest of the arguments, so mistakes are reported before the
+// registry is even opened. `store::filter` turns the parsed query into SQL.
+
+use crate::args::Age;
+use anyhow::{bail, Context, Error, Result};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub struct Query {
+    /// The query as it was written, which is also how it is saved.
+    source: String,
+    pub expr: Expr,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Name(String),
+    Breed(String),
+    Age(Age),
+    AgeCompare(Comparison, u32),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        if parser.tokens.is_empty() {
+            bail!("The query is empty");
+        }
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {} in the query", token);
+        }
+        Ok(Self {
+            source: s.to_string(),
+            expr,
+        })
+    }
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Query {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(&'static str),
+    Word(String),
+    Quoted(String),
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Quoted(text) => write!(f, "`\"{}\"`", text),
+        }
+    }
+}
+
+/// The characters that end a word, besides whitespace.
+const SPECIAL: &[char] = &['(', ')', '"', ':', '=', '!', '<', '>'];
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut next_is = |expected| chars.next_if(|&(_, c)| c == expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ':' => Token::Op(":"),
+            '=' => Token::Op("="),
+            '!' if next_is('=') => Token::Op("!="),
+            '!' => bail!("Expected `!=` in the query"),
+            '<' if next_is('=') => Token::Op("<="),
+            '<' => Token::Op("<"),
+            '>' if next_is('=') => Token::Op(">="),
+            '>' => Token::Op(">"),
+            '"' => {
+                // A backslash lets a quote or another backslash be part of the text.
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => text.push(c),
+                            None => bail!("Unterminated quote in the query"),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => bail!("Unterminated quote in the query"),
+                    }
+                }
+                Token::Quoted(text)
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|&(_, c)| !c.is_whitespace() && !SPECIAL.contains(&c))
+                {
+                    end = i + c.len_utf8();
+                }
+                Token::Word(s[start..end].to_string())
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, where NOT binds tighter than AND, which binds tighter than OR.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token.is_keyword(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        loop {
+            // Conditions written next to each other are ANDed, as in most search boxes.
+            let implicit = match self.peek() {
+                Some(Token::Open) => true,
+                Some(token @ Token::Word(_)) => !token.is_keyword("or"),
+                _ => false,
+            };
+            if !self.eat_keyword("and") && !implicit {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(token) => bail!("Expected `)` in the query, found {}", token),
+                    None => bail!("Expected `)` at the end of the query"),
+                }
+            }
+            Some(Token::Word(field)) => self.condition(&field),
+            Some(token) => bail!("Expected a condition in the query, found {}", token),
+            None => bail!("Expected a condition at the end of the query"),
+        }
+    }
+
+    fn condition(&mut self, field: &str) -> Result<Expr> {
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(token) => bail!("Expected `:` after `{}`, found {}", field, token),
+            None => bail!("Expected `:` after `{}`", field),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => value,
+            Some(token) => bail!("Expected a value after `{}{}`, found {}", field, op, token),
+            None => bail!("Expected a value after `{}{}`", field, op),
+        };
+        let expr = match &*field.to_ascii_lowercase() {
+            "name" | "breed" if !matches!(op, ":" | "=" | "!=") => {
+                bail!("`{}` only works with age, not {}", op, field)
+            }
+            "name" => Expr::Name(value),
+            "breed" => Expr::Breed(value),
+            "age" => {
+                let age = || {
+                    value
+                        .parse::<u32>()
+                        .with_context(|| format!("Invalid age `{}`", value))
+                };
+                match op {
+                    "<" => Expr::AgeCompare(Comparison::Less, age()?),
+                    "<=" => Expr::AgeCompare(Comparison::LessOrEqual, age()?),
+                    ">" => Expr::AgeCompare(Comparison::Greater, age()?),
+                    ">=" => Expr::AgeCompare(Comparison::GreaterOrEqual, age()?),
+                    _ => Expr::Age(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid age `{}`", value))?,
+                    ),
+                }
+            }
+            _ => bail!("Unknown field `{}`, expected name, age, or breed", field),
+        };
+        Ok(if op == "!=" {
+            Expr::Not(Box::new(expr))
+        } else {
+            expr
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Span;
+    use Expr::*;
+
+    fn parse(query: &str) -> Expr {
+        query.parse::<Query>().unwrap().expr
+    }
+
+    fn error(query: &str) -> String {
+        query.parse::<Query>().unwrap_err().to_string()
+    }
+
+    fn name(name: &str) -> Box<Expr> {
+        Box::new(Name(name.to_string()))
+    }
+
+    fn breed(breed: &str) -> Box<Expr> {
+        Box::new(Breed(breed.to_string()))
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_than_or() {
+        assert_eq!(
+            parse("name:tom OR not name:kit and breed:tabby"),
+            Or(
+                name("tom"),
+                Box::new(And(Box::new(Not(name("kit"))), breed("tabby")))
+            )
+        );
+        assert_eq!(
+            parse("(name:tom OR name:kit) breed:tabby"),
+            And(Box::new(Or(name("tom"), name("kit"))), breed("tabby"))
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(parse("age:3"), Age(Span::Concrete(3)));
+        assert_eq!(parse("AGE=2-5"), Age(Span::Range(2..=5)));
+        assert_eq!(parse("age>=7"), AgeCompare(Comparison::GreaterOrEqual, 7));
+        assert_eq!(parse("age < 1"), AgeCompare(Comparison::Less, 1));
+        assert_eq!(parse("breed!=tabby"), Not(breed("tabby")));
+        assert_eq!(
+            parse(r#"name:"Mr. \"Whiskers\"""#),
+            *name(r#"Mr. "Whiskers""#)
+        );
+    }
+
+    #[test]
+    fn mistakes_are_explained() {
+        assert_eq!(error(""), "The query is empty");
+        assert_eq!(error("name:tom)"), "Unexpected `)` in the query");
+        assert_eq!(error("(name:tom"), "Expected `)` at the end of the query");
+        assert_eq!(error("name tom"), "Expected `:` after `name`, found `tom`");
+        assert_eq!(error("name>tom"), "`>` only works with age, not name");
+        assert_eq!(error("age:old"), "Invalid age `old`");
+        assert_eq!(
+            error("color:black"),
+            "Unknown field `color`, expected name, age, or breed"
+        );
+        assert_eq!(error("name:\"tom"), "Unterminated quote in the query");
+        assert_eq!(error("name!tom"), "Expected `!=` in the query");
+        assert_eq!(
+            error("name:tom and"),
+            "Expected a condition at the end of the query"
+        );
+    }
+
+    #[test]
+    fn saved_as_written() {
+        let query: Query = "age > 3  AND breed:tabby".parse().unwrap();
+        assert_eq!(query.to_string(), "age > 3  AND breed:tabby");
+    }
+}
@@ -0,0 +1,569 @@
+// A registry hosted on a libsql server, such as Turso, reached over its HTTP API.
+// Requests are batched into pipelines on a single stream, which the server identifies by a baton.
+// Keeping the stream open between requests is what lets a transaction span several of them.
+// See https://github.com/tursodatabase/libsql/blob/main/docs/HRANA_3_SPEC.md for the protocol.
+
+use crate::args::{Assignment, CmdAdd, CmdFind, CmdUpdate, Microchip, Weight};
+use crate::cmds::Cat;
+use crate::migrations::{self, LATEST};
+use crate::store::{self, Action, CatStore};
+use crate::timing;
+use anyhow::{anyhow, bail, Context, Result};
+use rusqlite::types::Value;
+use serde_json::{json, Value as Json};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::slice;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct Remote {
+    agent: ureq::Agent,
+    url: RefCell<String>,
+    token: Option<String>,
+    baton: RefCell<Option<String>>,
+}
+
+impl Remote {
+    /// Connects to a registry at a `libsql://`, `https://`, or `http://` URL.
+    ///
+    /// `libsql://` is what Turso hands out, and always means HTTPS.
+    pub fn connect(url: &str, token: Option<String>) -> Result<Self> {
+        let url = match url.strip_prefix("libsql://") {
+            Some(host) => format!("https://{}", host),
+            None => url.to_string(),
+        };
+        Ok(Self {
+            agent: ureq::Agent::new(),
+            url: RefCell::new(url.trim_end_matches('/').to_string()),
+            token,
+            baton: RefCell::new(None),
+        })
+    }
+
+    /// Runs a single statement, returning the rows it produced.
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+        Ok(self.execute_with_columns(sql, params)?.1)
+    }
+
+    /// Runs a single statement, returning the names of its columns and the rows it produced.
+    pub fn execute_with_columns(
+        &self,
+        sql: &str,
+        params: &[Value],
+    ) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        let args = params.iter().map(to_json).collect::<Vec<_>>();
+        let start = Instant::now();
+        let response = self.pipeline(json!({
+            "type": "execute",
+            "stmt": { "sql": sql, "args": args, "want_rows": true },
+        }))?;
+        let columns = response["result"]["cols"]
+            .as_array()
+            .ok_or_else(|| anyhow!("The remote registry sent a malformed response"))?
+            .iter()
+            .map(|column| column["name"].as_str().unwrap_or_default().to_string())
+            .collect();
+        let rows = response["result"]["rows"]
+            .as_array()
+            .ok_or_else(|| anyhow!("The remote registry sent a malformed response"))?
+            .iter()
+            .map(|row| {
+                row.as_array()
+                    .ok_or_else(|| anyhow!("The remote registry sent a malformed response"))?
+                    .iter()
+                    .map(from_json)
+                    .collect()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // The server prepares statements itself, so all of it counts as running them.
+        timing::record(Duration::ZERO, start.elapsed(), rows.len());
+        Ok((columns, rows))
+    }
+
+    /// Runs several statements separated by semicolons, ignoring any rows they produce.
+    pub fn execute_batch(&self, sql: &str) -> Result<()> {
+        self.pipeline(json!({ "type": "sequence", "sql": sql }))?;
+        Ok(())
+    }
+
+    /// Runs `f` inside a transaction, so that either all of its changes are applied or none are.
+    pub fn atomic<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.execute_batch("BEGIN IMMEDIATE")?;
+        match f() {
+            Ok(t) => {
+                self.execute_batch("COMMIT")?;
+                Ok(t)
+            }
+            Err(e) => {
+                // The server rolls back on its own if the stream is gone, so this can fail harmlessly.
+                let _ = self.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    pub fn version(&self) -> Result<usize> {
+        match self
+            .execute("PRAGMA user_version", &[])?
+            .first()
+            .map(|row| &row[..])
+        {
+            Some([Value::Integer(version)]) => Ok(*version as usize),
+            _ => bail!("The remote registry sent a malformed response"),
+        }
+    }
+
+    /// Makes sure the registry is up to date, without changing it.
+    pub fn check(&self) -> Result<()> {
+        migrations::ensure_latest(self.version()?)
+    }
+
+    /// Applies every pending migration, in order, in a single transaction.
+    pub fn migrate(&self) -> Result<()> {
+        if self.version()? == LATEST {
+            return Ok(());
+        }
+        self.atomic(|| {
+            for migration in migrations::pending(self.version()?)? {
+                self.execute_batch(migration)?;
+            }
+            self.execute_batch(&format!("PRAGMA user_version = {}", LATEST))
+        })
+    }
+
+    /// Sends one request on the stream, returning its response.
+    fn pipeline(&self, request: Json) -> Result<Json> {
+        let url = format!("{}/v2/pipeline", self.url.borrow());
+        let mut req = self.agent.post(&url);
+        if let Some(token) = &self.token {
+            req = req.set("Authorization", &format!("Bearer {}", token));
+        }
+        let body = json!({ "baton": *self.baton.borrow(), "requests": [request] });
+        let mut body: Json = match req.send_json(body) {
+            Ok(res) => res.into_json()?,
+            Err(ureq::Error::Status(401, _)) | Err(ureq::Error::Status(403, _)) => {
+                bail!("The remote registry rejected the token, check --token")
+            }
+            Err(e) => return Err(e).context("Couldn't reach the remote registry"),
+        };
+        *self.baton.borrow_mut() = body["baton"].as_str().map(String::from);
+        // The server may ask for the rest of the stream to be sent somewhere else.
+        if let Some(base_url) = body["base_url"].as_str() {
+            *self.url.borrow_mut() = base_url.trim_end_matches('/').to_string();
+        }
+        let mut result = body["results"][0].take();
+        match result["type"].as_str() {
+            Some("ok") => Ok(result["response"].take()),
+            Some("error") => bail!(
+                "The remote registry reported an error: {}",
+                result["error"]["message"]
+                    .as_str()
+                    .unwrap_or("unknown error")
+            ),
+            _ => bail!("The remote registry sent a malformed response"),
+        }
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        // Streams expire on their own, so this is only a courtesy.
+        if self.baton.borrow().is_some() {
+            let _ = self.pipeline(json!({ "type": "close" }));
+        }
+    }
+}
+
+fn to_json(value: &Value) -> Json {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        // Integers are sent as strings, since JSON numbers can't hold all of them.
+        Value::Integer(i) => json!({ "type": "integer", "value": i.to_string() }),
+        Value::Real(f) => json!({ "type": "float", "value": f }),
+        Value::Text(s) => json!({ "type": "text", "value": s }),
+        Value::Blob(b) => json!({ "type": "blob", "base64": base64::encode(b) }),
+    }
+}
+
+fn from_json(value: &Json) -> Result<Value> {
+    Ok(match (value["type"].as_str(), &value["value"]) {
+        (Some("null"), _) => Value::Null,
+        (Some("integer"), Json::String(i)) => Value::Integer(i.parse()?),
+        (Some("float"), Json::Number(f)) => Value::Real(f.as_f64().unwrap_or_default()),
+        (Some("text"), Json::String(s)) => Value::Text(s.clone()),
+        (Some("blob"), _) => match value["base64"].as_str() {
+            Some(b) => Value::Blob(base64::decode(b)?),
+            None => bail!("The remote registry sent a blob without its contents"),
+        },
+        _ => bail!("The remote registry sent a value cats doesn't understand"),
+    })
+}
+
+#[derive(Debug)]
+pub struct RemoteStore<'a> {
+    remote: &'a Remote,
+}
+
+impl<'a> RemoteStore<'a> {
+    pub fn new(remote: &'a Remote) -> Self {
+        Self { remote }
+    }
+
+    fn cats(&self, sql: &str, params: &[Value]) -> Result<Vec<Cat>> {
+        let (columns, rows) = self.remote.execute_with_columns(sql, params)?;
+        rows.into_iter()
+            .map(|row| cat(columns.iter().map(String::as_str).zip(row).collect()))
+            .collect()
+    }
+
+    fn record(&self, action: Action, old: Option<&Cat>, new: Option<&Cat>) -> Result<()> {
+        let cat_id = old.or(new).map(|cat| cat.id as i64);
+        let snapshot = |cat: Option<&Cat>| -> Result<Value> {
+            Ok(match cat {
+                Some(cat) => Value::Text(serde_json::to_string(cat)?),
+                None => Value::Null,
+            })
+        };
+        self.remote.execute(
+            "INSERT INTO cat_history (cat_id, action, old, new) VALUES (?, ?, ?, ?)",
+            &[
+                cat_id.into(),
+                Value::Text(action.as_str().to_string()),
+                snapshot(old)?,
+                snapshot(new)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn check_microchip(&self, microchip: Option<&Microchip>, id: Option<u64>) -> Result<()> {
+        let microchip = match microchip {
+            Some(microchip) => microchip.as_str(),
+            None => return Ok(()),
+        };
+        let owner = self.remote.execute(
+            store::MICROCHIP_OWNER,
+            &[
+                Value::Text(microchip.to_string()),
+                id.map(|id| id as i64).into(),
+            ],
+        )?;
+        match owner.first().map(|row| &row[..]) {
+            None => Ok(()),
+            Some([Value::Integer(owner), Value::Integer(trashed)]) => Err(store::microchip_taken(
+                microchip,
+                *owner as u64,
+                *trashed != 0,
+            )),
+            Some(_) => bail!("The remote registry sent a malformed response"),
+        }
+    }
+
+    /// The type of a custom field, as it's stored.
+    fn field_type(&self, field: &str) -> Result<String> {
+        let rows = self
+            .remote
+            .execute(store::FIELD_TYPE, &[Value::Text(field.to_string())])?;
+        match rows.first().map(|row| &row[..]) {
+            None => Err(store::no_such_field(field)),
+            Some([Value::Text(field_type)]) => Ok(field_type.clone()),
+            Some(_) => bail!("The remote registry sent a malformed response"),
+        }
+    }
+
+    /// Sets and clears custom fields on a cat, each of which must have been defined.
+    fn set_fields(&self, id: u64, set: &[Assignment], unset: &[String]) -> Result<()> {
+        for Assignment { field, value } in set {
+            let value = store::field_value(field, self.field_type(field)?.parse()?, value)?;
+            self.remote.execute(
+                store::SET_FIELD,
+                &[Value::Integer(id as i64), Value::Text(field.clone()), value],
+            )?;
+        }
+        for field in unset {
+            self.field_type(field)?;
+            self.remote.execute(
+                store::UNSET_FIELD,
+                &[Value::Integer(id as i64), Value::Text(field.clone())],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Fills in the custom fields of each cat.
+    fn load_fields(&self, cats: &mut [Cat]) -> Result<()> {
+        let ids = serde_json::to_string(&cats.iter().map(|cat| cat.id).collect::<Vec<_>>())?;
+        let mut fields = HashMap::<u64, BTreeMap<String, Json>>::new();
+        for row in self.remote.execute(store::FIELDS, &[Value::Text(ids)])? {
+            match <[Value; 4]>::try_from(row) {
+                Ok([Value::Integer(id), Value::Text(field), Value::Text(field_type), value]) => {
+                    let value = store::field_json(&field_type, value);
+                    fields.entry(id as u64).or_default().insert(field, value);
+                }
+                _ => bail!("The remote registry sent a malformed response"),
+            }
+        }
+        for cat in cats {
+            cat.fields = fields.remove(&cat.id).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    fn record_weight(&self, id: u64, weight: Weight) -> Result<()> {
+        self.remote.execute(
+            store::RECORD_WEIGHT,
+            &[
+                Value::Integer(id as i64),
+                Value::Integer(weight.grams.into()),
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl CatStore for RemoteStore<'_> {
+    fn add(&self, cmd: &CmdAdd) -> Result<Cat> {
+        self.remote.atomic(|| {
+            self.check_microchip(cmd.microchip.as_ref(), None)?;
+            let cat = self
+                .cats(
+                    "INSERT INTO cats (name, age, breed, birthdate, weight, sex, fixed, color, \
+                    pattern, microchip, notes, diet, license_number, license_expires, status, \
+                    created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+                    datetime('now'), datetime('now')) RETURNING *",
+                    &[
+                        cmd.name.clone().into(),
+                        Value::Integer(cmd.current_age().into()),
+                        cmd.breed.clone().into(),
+                        cmd.birthdate.map(|birthdate| birthdate.to_string()).into(),
+                        cmd.weight.map(|weight| weight.grams).into(),
+                        Value::Text(cmd.sex.as_str().to_string()),
+                        Value::Integer(cmd.fixed.into()),
+                        cmd.color.clone().into(),
+                        cmd.pattern.clone().into(),
+                        cmd.microchip.as_ref().map(Microchip::to_string).into(),
+                        cmd.notes.clone().into(),
+                        cmd.diet.clone().into(),
+                        cmd.license_number.clone().into(),
+                        cmd.license_expires
+                            .map(|expires| expires.to_string())
+                            .into(),
+                        Value::Text(cmd.status.as_str().to_string()),
+                    ],
+                )?
+                .pop()
+                .ok_or_else(|| anyhow!("The remote registry sent a malformed response"))?;
+            self.record(Action::Add, None, Some(&cat))?;
+            if let Some(weight) = cmd.weight {
+                self.record_weight(cat.id, weight)?;
+            }
+            self.set_fields(cat.id, &cmd.set, &[])?;
+            let mut cat = cat;
+            self.load_fields(slice::from_mut(&mut cat))?;
+            Ok(cat)
+        })
+    }
+
+    fn get(&self, ids: &[u64]) -> Result<Vec<Cat>> {
+        let stmt = format!(
+            "SELECT * FROM {} WHERE deleted_at ISNULL AND id IN ({})",
+            store::CATS_WITH_DETAILS,
+            vec!["?"; ids.len()].join(", ")
+        );
+        let params = ids
+            .iter()
+            .map(|&id| Value::Integer(id as i64))
+            .collect::<Vec<_>>();
+        let mut cats = self.cats(&stmt, &params)?;
+        self.load_fields(&mut cats)?;
+        Ok(cats)
+    }
+
+    fn find(&self, cmd: &CmdFind) -> Result<Vec<Cat>> {
+        store::check_conditions(cmd, |field| self.field_type(field))?;
+        let (stmt, params) = store::find_stmt(cmd);
+        let mut cats = self.cats(&stmt, &params)?;
+        self.load_fields(&mut cats)?;
+        Ok(cats)
+    }
+
+    fn count(&self, cmd: &CmdFind) -> Result<u64> {
+        store::check_conditions(cmd, |field| self.field_type(field))?;
+        let (clauses, params) = store::filter(cmd);
+        let stmt = format!("SELECT COUNT(*) FROM cats WHERE {}", clauses);
+        match self
+            .remote
+            .execute(&stmt, &params)?
+            .first()
+            .map(|row| &row[..])
+        {
+            Some([Value::Integer(count)]) => Ok(*count as u64),
+            _ => bail!("The remote registry sent a malformed response"),
+        }
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Cat>> {
+        let mut cats = self
+            .cats(store::SEARCH, &[Value::Text(query.to_string())])
+            .with_context(|| format!("Invalid search query `{}`", query))?;
+        self.load_fields(&mut cats)?;
+        Ok(cats)
+    }
+
+    fn update(&self, id: u64, cmd: &CmdUpdate) -> Result<Option<Cat>> {
+        let (stmt, params) = store::update_stmt(id, cmd);
+        self.remote.atomic(|| {
+            let old = self
+                .cats(
+                    "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    &[Value::Integer(id as i64)],
+                )?
+                .pop();
+            let old = match old {
+                Some(old) => old,
+                None => return Ok(None),
+            };
+            self.check_microchip(cmd.microchip.as_ref(), Some(id))?;
+            let mut new = self.cats(&stmt, &params)?.pop();
+            if let Some(new) = &mut new {
+                self.record(Action::Update, Some(&old), Some(new))?;
+                if let Some(weight) = cmd.weight {
+                    self.record_weight(new.id, weight)?;
+                }
+                self.set_fields(new.id, &cmd.set, &cmd.unset)?;
+                self.load_fields(slice::from_mut(new))?;
+            }
+            Ok(new)
+        })
+    }
+
+    fn delete(&self, id: u64, purge: bool) -> Result<Option<Cat>> {
+        let (stmt, action) = if purge {
+            ("DELETE FROM cats WHERE id = ? RETURNING *", Action::Purge)
+        } else {
+            (
+                "UPDATE cats SET deleted_at = datetime('now') WHERE id = ? AND deleted_at ISNULL RETURNING *",
+                Action::Delete,
+            )
+        };
+        self.remote.atomic(|| {
+            let cat = self.cats(stmt, &[Value::Integer(id as i64)])?.pop();
+            if let Some(cat) = &cat {
+                self.record(action, Some(cat), None)?;
+            }
+            Ok(cat)
+        })
+    }
+}
+
+/// Reads a row of `cats` by column name, like `Cat::from_row`, along with any details joined on.
+fn cat(mut row: HashMap<&str, Value>) -> Result<Cat> {
+    let mut take = |column: &str| row.remove(column).unwrap_or(Value::Null);
+    let text = |value: Value| match value {
+        Value::Text(text) => Some(text),
+        _ => None,
+    };
+    let integer = |value: Value| match value {
+        Value::Integer(integer) => Some(integer),
+        _ => None,
+    };
+    let flag = |value: Value| integer(value).map(|known| known != 0);
+    let invalid = || anyhow!("The remote registry sent a cat cats doesn't understand");
+    let (id, name, age) = match (take("id"), take("name"), take("age")) {
+        (Value::Integer(id), Value::Text(name), Value::Integer(age)) => (id, name, age),
+        _ => return Err(invalid()),
+    };
+    let birthdate = text(take("birthdate"));
+    Ok(Cat {
+        id: id as u64,
+        name,
+        age: Cat::current_age(age as u32, birthdate.as_deref()),
+        breed: text(take("breed")),
+        birthdate,
+        weight: integer(take("weight")).map(|grams| grams as u32),
+        sex: text(take("sex")).ok_or_else(invalid)?.parse()?,
+        fixed: flag(take("fixed")) == Some(true),
+        color: text(take("color")),
+        pattern: text(take("pattern")),
+        microchip: text(take("microchip")),
+        notes: text(take("notes")),
+        owner_id: integer(take("owner_id")).map(|id| id as u64),
+        mother_id: integer(take("mother_id")).map(|id| id as u64),
+        father_id: integer(take("father_id")).map(|id| id as u64),
+        litter_id: integer(take("litter_id")).map(|id| id as u64),
+        status: text(take("status")).ok_or_else(invalid)?.parse()?,
+        adopted_at: text(take("adopted_at")),
+        deceased_at: text(take("deceased_at")),
+        location_id: integer(take("location_id")).map(|id| id as u64),
+        good_with_dogs: flag(take("good_with_dogs")),
+        good_with_kids: flag(take("good_with_kids")),
+        energy: integer(take("energy")).map(|energy| energy as u8),
+        litter_trained: flag(take("litter_trained")),
+        diet: text(take("diet")),
+        license_number: text(take("license_number")),
+        license_expires: text(take("license_expires")),
+        owner: text(take("owner_name")),
+        bonded_with: match text(take("bonded_with")) {
+            Some(bonded_with) => serde_json::from_str(&bonded_with)?,
+            None => Vec::new(),
+        },
+        location: text(take("location_name")),
+        fields: BTreeMap::new(),
+        created_at: text(take("created_at")),
+        updated_at: text(take("updated_at")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn cats_are_read_the_same_as_locally() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        store::add_functions(&conn).unwrap();
+        migrations::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO owners (name) VALUES ('Ann');
+            INSERT INTO cats (name, age, breed, sex, fixed, owner_id, notes, energy, created_at)
+                VALUES ('Tom', 3, 'Manx', 'male', 1, 1, 'Shy', 4, datetime('now'));
+            INSERT INTO cats (name, age, sex) VALUES ('Rex', 2, 'female');
+            INSERT INTO bonds VALUES (1, 2)",
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT * FROM {} WHERE id = 1",
+                store::CATS_WITH_DETAILS
+            ))
+            .unwrap();
+        let columns = stmt
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let local = stmt.query_row([], Cat::from_row).unwrap();
+        let row = stmt
+            .query_row([], |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, Value>(i))
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .unwrap();
+        let remote = cat(columns.iter().map(String::as_str).zip(row).collect()).unwrap();
+        assert_eq!(remote, local);
+    }
+
+    #[test]
+    fn blobs_are_sent_as_base64() {
+        let blob = Value::Blob(vec![0, 1, 2, 255]);
+        assert_eq!(
+            to_json(&blob),
+            json!({ "type": "blob", "base64": "AAEC/w==" })
+        );
+        assert_eq!(from_json(&to_json(&blob)).unwrap(), blob);
+    }
+}
@@ -0,0 +1,1433 @@
+// Everything that talks to the database on behalf of the basic commands goes through `CatStore`,
+// so that the command layer can be tested against a mock and other backends can be plugged in.
+// Commands that only make sense for SQLite, like `backup`, still take a `Connection` directly.
+
+use crate::args::{
+    Age, Assignment, CmdAdd, CmdFind, CmdUpdate, Condition, Date, FieldType, MatchMode, Microchip,
+    SortField, Span, Weight,
+};
+use crate::cmds::Cat;
+use crate::query::{Comparison, Expr};
+use crate::timing;
+use anyhow::{anyhow, bail, Context, Error, Result};
+use itertools::Itertools;
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection, ErrorCode, OptionalExtension};
+use serde_json::Value as Json;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as StdError;
+use std::mem;
+use std::slice;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+pub trait CatStore {
+    /// Adds a new cat, returning it along with its new ID.
+    fn add(&self, cmd: &CmdAdd) -> Result<Cat>;
+    /// Gets the cats with the given IDs. IDs that don't exist are skipped.
+    fn get(&self, ids: &[u64]) -> Result<Vec<Cat>>;
+    /// Gets every cat matching the search.
+    fn find(&self, cmd: &CmdFind) -> Result<Vec<Cat>>;
+    /// Counts the cats matching the search.
+    fn count(&self, cmd: &CmdFind) -> Result<u64>;
+    /// Gets every cat whose name or breed matches a full-text query, best matches first.
+    fn search(&self, query: &str) -> Result<Vec<Cat>>;
+    /// Changes a cat, returning its new state, or `None` if it doesn't exist.
+    fn update(&self, id: u64, cmd: &CmdUpdate) -> Result<Option<Cat>>;
+    /// Moves a cat to the trash, or removes it completely if `purge` is set.
+    fn delete(&self, id: u64, purge: bool) -> Result<Option<Cat>>;
+}
+
+#[derive(Debug)]
+pub struct SqliteStore<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> SqliteStore<'a> {
+    pub fn new(conn: &'a Connection) -> Self {
+        Self { conn }
+    }
+}
+
+impl CatStore for SqliteStore<'_> {
+    fn add(&self, cmd: &CmdAdd) -> Result<Cat> {
+        let conn = self.conn;
+        atomic(conn, || {
+            check_microchip(conn, cmd.microchip.as_ref().map(Microchip::as_str), None)?;
+            // The statement is cached, since `add --from` runs it once for every cat.
+            let cat = conn
+                .prepare_cached(
+                    "INSERT INTO cats (name, age, breed, birthdate, weight, sex, fixed, color, \
+                pattern, microchip, notes, diet, license_number, license_expires, status, \
+                created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+                datetime('now'), datetime('now')) RETURNING *",
+                )?
+                .query_row(
+                    params![
+                        cmd.name,
+                        cmd.current_age(),
+                        cmd.breed,
+                        cmd.birthdate.map(|birthdate| birthdate.to_string()),
+                        cmd.weight.map(|weight| weight.grams),
+                        cmd.sex.as_str(),
+                        cmd.fixed,
+                        cmd.color,
+                        cmd.pattern,
+                        cmd.microchip.as_ref().map(Microchip::as_str),
+                        cmd.notes,
+                        cmd.diet,
+                        cmd.license_number,
+                        cmd.license_expires.map(|expires| expires.to_string()),
+                        cmd.status.as_str()
+                    ],
+                    Cat::from_row,
+                )?;
+            record(conn, Action::Add, None, Some(&cat))?;
+            if let Some(weight) = cmd.weight {
+                record_weight(conn, cat.id, weight)?;
+            }
+            set_fields(conn, cat.id, &cmd.set, &[])?;
+            let mut cat = cat;
+            load_fields(conn, slice::from_mut(&mut cat))?;
+            Ok(cat)
+        })
+    }
+
+    fn get(&self, ids: &[u64]) -> Result<Vec<Cat>> {
+        let mut stmt = format!(
+            "SELECT * FROM {} WHERE deleted_at ISNULL AND (",
+            CATS_WITH_DETAILS
+        );
+        stmt.push_str(&ids.iter().map(|_| "id = ?").join(" OR "));
+        stmt.push(')');
+        let mut cats = timing::query(
+            || self.conn.prepare(&stmt),
+            |mut stmt| {
+                stmt.query_map(params_from_iter(ids), Cat::from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            },
+        )?;
+        load_fields(self.conn, &mut cats)?;
+        Ok(cats)
+    }
+
+    fn find(&self, cmd: &CmdFind) -> Result<Vec<Cat>> {
+        check_conditions(cmd, |field| field_type(self.conn, field))?;
+        let (stmt, params) = find_stmt(cmd);
+        let mut cats = timing::query(
+            || self.conn.prepare(&stmt),
+            |mut stmt| {
+                stmt.query_map(params_from_iter(params), Cat::from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            },
+        )?;
+        load_fields(self.conn, &mut cats)?;
+        Ok(cats)
+    }
+
+    fn count(&self, cmd: &CmdFind) -> Result<u64> {
+        check_conditions(cmd, |field| field_type(self.conn, field))?;
+        let (stmt, params) = count_stmt(cmd);
+        let count = timing::query(
+            || self.conn.prepare(&stmt),
+            |mut stmt| {
+                stmt.query_row(params_from_iter(params), |row| row.get(0))
+                    .map(|count| vec![count])
+            },
+        )?;
+        Ok(count[0])
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Cat>> {
+        let mut cats = timing::query(
+            || self.conn.prepare(SEARCH),
+            |mut stmt| {
+                stmt.query_map([query], Cat::from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            },
+        )
+        .with_context(|| format!("Invalid search query `{}`", query))?;
+        load_fields(self.conn, &mut cats)?;
+        Ok(cats)
+    }
+
+    fn update(&self, id: u64, cmd: &CmdUpdate) -> Result<Option<Cat>> {
+        let conn = self.conn;
+        let (stmt, params) = update_stmt(id, cmd);
+        atomic(conn, || {
+            let old = conn
+                .query_row(
+                    "SELECT * FROM cats WHERE id = ? AND deleted_at ISNULL",
+                    [id],
+                    Cat::from_row,
+                )
+                .optional()?;
+            let old = match old {
+                Some(old) => old,
+                None => return Ok(None),
+            };
+            let microchip = cmd.microchip.as_ref().map(Microchip::as_str);
+            check_microchip(conn, microchip, Some(id))?;
+            let mut new = conn.query_row(&stmt, params_from_iter(&params), Cat::from_row)?;
+            record(conn, Action::Update, Some(&old), Some(&new))?;
+            if let Some(weight) = cmd.weight {
+                record_weight(conn, id, weight)?;
+            }
+            set_fields(conn, id, &cmd.set, &cmd.unset)?;
+            load_fields(conn, slice::from_mut(&mut new))?;
+            Ok(Some(new))
+        })
+    }
+
+    fn delete(&self, id: u64, purge: bool) -> Result<Option<Cat>> {
+        let conn = self.conn;
+        let (stmt, action) = if purge {
+            ("DELETE FROM cats WHERE id = ? RETURNING *", Action::Purge)
+        } else {
+            (
+                "UPDATE cats SET deleted_at = datetime('now') WHERE id = ? AND deleted_at ISNULL RETURNING *",
+                Action::Delete,
+            )
+        };
+        atomic(conn, || {
+            let cat = conn.query_row(stmt, [id], Cat::from_row).optional()?;
+            if let Some(cat) = &cat {
+                record(conn, action, Some(cat), None)?;
+            }
+            Ok(cat)
+        })
+    }
+}
+
+/// A cat's age in years, counted from its birthdate if it has one.
+///
+/// This must agree with `Cat::current_age`, which does the same for cats once they are read.
+pub const AGE: &str = "ifnull(strftime('%Y', 'now') - strftime('%Y', birthdate) \
+    - (strftime('%m-%d', 'now') < strftime('%m-%d', birthdate)), age)";
+
+/// `cats` with the name of each cat's owner joined on, as `owner_name`, the IDs of the cats it is
+/// bonded with as a JSON array, as `bonded_with`, and the name of its location, as `location_name`.
+///
+/// The joined columns are renamed, so the columns of `cats` can still be used unqualified.
+pub const CATS_WITH_DETAILS: &str = "cats LEFT JOIN \
+    (SELECT id AS owner_key, name AS owner_name FROM owners) ON owner_key = owner_id LEFT JOIN \
+    (SELECT bond_key, json_group_array(other) AS bonded_with FROM \
+        (SELECT cat_id AS bond_key, other_id AS other FROM bonds \
+        UNION ALL SELECT other_id, cat_id FROM bonds) \
+    JOIN cats ON cats.id = other AND deleted_at ISNULL GROUP BY bond_key) ON bond_key = cats.id \
+    LEFT JOIN (SELECT id AS location_key, name AS location_name FROM locations) \
+    ON location_key = location_id";
+
+pub const SEARCH: &str = "SELECT cats.* FROM cats_fts JOIN cats ON cats.id = cats_fts.rowid \
+    WHERE cats_fts MATCH ? AND cats.deleted_at ISNULL ORDER BY cats_fts.rank, cats.id";
+
+/// Adds a weight to a cat's history, as of today.
+pub const RECORD_WEIGHT: &str =
+    "INSERT INTO weights (cat_id, date, weight) VALUES (?, date('now'), ?)";
+
+fn record_weight(conn: &Connection, id: u64, weight: Weight) -> Result<()> {
+    conn.prepare_cached(RECORD_WEIGHT)?
+        .execute(params![id, weight.grams])?;
+    Ok(())
+}
+
+/// Finds the cat other than the given ID that has a microchip, and whether it is in the trash.
+pub const MICROCHIP_OWNER: &str =
+    "SELECT id, deleted_at NOTNULL FROM cats WHERE microchip = ? AND id IS NOT ?";
+
+/// The error for giving a cat a microchip another cat already has.
+pub fn microchip_taken(microchip: &str, owner: u64, trashed: bool) -> Error {
+    if trashed {
+        anyhow!(
+            "Microchip {} already belongs to cat #{}, which is in the trash",
+            microchip,
+            owner
+        )
+    } else {
+        anyhow!("Microchip {} already belongs to cat #{}", microchip, owner)
+    }
+}
+
+/// Makes sure no other cat has `microchip`, which the unique index would refuse less helpfully.
+fn check_microchip(conn: &Connection, microchip: Option<&str>, id: Option<u64>) -> Result<()> {
+    let microchip = match microchip {
+        Some(microchip) => microchip,
+        None => return Ok(()),
+    };
+    let owner = conn
+        .prepare_cached(MICROCHIP_OWNER)?
+        .query_row(params![microchip, id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()?;
+    match owner {
+        Some((owner, trashed)) => Err(microchip_taken(microchip, owner, trashed)),
+        None => Ok(()),
+    }
+}
+
+/// Looks up the type of a custom field.
+pub const FIELD_TYPE: &str = "SELECT type FROM fields WHERE name = ?";
+
+pub const SET_FIELD: &str =
+    "INSERT OR REPLACE INTO attributes (cat_id, field, value) VALUES (?, ?, ?)";
+
+pub const UNSET_FIELD: &str = "DELETE FROM attributes WHERE cat_id = ? AND field = ?";
+
+/// Every custom field set on the cats whose IDs are in a JSON array, along with its type.
+pub const FIELDS: &str = "SELECT cat_id, field, type, attributes.value FROM attributes \
+    JOIN fields ON fields.name = attributes.field \
+    WHERE cat_id IN (SELECT value FROM json_each(?)) ORDER BY field";
+
+pub fn no_such_field(field: &str) -> Error {
+    anyhow!(
+        "There is no field named `{}`, add one with `cats field define`",
+        field
+    )
+}
+
+/// Converts a value given for a custom field to the way it is stored.
+pub fn field_value(field: &str, field_type: FieldType, value: &str) -> Result<Value> {
+    let invalid = || {
+        anyhow!(
+            "Invalid value `{}` for the {} field `{}`",
+            value,
+            field_type,
+            field
+        )
+    };
+    Ok(match field_type {
+        FieldType::Text => Value::Text(value.to_string()),
+        FieldType::Int => Value::Integer(value.parse().map_err(|_| invalid())?),
+        FieldType::Number => match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => Value::Real(number),
+            _ => return Err(invalid()),
+        },
+        FieldType::Bool => match &*value.to_ascii_lowercase() {
+            "true" | "yes" => Value::Integer(1),
+            "false" | "no" => Value::Integer(0),
+            _ => return Err(invalid()),
+        },
+        FieldType::Date => Value::Text(value.parse::<Date>().map_err(|_| invalid())?.to_string()),
+    })
+}
+
+/// Converts a stored value of a custom field to the way it is shown.
+pub fn field_json(field_type: &str, value: Value) -> Json {
+    match (field_type, value) {
+        ("bool", Value::Integer(value)) => Json::Bool(value != 0),
+        (_, Value::Integer(value)) => value.into(),
+        (_, Value::Real(value)) => value.into(),
+        (_, Value::Text(value)) => value.into(),
+        _ => Json::Null,
+    }
+}
+
+/// The type of a custom field, as it's stored.
+fn field_type(conn: &Connection, field: &str) -> Result<String> {
+    conn.prepare_cached(FIELD_TYPE)?
+        .query_row([field], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| no_such_field(field))
+}
+
+/// Sets and clears custom fields on a cat, each of which must have been defined.
+fn set_fields(conn: &Connection, id: u64, set: &[Assignment], unset: &[String]) -> Result<()> {
+    let field_type = |field: &str| field_type(conn, field);
+    for Assignment { field, value } in set {
+        let value = field_value(field, field_type(field)?.parse()?, value)?;
+        conn.prepare_cached(SET_FIELD)?
+            .execute(params![id, field, value])?;
+    }
+    for field in unset {
+        field_type(field)?;
+        conn.prepare_cached(UNSET_FIELD)?
+            .execute(params![id, field])?;
+    }
+    Ok(())
+}
+
+/// Fills in the custom fields of each cat.
+fn load_fields(conn: &Connection, cats: &mut [Cat]) -> Result<()> {
+    let ids = serde_json::to_string(&cats.iter().map(|cat| cat.id).collect::<Vec<_>>())?;
+    let mut fields = HashMap::<u64, BTreeMap<String, Json>>::new();
+    let mut stmt = conn.prepare_cached(FIELDS)?;
+    let mut rows = stmt.query([ids])?;
+    while let Some(row) = rows.next()? {
+        let value = field_json(&row.get::<_, String>(2)?, row.get(3)?);
+        fields
+            .entry(row.get(0)?)
+            .or_default()
+            .insert(row.get(1)?, value);
+    }
+    for cat in cats {
+        cat.fields = fields.remove(&cat.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Builds the `UPDATE` statement for a change to a cat, along with the parameters it uses.
+pub fn update_stmt(id: u64, cmd: &CmdUpdate) -> (String, Vec<Value>) {
+    let mut params = Vec::new();
+    let name_clause = cmd.name.as_ref().map(|name| {
+        params.push(Value::Text(name.clone()));
+        "name = ?"
+    });
+    // The age column is still kept for cats with a birthdate, as of when it was set.
+    let age_clause = match (cmd.age, cmd.birthdate) {
+        (Some(age), _) => {
+            params.push(Value::Integer(age.into()));
+            Some("age = ?, birthdate = NULL")
+        }
+        (None, Some(birthdate)) => {
+            params.push(Value::Integer(birthdate.years_until(Date::today()).into()));
+            params.push(Value::Text(birthdate.to_string()));
+            Some("age = ?, birthdate = ?")
+        }
+        (None, None) => None,
+    };
+    let breed_clause = cmd.breed.as_ref().map(|breed| {
+        params.push(Value::Text(breed.clone()));
+        "breed = ?"
+    });
+    let weight_clause = cmd.weight.map(|weight| {
+        params.push(Value::Integer(weight.grams.into()));
+        "weight = ?"
+    });
+    let sex_clause = cmd.sex.map(|sex| {
+        params.push(Value::Text(sex.as_str().to_string()));
+        "sex = ?"
+    });
+    let fixed_clause = match (cmd.fixed, cmd.not_fixed) {
+        (true, _) => Some("fixed = 1"),
+        (_, true) => Some("fixed = 0"),
+        _ => None,
+    };
+    let good_with_dogs_clause = match (cmd.good_with_dogs, cmd.not_good_with_dogs) {
+        (true, _) => Some("good_with_dogs = 1"),
+        (_, true) => Some("good_with_dogs = 0"),
+        _ => None,
+    };
+    let good_with_kids_clause = match (cmd.good_with_kids, cmd.not_good_with_kids) {
+        (true, _) => Some("good_with_kids = 1"),
+        (_, true) => Some("good_with_kids = 0"),
+        _ => None,
+    };
+    let energy_clause = cmd.energy.map(|energy| {
+        params.push(Value::Integer(energy.into()));
+        "energy = ?"
+    });
+    let litter_trained_clause = match (cmd.litter_trained, cmd.not_litter_trained) {
+        (true, _) => Some("litter_trained = 1"),
+        (_, true) => Some("litter_trained = 0"),
+        _ => None,
+    };
+    let color_clause = cmd.color.as_ref().map(|color| {
+        params.push(Value::Text(color.clone()));
+        "color = ?"
+    });
+    let pattern_clause = cmd.pattern.as_ref().map(|pattern| {
+        params.push(Value::Text(pattern.clone()));
+        "pattern = ?"
+    });
+    let microchip_clause = cmd.microchip.as_ref().map(|microchip| {
+        params.push(Value::Text(microchip.to_string()));
+        "microchip = ?"
+    });
+    let notes_clause = match (&cmd.notes, &cmd.append_notes) {
+        (Some(notes), _) => {
+            params.push(Value::Text(notes.clone()));
+            Some("notes = ?")
+        }
+        // e.g. `2024-03-02: Allergic to chicken`, on a line of its own.
+        (None, Some(line)) => {
+            params.push(Value::Text(format!("{}: {}", Date::today(), line)));
+            Some("notes = ifnull(notes || char(10), '') || ?")
+        }
+        (None, None) => None,
+    };
+    let diet_clause = cmd.diet.as_ref().map(|diet| {
+        params.push(Value::Text(diet.clone()));
+        "diet = ?"
+    });
+    let license_number_clause = cmd.license_number.as_ref().map(|number| {
+        params.push(Value::Text(number.clone()));
+        "license_number = ?"
+    });
+    let license_expires_clause = cmd.license_expires.map(|expires| {
+        params.push(Value::Text(expires.to_string()));
+        "license_expires = ?"
+    });
+    let status_clause = cmd.status.map(|status| {
+        params.push(Value::Text(status.as_str().to_string()));
+        "status = ?"
+    });
+    let stmt = format!(
+        "UPDATE cats SET {} WHERE id = ? AND deleted_at ISNULL RETURNING *",
+        [
+            name_clause,
+            age_clause,
+            breed_clause,
+            weight_clause,
+            sex_clause,
+            fixed_clause,
+            good_with_dogs_clause,
+            good_with_kids_clause,
+            energy_clause,
+            litter_trained_clause,
+            color_clause,
+            pattern_clause,
+            microchip_clause,
+            notes_clause,
+            diet_clause,
+            license_number_clause,
+            license_expires_clause,
+            status_clause,
+        ]
+        .iter()
+        .flatten()
+        .chain(&["updated_at = datetime('now')"])
+        .join(", ")
+    );
+    params.push(Value::Integer(id as i64));
+    (stmt, params)
+}
+
+/// Checks that every `--where` condition is on a field that exists, with a value of its type, since
+/// SQL would otherwise quietly match nothing or compare against whatever the value casts to.
+pub fn check_conditions(cmd: &CmdFind, field_type: impl Fn(&str) -> Result<String>) -> Result<()> {
+    for condition in cmd.conditions.iter().flatten() {
+        let field_type = field_type(&condition.field)?.parse()?;
+        field_value(&condition.field, field_type, &condition.value)?;
+    }
+    Ok(())
+}
+
+/// Builds the clause for a condition on a custom field.
+///
+/// Which type to compare the value as depends on the field, so the conversion happens in SQL.
+fn condition_clause(condition: &Condition, params: &mut Vec<Value>) -> String {
+    params.push(Value::Text(condition.value.clone()));
+    params.push(Value::Text(condition.field.clone()));
+    format!(
+        "EXISTS (SELECT 1 FROM attributes JOIN fields ON fields.name = attributes.field, \
+        (SELECT ? AS operand) WHERE attributes.cat_id = cats.id AND attributes.field = ? \
+        AND attributes.value {} CASE fields.type WHEN 'int' THEN CAST(operand AS INTEGER) \
+        WHEN 'number' THEN CAST(operand AS REAL) WHEN 'bool' THEN lower(operand) IN ('true', 'yes') \
+        ELSE operand END)",
+        condition.operator.as_str()
+    )
+}
+
+/// Builds the `SELECT` statement for a search, along with the parameters it uses.
+pub fn find_stmt(cmd: &CmdFind) -> (String, Vec<Value>) {
+    let (clauses, mut params) = filter(cmd);
+    let stmt = format!(
+        "SELECT * FROM {} WHERE {} {}{}",
+        CATS_WITH_DETAILS,
+        clauses,
+        order(cmd),
+        limit(cmd, &mut params)
+    );
+    (stmt, params)
+}
+
+/// Builds the statement that counts the results of a search, along with the parameters it uses.
+pub fn count_stmt(cmd: &CmdFind) -> (String, Vec<Value>) {
+    let (clauses, params) = filter(cmd);
+    (
+        format!("SELECT COUNT(*) FROM cats WHERE {}", clauses),
+        params,
+    )
+}
+
+/// Every column of free text about a cat, all of which `--text` searches.
+const TEXT_COLUMNS: &[&str] = &["name", "breed", "color", "pattern", "notes", "diet"];
+
+/// Builds the `WHERE` clause for a search, along with the parameters it uses.
+///
+/// Only column names and placeholders go into the clause; every user-supplied value is a parameter.
+pub fn filter(cmd: &CmdFind) -> (String, Vec<Value>) {
+    let mut matches = Vec::new();
+    let mut params = Vec::new();
+    if let Some(ids) = &cmd.id {
+        matches.push(span_clause("id", ids, &mut params));
+    }
+    if let Some(names) = &cmd.name {
+        matches.push(text_clause(
+            "name",
+            names,
+            cmd,
+            MatchMode::Exact,
+            &mut params,
+        ));
+    }
+    if let Some(ages) = &cmd.age {
+        matches.push(age_clause(ages, &mut params));
+    }
+    if let Some(weights) = &cmd.weight {
+        matches.push(span_clause("weight", weights, &mut params));
+    }
+    if let Some(breeds) = &cmd.breed {
+        matches.push(text_clause(
+            "breed",
+            breeds,
+            cmd,
+            MatchMode::Exact,
+            &mut params,
+        ));
+    }
+    if cmd.no_breed {
+        matches.push("breed ISNULL".to_string());
+    }
+    if let Some(sexes) = &cmd.sex {
+        params.extend(
+            sexes
+                .iter()
+                .map(|sex| Value::Text(sex.as_str().to_string())),
+        );
+        matches.push(format!("sex IN ({})", vec!["?"; sexes.len()].join(", ")));
+    }
+    if cmd.fixed {
+        matches.push("fixed".to_string());
+    }
+    if cmd.good_with_dogs {
+        matches.push("good_with_dogs".to_string());
+    }
+    if cmd.good_with_kids {
+        matches.push("good_with_kids".to_string());
+    }
+    if let Some(energies) = &cmd.energy {
+        matches.push(span_clause("energy", energies, &mut params));
+    }
+    if cmd.litter_trained {
+        matches.push("litter_trained".to_string());
+    }
+    if let Some(owners) = &cmd.owner {
+        params.extend(owners.iter().map(|&owner| Value::Integer(owner as i64)));
+        let placeholders = vec!["?"; owners.len()].join(", ");
+        matches.push(format!("owner_id IN ({})", placeholders));
+    }
+    for (kind, types) in [("intake", &cmd.intake), ("outcome", &cmd.outcome)] {
+        if let Some(types) = types {
+            params.push(Value::Text(kind.to_string()));
+            params.extend(
+                types
+                    .iter()
+                    .map(|event_type| Value::Text(event_type.as_str().to_string())),
+            );
+            matches.push(format!(
+                "EXISTS (SELECT 1 FROM events WHERE cat_id = cats.id AND kind = ? AND type IN ({}))",
+                vec!["?"; types.len()].join(", ")
+            ));
+        }
+    }
+    if let Some(locations) = &cmd.location {
+        params.extend(
+            locations
+                .iter()
+                .map(|location| Value::Text(location.clone())),
+        );
+        matches.push(format!(
+            "location_id IN (SELECT id FROM locations WHERE name IN ({}))",
+            vec!["?"; locations.len()].join(", ")
+        ));
+    }
+    if let Some(statuses) = &cmd.status {
+        params.extend(
+            statuses
+                .iter()
+                .map(|status| Value::Text(status.as_str().to_string())),
+        );
+        matches.push(format!(
+            "status IN ({})",
+            vec!["?"; statuses.len()].join(", ")
+        ));
+    }
+    if cmd.has_photo {
+        matches.push("EXISTS (SELECT 1 FROM photos WHERE cat_id = cats.id)".to_string());
+    }
+    if let Some(kittens) = &cmd.parent {
+        params.extend(kittens.iter().map(|&kitten| Value::Integer(kitten as i64)));
+        let placeholders = vec!["?"; kittens.len()].join(", ");
+        matches.push(format!(
+            "EXISTS (SELECT 1 FROM cats AS kitten WHERE kitten.id IN ({}) \
+            AND cats.id IN (kitten.mother_id, kitten.father_id))",
+            placeholders
+        ));
+    }
+    if let Some(parents) = &cmd.offspring_of {
+        params.extend(parents.iter().map(|&parent| Value::Integer(parent as i64)));
+        let placeholders = vec!["?"; parents.len()].join(", ");
+        matches.push(format!(
+            "EXISTS (SELECT 1 FROM cats AS parent WHERE parent.id IN ({}) \
+            AND parent.id IN (cats.mother_id, cats.father_id))",
+            placeholders
+        ));
+    }
+    if let Some(colors) = &cmd.color {
+        let clause = text_clause("color", colors, cmd, MatchMode::Exact, &mut params);
+        matches.push(clause);
+    }
+    if let Some(patterns) = &cmd.pattern {
+        let clause = text_clause("pattern", patterns, cmd, MatchMode::Exact, &mut params);
+        matches.push(clause);
+    }
+    if let Some(microchips) = &cmd.microchip {
+        params.extend(
+            microchips
+                .iter()
+                .map(|microchip| Value::Text(microchip.to_string())),
+        );
+        let placeholders = vec!["?"; microchips.len()].join(", ");
+        matches.push(format!("microchip IN ({})", placeholders));
+    }
+    if let Some(conditions) = &cmd.conditions {
+        for condition in conditions {
+            matches.push(condition_clause(condition, &mut params));
+        }
+    }
+    if let Some(terms) = &cmd.text {
+        let clause = TEXT_COLUMNS
+            .iter()
+            .map(|column| text_clause(column, terms, cmd, MatchMode::Contains, &mut params))
+            .join(" OR ");
+        matches.push(format!("({})", clause));
+    }
+    if let Some(query) = &cmd.query {
+        matches.push(query_clause(&query.expr, cmd, &mut params));
+    }
+    let mut clauses = Vec::new();
+    if !matches.is_empty() {
+        let operator = if cmd.any { " OR " } else { " AND " };
+        clauses.push(format!("({})", matches.join(operator)));
+    }
+    // Exclusions, recency, and the trash apply no matter how the rest is combined.
+    if let Some(names) = &cmd.not_name {
+        let clause = text_clause("name", names, cmd, MatchMode::Exact, &mut params);
+        clauses.push(format!("NOT {}", clause));
+    }
+    if let Some(ages) = &cmd.not_age {
+        let clause = age_clause(ages, &mut params);
+        clauses.push(format!("NOT {}", clause));
+    }
+    if cmd.not_fixed {
+        clauses.push("NOT fixed".to_string());
+    }
+    if let Some(breeds) = &cmd.not_breed {
+        // A cat without a breed isn't any of the excluded breeds, so it stays in.
+        let clause = text_clause("breed", breeds, cmd, MatchMode::Exact, &mut params);
+        clauses.push(format!("(breed ISNULL OR NOT {})", clause));
+    }
+    // Timestamps are stored the way `datetime` writes them, so they compare correctly as text.
+    if let Some(since) = &cmd.added_since {
+        params.push(Value::Text(since.to_string()));
+        clauses.push("created_at >= ?".to_string());
+    }
+    if let Some(since) = &cmd.updated_since {
+        params.push(Value::Text(since.to_string()));
+        clauses.push("updated_at >= ?".to_string());
+    }
+    if let Some(within) = cmd.added_within {
+        params.push(Value::Text(within.modifier()));
+        clauses.push("created_at >= datetime('now', ?)".to_string());
+    }
+    if let Some(within) = cmd.updated_within {
+        params.push(Value::Text(within.modifier()));
+        clauses.push("updated_at >= datetime('now', ?)".to_string());
+    }
+    if cmd.deceased_only {
+        clauses.push("deceased_at NOTNULL".to_string());
+    } else if !cmd.include_deceased {
+        clauses.push("deceased_at ISNULL".to_string());
+    }
+    clauses.push(
+        if cmd.deleted {
+            "deleted_at NOTNULL"
+        } else {
+            "deleted_at ISNULL"
+        }
+        .to_string(),
+    );
+    (clauses.join(" AND "), params)
+}
+
+/// Matches `column` against any of `spans`, each of which is either an exact value or a range.
+fn span_clause<T>(column: &str, spans: &[Span<T>], params: &mut Vec<Value>) -> String
+where
+    T: Copy + Into<u64>,
+{
+    let integer = |value: &T| Value::Integer((*value).into() as i64);
+    let clause = spans
+        .iter()
+        .map(|span| match span {
+            Span::Concrete(value) => {
+                params.push(integer(value));
+                format!("{} = ?", column)
+            }
+            Span::Range(range) => {
+                params.push(integer(range.start()));
+                params.push(integer(range.end()));
+                format!("{} BETWEEN ? AND ?", column)
+            }
+        })
+        .join(" OR ");
+    format!("({})", clause)
+}
+
+/// Matches cats whose age is in any of `ages`.
+///
+/// Cats with a birthdate are matched by the range of dates they would have been born in instead.
+fn age_clause(ages: &[Age], params: &mut Vec<Value>) -> String {
+    let clause = ages
+        .iter()
+        .map(|age| {
+            let (min, max) = match age {
+                Span::Concrete(age) => (*age, *age),
+                Span::Range(range) => (*range.start(), *range.end()),
+            };
+            params.push(Value::Integer(min.into()));
+            params.push(Value::Integer(max.into()));
+            params.push(Value::Text(format!("-{} years", min)));
+            let mut clause = String::from(
+                "(birthdate ISNULL AND age BETWEEN ? AND ? OR birthdate <= date('now', ?)",
+            );
+            // SQLite's dates stop at year 0, so nobody can be older than that anyway.
+            if max < 9999 {
+                params.push(Value::Text(format!("-{} years", max + 1)));
+                clause.push_str(" AND birthdate > date('now', ?)");
+            }
+            clause.push(')');
+            clause
+        })
+        .join(" OR ");
+    format!("({})", clause)
+}
+
+/// Compiles a `--query` into a clause, matching names and breeds the same way the other options do.
+fn query_clause(expr: &Expr, cmd: &CmdFind, params: &mut Vec<Value>) -> String {
+    match expr {
+        Expr::And(left, right) => format!(
+            "({} AND {})",
+            query_clause(left, cmd, params),
+            query_clause(right, cmd, params)
+        ),
+        Expr::Or(left, right) => format!(
+            "({} OR {})",
+            query_clause(left, cmd, params),
+            query_clause(right, cmd, params)
+        ),
+        Expr::Not(expr) => format!("NOT {}", query_clause(expr, cmd, params)),
+        Expr::Name(name) => {
+            text_clause("name", slice::from_ref(name), cmd, MatchMode::Exact, params)
+        }
+        // Without the NOTNULL, `NOT breed:tabby` would leave out cats without a breed.
+        Expr::Breed(breed) => format!(
+            "(breed NOTNULL AND {})",
+            text_clause(
+                "breed",
+                slice::from_ref(breed),
+                cmd,
+                MatchMode::Exact,
+                params
+            )
+        ),
+        Expr::Age(age) => age_clause(slice::from_ref(age), params),
+        // Comparisons become ranges, so that they work with birthdates too.
+        Expr::AgeCompare(comparison, age) => {
+            let range = match comparison {
+                Comparison::Less if *age == 0 => return "0".to_string(),
+                Comparison::Less => 0..=age - 1,
+                Comparison::LessOrEqual => 0..=*age,
+                Comparison::Greater => age.saturating_add(1)..=u32::MAX,
+                Comparison::GreaterOrEqual => *age..=u32::MAX,
+            };
+            age_clause(&[Span::Range(range)], params)
+        }
+    }
+}
+
+/// Builds the `ORDER BY` clause for a search. Ties are broken by ID, so results are stable.
+pub fn order(cmd: &CmdFind) -> String {
+    if cmd.oldest.is_some() {
+        return format!("ORDER BY {} DESC, id", AGE);
+    } else if cmd.youngest.is_some() {
+        return format!("ORDER BY {} ASC, id", AGE);
+    } else if cmd.newest.is_some() {
+        // Cats that were added before timestamps were kept have none, and are sorted by ID instead.
+        return "ORDER BY created_at DESC, id DESC".to_string();
+    }
+    let direction = if cmd.desc { "DESC" } else { "ASC" };
+    match cmd.sort.unwrap_or(SortField::Id) {
+        SortField::Id => format!("ORDER BY id {}", direction),
+        SortField::Age => format!("ORDER BY {} {}, id", AGE, direction),
+        field => format!("ORDER BY {} {}, id", field.column(), direction),
+    }
+}
+
+/// Builds the `LIMIT` clause for a search, if it has one, adding its parameters to `params`.
+pub fn limit(cmd: &CmdFind, params: &mut Vec<Value>) -> String {
+    let limit = cmd.oldest.or(cmd.youngest).or(cmd.newest).or(cmd.limit);
+    let offset = match (cmd.page, limit) {
+        (Some(page), Some(limit)) => Some(page.saturating_sub(1).saturating_mul(limit)),
+        _ => cmd.offset,
+    };
+    if limit.is_none() && offset.is_none() {
+        return String::new();
+    }
+    // SQLite only allows an offset after a limit, where a negative limit means there isn't one.
+    params.push(Value::Integer(limit.map_or(-1, |limit| limit as i64)));
+    params.push(Value::Integer(offset.unwrap_or(0) as i64));
+    " LIMIT ? OFFSET ?".to_string()
+}
+
+/// Matches a text column against any of `values`, either as a regex, by edit distance, or as a
+/// `LIKE` or `GLOB` pattern.
+///
+/// `default` is how to match when neither `--match` nor `--fuzzy` say otherwise.
+fn text_clause(
+    column: &str,
+    values: &[String],
+    cmd: &CmdFind,
+    default: MatchMode,
+    params: &mut Vec<Value>,
+) -> String {
+    if cmd.regex {
+        params.extend(values.iter().cloned().map(Value::Text));
+        let regexp = format!("{} REGEXP ?", column);
+        format!("({})", vec![regexp; values.len()].join(" OR "))
+    } else if let Some(distance) = cmd.fuzzy_distance {
+        for value in values {
+            params.push(Value::Text(value.clone()));
+            params.push(Value::Integer(distance.into()));
+        }
+        let close = format!("levenshtein({}, ?) <= ?", column);
+        format!("({})", vec![close; values.len()].join(" OR "))
+    } else if cmd.case_sensitive {
+        // Unlike `LIKE`, `GLOB` is always case sensitive.
+        params.extend(
+            values
+                .iter()
+                .map(|value| Value::Text(glob_pattern(&pattern(value, cmd, default)))),
+        );
+        let glob = format!("{} GLOB ?", column);
+        format!("({})", vec![glob; values.len()].join(" OR "))
+    } else {
+        params.extend(
+            values
+                .iter()
+                .map(|value| Value::Text(like_pattern(&pattern(value, cmd, default)))),
+        );
+        let like = format!("{} LIKE ? ESCAPE '\\'", column);
+        format!("({})", vec![like; values.len()].join(" OR "))
+    }
+}
+
+/// A piece of a pattern, which can be written out for either `LIKE` or `GLOB`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Piece {
+    /// Any number of characters, including none.
+    Any,
+    /// Exactly one character.
+    One,
+    Char(char),
+}
+
+/// Builds the pattern for a name or breed, according to `--glob`, `--match`, or `--fuzzy`.
+fn pattern(value: &str, cmd: &CmdFind, default: MatchMode) -> Vec<Piece> {
+    if cmd.glob {
+        return value
+            .chars()
+            .map(|c| match c {
+                '*' => Piece::Any,
+                '?' => Piece::One,
+                c => Piece::Char(c),
+            })
+            .collect();
+    }
+    let mode = cmd.match_mode.unwrap_or(if cmd.fuzzy {
+        MatchMode::Contains
+    } else {
+        default
+    });
+    let leading = matches!(mode, MatchMode::Suffix | MatchMode::Contains).then_some(Piece::Any);
+    let trailing = matches!(mode, MatchMode::Prefix | MatchMode::Contains).then_some(Piece::Any);
+    leading
+        .into_iter()
+        .chain(value.chars().map(Piece::Char))
+        .chain(trailing)
+        .collect()
+}
+
+/// A regex that matches the same names or breeds as a filter, for highlighting them, or `None` if
+/// there isn't one, like for `--fuzzy-distance`.
+pub fn matcher(value: &str, cmd: &CmdFind, default: MatchMode) -> Option<Regex> {
+    if cmd.fuzzy_distance.is_some() {
+        return None;
+    }
+    if cmd.regex {
+        return Regex::new(value).ok();
+    }
+    let mut regex = String::from(if cmd.case_sensitive { "^" } else { "(?i)^" });
+    for piece in pattern(value, cmd, default) {
+        match piece {
+            Piece::Any => regex.push_str(".*"),
+            Piece::One => regex.push('.'),
+            Piece::Char(c) => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).ok()
+}
+
+/// Writes a pattern for `LIKE ? ESCAPE '\'`, escaping the characters it would treat as wildcards.
+fn like_pattern(pattern: &[Piece]) -> String {
+    let mut like = String::new();
+    for piece in pattern {
+        match piece {
+            Piece::Any => like.push('%'),
+            Piece::One => like.push('_'),
+            Piece::Char(c) => {
+                if matches!(c, '%' | '_' | '\\') {
+                    like.push('\\');
+                }
+                like.push(*c);
+            }
+        }
+    }
+    like
+}
+
+/// Writes a pattern for `GLOB`, which has no escape character, so wildcards go in brackets instead.
+fn glob_pattern(pattern: &[Piece]) -> String {
+    let mut glob = String::new();
+    for piece in pattern {
+        match piece {
+            Piece::Any => glob.push('*'),
+            Piece::One => glob.push('?'),
+            Piece::Char(c @ ('*' | '?' | '[')) => {
+                glob.push('[');
+                glob.push(*c);
+                glob.push(']');
+            }
+            Piece::Char(c) => glob.push(*c),
+        }
+    }
+    glob
+}
+
+/// Whether `text` matches a `LIKE` pattern, ignoring case in every script rather than just ASCII.
+fn like(pattern: &str, text: &str, escape: Option<char>) -> bool {
+    let mut pieces = Vec::new();
+    let mut chars = pattern
+        .to_lowercase()
+        .chars()
+        .collect::<Vec<_>>()
+        .into_iter();
+    while let Some(c) = chars.next() {
+        pieces.push(match c {
+            c if Some(c) == escape => Piece::Char(chars.next().unwrap_or(c)),
+            '%' => Piece::Any,
+            '_' => Piece::One,
+            c => Piece::Char(c),
+        });
+    }
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    // Each `%` only needs to be backtracked to if everything after it fails to match.
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pieces.get(p) {
+            Some(Piece::Any) => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(Piece::One) => {
+                p += 1;
+                t += 1;
+            }
+            Some(Piece::Char(c)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((any, start)) => {
+                    backtrack = Some((any, start + 1));
+                    p = any + 1;
+                    t = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pieces[p..].iter().all(|piece| *piece == Piece::Any)
+}
+
+/// Defines the SQL functions SQLite leaves to the application, like the one behind `REGEXP`.
+///
+/// `LIKE` is replaced too, since SQLite's own only ignores the case of ASCII letters.
+pub fn add_functions(conn: &Connection) -> Result<()> {
+    for args in [2, 3] {
+        conn.create_scalar_function(
+            "like",
+            args,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let escape = if ctx.len() == 3 {
+                    let escape = ctx.get::<String>(2)?;
+                    let mut chars = escape.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(escape), None) => Some(escape),
+                        _ => {
+                            return Err(rusqlite::Error::UserFunctionError(
+                                "ESCAPE expression must be a single character".into(),
+                            ))
+                        }
+                    }
+                } else {
+                    None
+                };
+                Ok(match (ctx.get_raw(0), ctx.get_raw(1)) {
+                    (ValueRef::Text(pattern), ValueRef::Text(text)) => Some(like(
+                        &String::from_utf8_lossy(pattern),
+                        &String::from_utf8_lossy(text),
+                        escape,
+                    )),
+                    _ => None,
+                })
+            },
+        )?;
+    }
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            // The pattern is the same for every row, so it is only compiled once.
+            let regex = ctx.get_or_create_aux(0, |pattern| -> Result<_, BoxError> {
+                Ok(Regex::new(pattern.as_str()?)?)
+            })?;
+            Ok(match ctx.get_raw(1) {
+                ValueRef::Text(text) => regex.is_match(&String::from_utf8_lossy(text)),
+                _ => false,
+            })
+        },
+    )?;
+    conn.create_scalar_function(
+        "levenshtein",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            Ok(match (ctx.get_raw(0), ctx.get_raw(1)) {
+                (ValueRef::Text(a), ValueRef::Text(b)) => Some(levenshtein(
+                    &String::from_utf8_lossy(a),
+                    &String::from_utf8_lossy(b),
+                ) as i64),
+                _ => None,
+            })
+        },
+    )?;
+    Ok(())
+}
+
+/// The number of characters that have to be inserted, removed, or replaced to turn one string into
+/// the other, ignoring case.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<_>>();
+    let b = b.to_lowercase().chars().collect::<Vec<_>>();
+    // Only the previous row of the table is needed to work out the next one.
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a != b);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Inserts a copy of `cat`, either with the same ID or with a new one.
+///
+/// The copy keeps the timestamps of `cat`, if it has them, since it is the same cat.
+pub fn insert(conn: &Connection, cat: &Cat, keep_id: bool) -> Result<Cat> {
+    check_microchip(conn, cat.microchip.as_deref(), keep_id.then_some(cat.id))?;
+    Ok(conn
+        .prepare_cached(
+            "INSERT INTO cats (id, name, age, breed, birthdate, weight, sex, fixed, color, \
+            pattern, microchip, notes, owner_id, mother_id, father_id, litter_id, status, \
+            adopted_at, deceased_at, location_id, good_with_dogs, good_with_kids, energy, \
+            litter_trained, diet, license_number, license_expires, created_at, updated_at) VALUES \
+            (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+            ifnull(?, datetime('now')), \
+            ifnull(?, datetime('now'))) RETURNING *",
+        )?
+        .query_row(
+            params![
+                keep_id.then_some(cat.id),
+                cat.name,
+                cat.age,
+                cat.breed,
+                cat.birthdate,
+                cat.weight,
+                cat.sex.as_str(),
+                cat.fixed,
+                cat.color,
+                cat.pattern,
+                cat.microchip,
+                cat.notes,
+                cat.owner_id,
+                cat.mother_id,
+                cat.father_id,
+                cat.litter_id,
+                cat.status.as_str(),
+                cat.adopted_at,
+                cat.deceased_at,
+                cat.location_id,
+                cat.good_with_dogs,
+                cat.good_with_kids,
+                cat.energy,
+                cat.litter_trained,
+                cat.diet,
+                cat.license_number,
+                cat.license_expires,
+                cat.created_at,
+                cat.updated_at
+            ],
+            Cat::from_row,
+        )?)
+}
+
+/// Replaces every field of the cat with the same ID as `cat`.
+pub fn overwrite(conn: &Connection, cat: &Cat) -> Result<Option<Cat>> {
+    check_microchip(conn, cat.microchip.as_deref(), Some(cat.id))?;
+    Ok(conn
+        .prepare_cached(
+            "UPDATE cats SET name = ?, age = ?, breed = ?, birthdate = ?, weight = ?, sex = ?, \
+            fixed = ?, color = ?, pattern = ?, microchip = ?, notes = ?, owner_id = ?, \
+            mother_id = ?, father_id = ?, litter_id = ?, status = ?, adopted_at = ?, \
+            deceased_at = ?, location_id = ?, good_with_dogs = ?, good_with_kids = ?, energy = ?, \
+            litter_trained = ?, diet = ?, license_number = ?, license_expires = ?, \
+            deleted_at = NULL, updated_at = datetime('now') WHERE id = ? RETURNING *",
+        )?
+        .query_row(
+            params![
+                cat.name,
+                cat.age,
+                cat.breed,
+                cat.birthdate,
+                cat.weight,
+                cat.sex.as_str(),
+                cat.fixed,
+                cat.color,
+                cat.pattern,
+                cat.microchip,
+                cat.notes,
+                cat.owner_id,
+                cat.mother_id,
+                cat.father_id,
+                cat.litter_id,
+                cat.status.as_str(),
+                cat.adopted_at,
+                cat.deceased_at,
+                cat.location_id,
+                cat.good_with_dogs,
+                cat.good_with_kids,
+                cat.energy,
+                cat.litter_trained,
+                cat.diet,
+                cat.license_number,
+                cat.license_expires,
+                cat.id
+            ],
+            Cat::from_row,
+        )
+        .optional()?)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Add,
+    Update,
+    Delete,
+    Restore,
+    Purge,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+            Self::Purge => "purge",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "add" => Ok(Self::Add),
+            "update" => Ok(Self::Update),
+            "delete" => Ok(Self::Delete),
+            "restore" => Ok(Self::Restore),
+            "purge" => Ok(Self::Purge),
+            _ => bail!("Unknown history action `{}`", s),
+        }
+    }
+}
+
+/// Writes an entry to the audit log. Call this in the same savepoint as the change itself.
+pub fn record(
+    conn: &Connection,
+    action: Action,
+    old: Option<&Cat>,
+    new: Option<&Cat>,
+) -> Result<()> {
+    record_entry(conn, action, old, new, None)?;
+    Ok(())
+}
+
+pub fn record_entry(
+    conn: &Connection,
+    action: Action,
+    old: Option<&Cat>,
+    new: Option<&Cat>,
+    undoes: Option<u64>,
+) -> Result<u64> {
+    let cat_id = old.or(new).map(|cat| cat.id);
+    let old = old.map(serde_json::to_string).transpose()?;
+    let new = new.map(serde_json::to_string).transpose()?;
+    Ok(conn
+        .prepare_cached(
+            "INSERT INTO cat_history (cat_id, action, old, new, undoes) VALUES (?, ?, ?, ?, ?) \
+            RETURNING id",
+        )?
+        .query_row(params![cat_id, action.as_str(), old, new, undoes], |row| {
+            row.get(0)
+        })?)
+}
+
+/// Runs `f` inside a savepoint, so that either all of its changes are applied or none are.
+///
+/// If another process has the registry locked, the whole thing is retried, unless this is part of
+/// a bigger transaction. Then only whoever started that can retry it, since everything before
+/// this would otherwise be lost or done twice.
+pub fn atomic<T>(conn: &Connection, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+    let nested = !conn.is_autocommit();
+    let mut attempt = || {
+        conn.execute_batch("SAVEPOINT atomic")?;
+        // The outermost `RELEASE` commits, which can fail too, and then the savepoint is still open.
+        let res = f().and_then(|t| {
+            conn.execute_batch("RELEASE atomic")?;
+            Ok(t)
+        });
+        if res.is_err() {
+            conn.execute_batch("ROLLBACK TO atomic; RELEASE atomic")?;
+        }
+        res
+    };
+    if nested {
+        attempt()
+    } else {
+        retry(attempt)
+    }
+}
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+const RETRIES: u32 = 5;
+
+/// Runs `f` until it stops failing because the registry is locked, backing off between attempts.
+///
+/// The connection's busy timeout covers most contention, but SQLite gives up immediately when
+/// waiting could deadlock, e.g. when two readers both try to start writing.
+fn retry<T>(mut f: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(e) if is_busy(&e) => {
+                if attempt == RETRIES {
+                    return Err(
+                        e.context("The registry is in use by another process, try again later")
+                    );
+                }
+                attempt += 1;
+                thread::sleep(Duration::from_millis(50 << attempt));
+            }
+            res => return res,
+        }
+    }
+}
+
+fn is_busy(e: &Error) -> bool {
+    matches!(
+        e.downcast_ref::<rusqlite::Error>(),
+        Some(rusqlite::Error::SqliteFailure(err, _))
+            if matches!(err.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::ffi;
+
+    fn busy() -> Error {
+        rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_BUSY), None).into()
+    }
+
+    #[test]
+    fn atomic_rolls_back_when_the_commit_fails() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+            CREATE TABLE parents (id INTEGER PRIMARY KEY);
+            CREATE TABLE kittens (
+                parent_id INTEGER REFERENCES parents (id) DEFERRABLE INITIALLY DEFERRED)",
+        )
+        .unwrap();
+        // The missing parent is only noticed by `RELEASE`, the same as a commit that's busy.
+        let res = atomic(&conn, || {
+            Ok(conn.execute("INSERT INTO kittens VALUES (1)", [])?)
+        });
+        assert!(res.is_err());
+        assert!(conn.is_autocommit());
+        let kittens: u32 = conn
+            .query_row("SELECT count(*) FROM kittens", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kittens, 0);
+    }
+
+    #[test]
+    fn atomic_only_retries_outside_a_transaction() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut calls = 0;
+        let res = atomic(&conn, || {
+            calls += 1;
+            if calls == 1 {
+                Err(busy())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(res.is_ok());
+        assert_eq!(calls, 2);
+        conn.execute_batch("BEGIN").unwrap();
+        calls = 0;
+        assert!(atomic(&conn, || -> Result<()> {
+            calls += 1;
+            Err(busy())
+        })
+        .is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,128 @@
+// Templates for `--template`, which render each result however the user likes.
+// They're Handlebars templates, so besides `{{field}}` there are `{{#if}}`, `{{#each}}`, and the
+// rest of what Handlebars has.
+
+use anyhow::{Context, Result};
+use handlebars::{handlebars_helper, html_escape, no_escape, Handlebars};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Write};
+use std::process;
+
+const NAME: &str = "template";
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    registry: Handlebars<'static>,
+    /// Whether each rendering needs a line break after it, which a template file usually has already.
+    newline: bool,
+}
+
+handlebars_helper!(escape: |text: str| html_escape(text));
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut registry = Handlebars::new();
+        // The output is usually for a terminal, so `{{escape field}}` is only used when it's HTML.
+        registry.register_escape_fn(no_escape);
+        registry.register_helper("escape", Box::new(escape));
+        registry
+            .register_template_string(NAME, template)
+            .context("Invalid template")?;
+        Ok(Self {
+            registry,
+            newline: !template.ends_with('\n'),
+        })
+    }
+
+    /// Parses a template from the command line, where `\n` and `\t` stand for a line break and a
+    /// tab, since shells make them awkward to type.
+    pub fn inline(template: &str) -> Result<Self> {
+        Self::parse(&template.replace("\\n", "\n").replace("\\t", "\t"))
+    }
+
+    /// Renders the template once for each result in `value`, or once for `value` if it is a single
+    /// result.
+    ///
+    /// Fields that are unknown or have no value render as nothing. Some mistakes, like using a
+    /// helper that doesn't exist, only show up here, and end the program like any other error.
+    pub fn render<T: Serialize>(&self, value: &T) {
+        let stdout = io::stdout();
+        if let Err(e) = self.render_to(value, &mut stdout.lock()) {
+            eprintln!("{:#}", e);
+            process::exit(-1);
+        }
+    }
+
+    fn render_to<T: Serialize>(&self, value: &T, out: &mut impl Write) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        let results = match &value {
+            Value::Array(results) => &results[..],
+            value => std::slice::from_ref(value),
+        };
+        for result in results {
+            let rendered = self
+                .registry
+                .render(NAME, result)
+                .context("Couldn't render template")?;
+            out.write_all(rendered.as_bytes())?;
+            if self.newline {
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &Template, value: Value) -> String {
+        let mut out = Vec::new();
+        template.render_to(&value, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn each_result_gets_a_line() {
+        let template =
+            Template::inline("{{name}} ({{fields.floof-level}}){{#if fixed}} fixed{{/if}}")
+                .unwrap();
+        let cats = json!([
+            {"name": "Tom", "fixed": true, "fields": {"floof-level": 11}},
+            {"name": "Kit", "fixed": false, "fields": {}},
+        ]);
+        assert_eq!(render(&template, cats), "Tom (11) fixed\nKit ()\n");
+    }
+
+    #[test]
+    fn only_escapes_when_asked() {
+        let template = Template::parse("{{breed}} {{escape breed}}").unwrap();
+        assert_eq!(
+            render(&template, json!({"breed": "<b>"})),
+            "<b> &lt;b&gt;\n"
+        );
+    }
+
+    #[test]
+    fn inline_line_breaks_replace_the_newline() {
+        let template = Template::inline("{{name}}\\n").unwrap();
+        assert_eq!(
+            render(&template, json!([{"name": "Tom"}, {"name": "Kit"}])),
+            "Tom\nKit\n"
+        );
+        let template = Template::inline("{{name}}\\t").unwrap();
+        assert_eq!(render(&template, json!({"name": "Tom"})), "Tom\t\n");
+    }
+
+    #[test]
+    fn bad_templates_are_errors() {
+        assert!(Template::parse("{{#if}").is_err());
+        let template = Template::parse("{{nope name}}").unwrap();
+        assert!(template
+            .render_to(&json!({"name": "Tom"}), &mut Vec::new())
+            .is_err());
+    }
+}
@@ -0,0 +1,548 @@
+// Drawing images in the terminal, for the terminals that have a way to, coloring tables, and
+// sending stdout through a pager or to a file.
+
+use crate::args::TableStyle;
+use prettytable::format::consts;
+use prettytable::{color, Attr, Cell, Table};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::raw::{c_int, c_ulong};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process::{Child, Command, Stdio};
+
+/// How wide thumbnails are drawn, in columns. The height follows from the image.
+const THUMBNAIL_COLUMNS: u32 = 24;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Protocol {
+    /// Kitty's graphics protocol, which Ghostty and WezTerm also speak.
+    Kitty,
+    /// iTerm2's inline images, which WezTerm and VS Code also speak.
+    Iterm,
+}
+
+/// Works out which image protocol the terminal speaks, if it's a terminal at all.
+///
+/// Terminals don't reliably answer questions about this, so it goes by what they put in the
+/// environment.
+fn protocol() -> Option<Protocol> {
+    if !atty::is(atty::Stream::Stdout) {
+        return None;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || term == "xterm-ghostty"
+    {
+        return Some(Protocol::Kitty);
+    }
+    match env::var("TERM_PROGRAM").as_deref() {
+        Ok("iTerm.app") | Ok("WezTerm") | Ok("vscode") => Some(Protocol::Iterm),
+        _ => None,
+    }
+}
+
+/// Draws an image as a thumbnail on its own line, returning whether it could.
+///
+/// Kitty can only be sent PNGs without decoding them first, so other formats get `false` there
+/// and the caller shows something else. Sixel isn't spoken at all, since every image would need
+/// decoding, and that's more than cats wants to depend on for thumbnails.
+pub fn show_image(format: &str, data: &[u8]) -> io::Result<bool> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match protocol() {
+        Some(Protocol::Kitty) if format == "png" => {
+            // Kitty takes the image in pieces of at most 4096 bytes, each saying if more follow.
+            let encoded = base64::encode(data);
+            let chunks = encoded.as_bytes().chunks(4096).collect::<Vec<_>>();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                if i == 0 {
+                    write!(out, "\x1b_Gf=100,a=T,c={},m={};", THUMBNAIL_COLUMNS, more)?;
+                } else {
+                    write!(out, "\x1b_Gm={};", more)?;
+                }
+                out.write_all(chunk)?;
+                out.write_all(b"\x1b\\")?;
+            }
+        }
+        Some(Protocol::Iterm) => write!(
+            out,
+            "\x1b]1337;File=inline=1;size={};width={};preserveAspectRatio=1:{}\x07",
+            data.len(),
+            THUMBNAIL_COLUMNS,
+            base64::encode(data)
+        )?,
+        _ => return Ok(false),
+    }
+    writeln!(out)?;
+    Ok(true)
+}
+
+/// How wide the terminal is, in columns, if stdout is one.
+///
+/// `COLUMNS` takes precedence, so that it can be overridden.
+pub fn width() -> Option<usize> {
+    if !atty::is(atty::Stream::Stdout) {
+        return None;
+    }
+    match env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+    {
+        Some(columns) => Some(columns),
+        None => window_width(),
+    }
+}
+
+// std doesn't wrap these, but libc is linked either way, so they're declared here instead of adding
+// a dependency for them.
+#[cfg(windows)]
+extern "system" {
+    fn GetStdHandle(id: u32) -> RawHandle;
+    fn SetStdHandle(id: u32, handle: RawHandle) -> i32;
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    fn dup(fd: c_int) -> c_int;
+    fn dup2(fd: c_int, to: c_int) -> c_int;
+    fn close(fd: c_int) -> c_int;
+    fn signal(signal: c_int, handler: usize) -> usize;
+}
+
+#[cfg(unix)]
+fn window_width() -> Option<usize> {
+    #[repr(C)]
+    #[derive(Default)]
+    struct Winsize {
+        rows: u16,
+        columns: u16,
+        x_pixels: u16,
+        y_pixels: u16,
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+    let mut size = Winsize::default();
+    // SAFETY: TIOCGWINSZ only writes a `Winsize` through the pointer.
+    let result = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut Winsize) };
+    (result == 0 && size.columns > 0).then_some(size.columns as usize)
+}
+
+#[cfg(not(unix))]
+fn window_width() -> Option<usize> {
+    None
+}
+
+/// Where stdout went before it was sent somewhere else. Dropping it puts stdout back.
+#[cfg(unix)]
+struct SavedStdout(c_int);
+
+#[cfg(windows)]
+struct SavedStdout(RawHandle);
+
+#[cfg(windows)]
+const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+/// Sends stdout to `to` until the returned `SavedStdout` is dropped.
+#[cfg(unix)]
+fn replace_stdout(to: &impl AsRawFd) -> io::Result<SavedStdout> {
+    io::stdout().flush()?;
+    // SAFETY: these only copy file descriptors that are open, and the copy of stdout is only
+    // closed if it isn't going to be used.
+    unsafe {
+        let stdout = dup(1);
+        if stdout < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if dup2(to.as_raw_fd(), 1) < 0 {
+            let error = io::Error::last_os_error();
+            close(stdout);
+            return Err(error);
+        }
+        Ok(SavedStdout(stdout))
+    }
+}
+
+#[cfg(windows)]
+fn replace_stdout(to: &impl AsRawHandle) -> io::Result<SavedStdout> {
+    io::stdout().flush()?;
+    // SAFETY: std looks stdout up again on every write, so swapping the handle is all it takes.
+    unsafe {
+        let stdout = GetStdHandle(STD_OUTPUT_HANDLE);
+        if SetStdHandle(STD_OUTPUT_HANDLE, to.as_raw_handle()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SavedStdout(stdout))
+    }
+}
+
+impl Drop for SavedStdout {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        // SAFETY: the saved stdout is still open, and this is the only thing that closes it.
+        #[cfg(unix)]
+        unsafe {
+            dup2(self.0, 1);
+            close(self.0);
+        }
+        #[cfg(windows)]
+        unsafe {
+            SetStdHandle(STD_OUTPUT_HANDLE, self.0);
+        }
+    }
+}
+
+/// A file that stdout is being written to, for `--output`.
+///
+/// The file is only put in place by `finish`, so that it never holds half of a result. Dropping it
+/// without finishing throws away what was written.
+pub struct Output {
+    path: PathBuf,
+    tmp: PathBuf,
+    /// The file being written to, which has to be closed before it can be moved on Windows.
+    file: Option<File>,
+    stdout: Option<SavedStdout>,
+}
+
+/// Sends stdout to a file next to `path`, until it's finished.
+pub fn redirect(path: &Path) -> io::Result<Output> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let file = File::create(&tmp)?;
+    let stdout = match replace_stdout(&file) {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+    Ok(Output {
+        path: path.to_path_buf(),
+        tmp,
+        file: Some(file),
+        stdout: Some(stdout),
+    })
+}
+
+impl Output {
+    /// Puts stdout back, and the file in place.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stdout = None;
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+        }
+        fs::rename(&self.tmp, &self.path)
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        self.stdout = None;
+        self.file = None;
+        // Once the file has been put in place, there's nothing left to remove.
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+/// Output held back from stdout until it's released, for `batch`.
+pub struct Held {
+    path: PathBuf,
+    file: File,
+    stdout: Option<SavedStdout>,
+}
+
+/// Sends stdout to a temporary file until it's released. Dropping it throws the output away.
+pub fn hold() -> io::Result<Held> {
+    let path = env::temp_dir().join(format!("cats-{}.out", std::process::id()));
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    let stdout = match replace_stdout(&file) {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+    };
+    Ok(Held {
+        path,
+        file,
+        stdout: Some(stdout),
+    })
+}
+
+impl Held {
+    /// Puts stdout back, and writes everything that was held back to it.
+    pub fn release(mut self) -> io::Result<()> {
+        self.stdout = None;
+        self.file.seek(SeekFrom::Start(0))?;
+        io::copy(&mut self.file, &mut io::stdout().lock())?;
+        Ok(())
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        self.stdout = None;
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A pager that stdout is being sent through. Dropping it waits for the pager to be closed.
+#[cfg(unix)]
+pub struct Pager {
+    pager: Child,
+    stdout: Option<SavedStdout>,
+}
+
+/// Sends stdout through `$PAGER`, or `less`, if it's a terminal.
+///
+/// Like git, `less` is told to quit straight away if everything fits on one screen, and to let
+/// colors through. `PAGER=cat` turns paging off.
+#[cfg(unix)]
+pub fn page() -> Option<Pager> {
+    if !atty::is(atty::Stream::Stdout) {
+        return None;
+    }
+    let mut command = match env::var("PAGER") {
+        Ok(pager) if pager.is_empty() || pager == "cat" => return None,
+        // `PAGER` can have arguments, like `less -S`, so it's run by the shell.
+        Ok(pager) => {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(pager);
+            command
+        }
+        Err(_) => Command::new("less"),
+    };
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut pager = command.stdin(Stdio::piped()).spawn().ok()?;
+    let stdout = replace_stdout(&pager.stdin.take()?).ok()?;
+    // Quitting the pager early makes writing to it fail, which should end cats quietly rather than
+    // with a panic about a broken pipe.
+    const SIGPIPE: c_int = 13;
+    const SIG_DFL: usize = 0;
+    // SAFETY: nothing else handles signals.
+    unsafe {
+        signal(SIGPIPE, SIG_DFL);
+    }
+    Some(Pager {
+        pager,
+        stdout: Some(stdout),
+    })
+}
+
+#[cfg(not(unix))]
+pub struct Pager;
+
+#[cfg(not(unix))]
+pub fn page() -> Option<Pager> {
+    None
+}
+
+#[cfg(unix)]
+impl Drop for Pager {
+    fn drop(&mut self) {
+        // Putting stdout back closes the pager's end of it, which tells it that's everything.
+        self.stdout = None;
+        let _ = self.pager.wait();
+    }
+}
+
+/// How to draw tables, from `--color`, `--table-style`, and `--no-truncate`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Style {
+    pub color: bool,
+    pub table: TableStyle,
+    /// The width to fit tables in, or `None` to let them be as wide as they need.
+    pub width: Option<usize>,
+}
+
+/// Prints a table in the given style, with placeholders like `<none>` dimmed if it has color.
+pub fn print_table(table: Table, style: Style) {
+    print_highlighting(table, style, |_, _, _| false);
+}
+
+/// Prints a table like `print_table`, also highlighting the cells that `highlight` picks by their
+/// row, column, and text, like the ones that matched a search.
+pub fn print_highlighting(
+    mut table: Table,
+    style: Style,
+    highlight: impl Fn(usize, usize, &str) -> bool,
+) {
+    table.set_format(match style.table {
+        TableStyle::Ascii => *consts::FORMAT_DEFAULT,
+        TableStyle::Unicode => *consts::FORMAT_BOX_CHARS,
+        TableStyle::Borderless => *consts::FORMAT_CLEAN,
+        TableStyle::Compact => *consts::FORMAT_NO_LINESEP_WITH_TITLE,
+    });
+    // Cells are styled after they're fitted, since shortening them means replacing them, but what
+    // they say has to be looked at before anything is cut off.
+    let styles = table
+        .row_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, cell)| {
+                    let content = cell.get_content();
+                    if highlight(i, j, &content) {
+                        vec![Attr::Bold, Attr::ForegroundColor(color::YELLOW)]
+                    } else if content.starts_with('<') && content.ends_with('>') {
+                        vec![Attr::Dim]
+                    } else {
+                        vec![]
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if let Some(width) = style.width {
+        fit(&mut table, width);
+    }
+    if !style.color {
+        table.print(&mut io::stdout()).unwrap();
+        return;
+    }
+    for (row, styles) in table.row_iter_mut().zip(styles) {
+        for (cell, attrs) in row.iter_mut().zip(styles) {
+            for attr in attrs {
+                cell.style(attr);
+            }
+        }
+    }
+    table.print_tty(true);
+}
+
+/// How wide bar charts are drawn when it isn't going to a terminal, in columns.
+const CHART_WIDTH: usize = 80;
+
+/// Draws a horizontal bar chart, with a labelled bar for each count, scaled so that the longest
+/// fills the width.
+///
+/// Bars are drawn with block characters down to an eighth of a column, and any count above zero
+/// gets at least a sliver, so that it isn't mistaken for none.
+pub fn print_bars(bars: &[(&str, u64)], style: Style) {
+    const EIGHTHS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+    let label_width = bars
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let count_width = bars
+        .iter()
+        .map(|(_, count)| count.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let most = bars
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let bar_width = style
+        .width
+        .unwrap_or(CHART_WIDTH)
+        .saturating_sub(label_width + count_width + 4)
+        .max(MIN_CELL_WIDTH) as u64;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (label, count) in bars {
+        let eighths = (count * bar_width * 8 / most).max(u64::from(*count > 0));
+        let mut bar = "█".repeat((eighths / 8) as usize);
+        bar.push_str(EIGHTHS[(eighths % 8) as usize]);
+        if style.color && !bar.is_empty() {
+            bar = format!("\x1b[36m{}\x1b[0m", bar);
+        }
+        writeln!(
+            out,
+            "{:<width$} │ {} {}",
+            label,
+            bar,
+            count,
+            width = label_width,
+        )
+        .unwrap();
+    }
+}
+
+/// Cells are never cut shorter than this, even if the table still won't fit.
+const MIN_CELL_WIDTH: usize = 8;
+
+/// Shortens the table's longest cells with an ellipsis until it fits in `width` columns.
+///
+/// Every cell is cut to the same length, the longest one that fits, so short columns like IDs and
+/// dates are left alone while long names and notes give way.
+fn fit(table: &mut Table, width: usize) {
+    let rendered_width = |table: &Table| {
+        let mut rendered = Vec::new();
+        table.print(&mut rendered).unwrap();
+        let rendered = String::from_utf8_lossy(&rendered);
+        rendered
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    if rendered_width(table) <= width {
+        return;
+    }
+    let truncated = |limit: usize| {
+        let mut table = table.clone();
+        for row in table.row_iter_mut() {
+            for cell in row.iter_mut() {
+                let content = cell.get_content();
+                if content.lines().any(|line| line.chars().count() > limit) {
+                    let lines = content.lines().map(|line| truncate(line, limit));
+                    *cell = Cell::new(&lines.collect::<Vec<_>>().join("\n"));
+                }
+            }
+        }
+        table
+    };
+    let longest = table
+        .row_iter()
+        .flat_map(|row| row.iter())
+        .flat_map(|cell| {
+            let content = cell.get_content();
+            let longest = content.lines().map(|line| line.chars().count()).max();
+            longest
+        })
+        .max()
+        .unwrap_or(0);
+    // The longest limit that still fits, found by bisection since each try draws the whole table.
+    let (mut fits, mut too_wide) = (MIN_CELL_WIDTH, longest);
+    while fits + 1 < too_wide {
+        let limit = (fits + too_wide) / 2;
+        if rendered_width(&truncated(limit)) <= width {
+            fits = limit;
+        } else {
+            too_wide = limit;
+        }
+    }
+    *table = truncated(fits);
+}
+
+/// Cuts a line down to `limit` characters, ending it with an ellipsis if anything was cut.
+fn truncate(line: &str, limit: usize) -> String {
+    if line.chars().count() <= limit {
+        return line.to_string();
+    }
+    let mut truncated = line.chars().take(limit - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
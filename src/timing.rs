@@ -0,0 +1,66 @@
+// How long each part of a command took, for `--timing`.
+// The stores record their SQL as they run it, since that's where the time goes in large registries,
+// and the rest is measured around the command by main.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The SQL run so far.
+struct Sql {
+    statements: u32,
+    prepare: Duration,
+    execute: Duration,
+    rows: usize,
+}
+
+static SQL: Mutex<Sql> = Mutex::new(Sql {
+    statements: 0,
+    prepare: Duration::ZERO,
+    execute: Duration::ZERO,
+    rows: 0,
+});
+
+/// Runs a statement, recording how long `prepare` took to prepare it, and how long `execute` took to
+/// run it and how many rows it returned.
+pub fn query<S, T, E>(
+    prepare: impl FnOnce() -> Result<S, E>,
+    execute: impl FnOnce(S) -> Result<Vec<T>, E>,
+) -> Result<Vec<T>, E> {
+    let start = Instant::now();
+    let stmt = prepare()?;
+    let prepared = Instant::now();
+    let rows = execute(stmt)?;
+    record(prepared - start, prepared.elapsed(), rows.len());
+    Ok(rows)
+}
+
+/// Records a statement that has already run, for statements that are prepared and run in one go.
+pub fn record(prepare: Duration, execute: Duration, rows: usize) {
+    let mut sql = SQL.lock().unwrap();
+    sql.statements += 1;
+    sql.prepare += prepare;
+    sql.execute += execute;
+    sql.rows += rows;
+}
+
+/// Prints how long each part of the command took to stderr.
+///
+/// SQL is only broken down for the statements the stores ran, so for other commands it's only
+/// included in how long the command took.
+pub fn report(open: Duration, command: Duration, render: Duration) {
+    eprintln!("Opening the registry: {:.2?}", open);
+    eprintln!("Running the command: {:.2?}", command);
+    let sql = SQL.lock().unwrap();
+    if sql.statements > 0 {
+        eprintln!(
+            "  Preparing SQL: {:.2?}\n  Running SQL: {:.2?}, {} row{} from {} statement{}",
+            sql.prepare,
+            sql.execute,
+            sql.rows,
+            if sql.rows == 1 { "" } else { "s" },
+            sql.statements,
+            if sql.statements == 1 { "" } else { "s" },
+        );
+    }
+    eprintln!("Printing the result: {:.2?}", render);
+}